@@ -2,6 +2,7 @@ use crate::cli::{Cli, Commands};
 use crate::config::Config;
 use crate::state::StateStore;
 use anyhow::Result;
+use std::fmt;
 
 /// Application context containing config and state
 pub struct AppContext {
@@ -15,10 +16,23 @@ impl AppContext {
     }
 }
 
+/// Error that makes `dev` exit with a given code without printing anything,
+/// e.g. to pass through the exit code of a wrapped command
+#[derive(Debug)]
+pub struct ExitCode(pub i32);
+
+impl fmt::Display for ExitCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "exit code {}", self.0)
+    }
+}
+
+impl std::error::Error for ExitCode {}
+
 /// Central command dispatcher
 /// Routes commands to appropriate module handlers
 pub fn run(cli: Cli, mut ctx: AppContext) -> Result<()> {
-    match cli.command {
+    let result = match cli.command {
         Commands::Ctx(args) => {
             crate::modules::ctx::run(args, &mut ctx)
         }
@@ -43,10 +57,33 @@ pub fn run(cli: Cli, mut ctx: AppContext) -> Result<()> {
         Commands::Db(args) => {
             crate::modules::db::run(args, &mut ctx)
         }
+        Commands::Sync(args) => {
+            crate::modules::sync::run(args, &mut ctx)
+        }
+        Commands::Trust(args) => {
+            crate::modules::trust::run(args, &mut ctx)
+        }
+        Commands::Llm(args) => {
+            crate::modules::llm::run(args, &mut ctx)
+        }
+        Commands::Init(args) => {
+            crate::modules::init::run(args, &mut ctx)
+        }
         Commands::Config(_args) => {
             println!("config: Configuration management (placeholder - not yet implemented)");
             Ok(())
         }
+    };
+    
+    // The command's own error (or exit code) matters more than a failed close
+    match result {
+        Ok(()) => ctx.state.close(),
+        Err(e) => {
+            if let Err(close_error) = ctx.state.close() {
+                log::warn!("Failed to close state store: {:#}", close_error);
+            }
+            Err(e)
+        }
     }
 }
 
@@ -1,10 +1,21 @@
-use clap::{Parser, Subcommand};
+use crate::config::Multiplexer;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
 
 /// A stateful developer workflow CLI tool
 #[derive(Parser, Debug)]
 #[command(name = "dev")]
 #[command(about = "A stateful developer workflow CLI", long_about = None)]
 pub struct Cli {
+    /// Log more (-v info, -vv debug, -vvv trace); RUST_LOG overrides this
+    #[arg(short, long, action = clap::ArgAction::Count, conflicts_with = "quiet")]
+    pub verbose: u8,
+    /// Only log errors
+    #[arg(short, long)]
+    pub quiet: bool,
+    /// Append JSON traces of every LLM call and subprocess to this file
+    #[arg(long, value_name = "PATH")]
+    pub trace_file: Option<PathBuf>,
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -29,6 +40,92 @@ pub enum Commands {
     Secrets(SecretsArgs),
     /// Database management
     Db(DbArgs),
+    /// Sync contexts and snippets across machines via git
+    Sync(SyncArgs),
+    /// Manage trusted hooks and project commands
+    Trust(TrustArgs),
+    /// LLM usage, spend and the response cache
+    Llm(LlmArgs),
+    /// Print shell integration, e.g. `eval "$(dev init zsh)"`
+    Init(InitArgs),
+}
+
+#[derive(Parser, Debug)]
+pub struct InitArgs {
+    /// Shell to integrate with (detected from $SHELL by default)
+    #[arg(value_enum)]
+    pub shell: Option<InitShell>,
+    /// Also keep the stderr of failed commands for `dev wtf` (bash and zsh).
+    /// Stderr then stops being a terminal, so programs drop colours and progress output on it
+    #[arg(long)]
+    pub capture_stderr: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum InitShell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+#[derive(Parser, Debug)]
+pub struct LlmArgs {
+    #[command(subcommand)]
+    pub command: LlmCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum LlmCommand {
+    /// Show tokens and spend by command
+    Usage {
+        /// How far back to look, e.g. 24h, 7d or 4w
+        #[arg(long, default_value = "30d")]
+        since: String,
+    },
+    /// Delete every cached LLM reply
+    ClearCache,
+}
+
+#[derive(Parser, Debug)]
+pub struct TrustArgs {
+    #[command(subcommand)]
+    pub command: TrustCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum TrustCommand {
+    /// List trusted commands
+    List,
+    /// Trust every hook, service and pane command of a context
+    Allow {
+        /// Name of the context
+        context: String,
+    },
+    /// Revoke trust by hash prefix, or for every command of a context
+    Revoke {
+        /// Hash prefix of at least 8 characters (from `dev trust list`) or context name
+        target: String,
+    },
+}
+
+#[derive(Parser, Debug)]
+pub struct SyncArgs {
+    #[command(subcommand)]
+    pub command: SyncCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SyncCommand {
+    /// Create the sync repository and optionally set its remote
+    Init {
+        /// Git remote URL (e.g. a private repo or a bare repo on a shared host)
+        #[arg(long)]
+        remote: Option<String>,
+    },
+    /// Export local state, merge with the remote, import and push
+    Run,
+    /// Show the sync repository and last sync
+    Status,
 }
 
 #[derive(Parser, Debug)]
@@ -43,9 +140,14 @@ pub enum DocsCommand {
     Generate {
         /// File to document
         file: String,
+        #[command(flatten)]
+        llm: ProviderArgs,
     },
     /// Generate README.md from project structure
-    Readme,
+    Readme {
+        #[command(flatten)]
+        llm: ProviderArgs,
+    },
     /// Generate API documentation
     Api,
     /// Keep docs in sync with code
@@ -157,11 +259,26 @@ pub enum CtxCommand {
     Save {
         /// Name of the context
         name: String,
+        /// Attach a session layout from a TOML file
+        #[arg(long, value_name = "FILE")]
+        layout: Option<String>,
+        /// Record the current tmux session's windows and panes as the layout
+        #[arg(long, conflicts_with = "layout")]
+        capture_tmux: bool,
     },
     /// Switch to a saved context (outputs shell code)
     Switch {
         /// Name of the context
         name: String,
+        /// Create or attach a multiplexer session with the context's layout
+        #[arg(long)]
+        session: bool,
+        /// Multiplexer to use with --session (overrides config)
+        #[arg(long, value_enum, requires = "session")]
+        multiplexer: Option<Multiplexer>,
+        /// Stop the services of the context being switched away from
+        #[arg(long)]
+        stop_services: bool,
     },
     /// List all saved contexts
     List,
@@ -180,13 +297,197 @@ pub enum CtxCommand {
         /// Name of the context to compare against
         name: String,
     },
+    /// Start a context's services
+    Up {
+        /// Name of the context (defaults to the active context)
+        name: Option<String>,
+        /// Only start these services
+        #[arg(short, long)]
+        service: Vec<String>,
+    },
+    /// Stop a context's services
+    Down {
+        /// Name of the context (defaults to the active context)
+        name: Option<String>,
+        /// Only stop these services
+        #[arg(short, long)]
+        service: Vec<String>,
+    },
+    /// Show the state of a context's services
+    Status {
+        /// Name of the context (defaults to the active context)
+        name: Option<String>,
+    },
+    /// Declare or remove context services
+    Service(CtxServiceArgs),
+    /// Write a context to .dev/context.toml in its project for sharing
+    ExportProject {
+        /// Name of the context (defaults to the active context)
+        name: Option<String>,
+        /// Overwrite an existing .dev/context.toml that differs
+        #[arg(long)]
+        force: bool,
+    },
+    /// Import a project's .dev/context.toml (plus .dev/context.local.toml overrides)
+    ImportProject {
+        /// Project directory (defaults to the current directory)
+        #[arg(long)]
+        path: Option<String>,
+        /// Context name (defaults to the name in the file)
+        #[arg(long)]
+        name: Option<String>,
+    },
+}
+
+#[derive(Parser, Debug)]
+pub struct CtxServiceArgs {
+    #[command(subcommand)]
+    pub command: CtxServiceCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CtxServiceCommand {
+    /// Add or replace a service on a context
+    Add {
+        /// Name of the context
+        context: String,
+        /// Name of the service
+        name: String,
+        /// Command that runs the service in the foreground
+        #[arg(short, long)]
+        command: String,
+        /// Working directory, relative to the context's directory
+        #[arg(long)]
+        cwd: Option<String>,
+        /// Environment variables as key=value pairs
+        #[arg(short, long)]
+        env: Vec<String>,
+        /// Ready once this localhost port accepts connections
+        #[arg(long)]
+        port: Option<u16>,
+        /// Ready once the service log matches this regex
+        #[arg(long)]
+        log_regex: Option<String>,
+        /// Seconds to wait for readiness
+        #[arg(long, default_value_t = 30)]
+        timeout: u64,
+    },
+    /// Remove a service from a context
+    Rm {
+        /// Name of the context
+        context: String,
+        /// Name of the service
+        name: String,
+    },
 }
 
 #[derive(Parser, Debug)]
+#[command(args_conflicts_with_subcommands = true)]
 pub struct WtfArgs {
-    /// Error text to explain
-    #[arg(required = true)]
-    pub error_text: String,
+    #[command(subcommand)]
+    pub action: Option<WtfCommand>,
+    /// Error text to explain (read from stdin when piped)
+    pub error_text: Option<String>,
+    /// Run this command and explain its output if it fails, e.g. `dev wtf -- cargo build`
+    #[arg(last = true)]
+    pub command: Vec<String>,
+    /// Neither read nor write the explanation cache
+    #[arg(long, conflicts_with = "refresh")]
+    pub no_cache: bool,
+    /// Ignore a cached explanation and replace it with a new one
+    #[arg(long)]
+    pub refresh: bool,
+    /// Source lines either side of the error to include in the LLM prompt (0 to send none)
+    #[arg(long, default_value_t = 5)]
+    pub context_lines: usize,
+    /// Offer to run the suggested commands and apply the suggested patches
+    #[arg(long)]
+    pub apply: bool,
+    #[command(flatten)]
+    pub llm: ProviderArgs,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum WtfCommand {
+    /// Manage the offline rule packs
+    Rules(WtfRulesArgs),
+    /// List cached explanations, most recently used first
+    History {
+        /// Number of entries to show
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+        /// Show the actions applied with `--apply` instead
+        #[arg(long)]
+        applied: bool,
+    },
+    /// Delete a cached explanation
+    Forget {
+        /// Fingerprint or unique prefix (from `dev wtf history`)
+        fingerprint: String,
+    },
+    /// Rate a cached explanation, adjusting its confidence
+    Rate {
+        #[arg(value_enum)]
+        rating: Rating,
+        /// Fingerprint or unique prefix (defaults to the last explanation shown)
+        fingerprint: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Rating {
+    Up,
+    Down,
+}
+
+#[derive(Parser, Debug)]
+pub struct WtfRulesArgs {
+    #[command(subcommand)]
+    pub command: WtfRulesCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum WtfRulesCommand {
+    /// List the bundled and user rules
+    List {
+        /// Only rules for this tool or language (e.g. rust, tsc, python)
+        #[arg(long)]
+        scope: Option<String>,
+    },
+    /// Show which rules match some error output (read from stdin when piped)
+    Test {
+        /// Error text to test
+        text: Option<String>,
+    },
+    /// Add a rule to a user pack in ~/.dev/wtf-rules
+    Add {
+        /// Unique rule id; an existing rule with this id is replaced
+        id: String,
+        /// Regex with named captures, e.g. "No module named '(?P<module>[^']+)'"
+        #[arg(long)]
+        pattern: String,
+        /// Cause template, may use captures as {name}
+        #[arg(long)]
+        cause: String,
+        /// Fix template, may use captures as {name}
+        #[arg(long)]
+        fix: String,
+        /// Tool or language the rule applies to
+        #[arg(long)]
+        scope: Option<String>,
+        /// Documentation link (repeatable)
+        #[arg(long = "link")]
+        links: Vec<String>,
+        /// Command `dev wtf --apply` offers to run; may use `{name}` captures (repeatable)
+        #[arg(long = "command")]
+        commands: Vec<String>,
+        /// Confidence between 0 and 1
+        #[arg(long, default_value_t = 0.6)]
+        confidence: f64,
+        /// Pack file to add the rule to (~/.dev/wtf-rules/<pack>.toml)
+        #[arg(long, default_value = "custom")]
+        pack: String,
+    },
 }
 
 #[derive(Parser, Debug)]
@@ -202,27 +503,178 @@ pub enum SnipCommand {
         /// Name of the snippet
         name: String,
         /// Command template (use {var} for variables)
-        command: String,
+        #[arg(required_unless_present = "file")]
+        command: Option<String>,
+        /// Add a runbook of steps from a TOML, YAML or JSON file instead
+        #[arg(long, value_name = "FILE", conflicts_with = "command")]
+        file: Option<String>,
         /// Tags (comma-separated)
         #[arg(short, long)]
         tags: Option<String>,
+        /// What the snippet does
+        #[arg(short, long)]
+        description: Option<String>,
+        /// Replace an existing snippet with the same name
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Show a snippet's command and tags
+    Show {
+        /// Name of the snippet
+        name: String,
+    },
+    /// Open a snippet's command in $EDITOR
+    Edit {
+        /// Name of the snippet
+        name: String,
+        /// Only set the description (empty to remove it)
+        #[arg(short, long)]
+        description: Option<String>,
+    },
+    /// Rename a snippet
+    Rename {
+        /// Current name
+        name: String,
+        /// New name
+        new_name: String,
+        /// Replace an existing snippet with the new name
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Duplicate a snippet under a new name
+    Copy {
+        /// Name of the snippet to copy
+        name: String,
+        /// Name of the copy
+        new_name: String,
+        /// Replace an existing snippet with the new name
+        #[arg(short, long)]
+        force: bool,
     },
+    /// Delete a snippet
+    Rm {
+        /// Name of the snippet
+        name: String,
+    },
+    /// Add or remove a snippet's tags
+    Tag(SnipTagArgs),
     /// List all snippets
     List {
         /// Filter by tag
         #[arg(short, long)]
         tag: Option<String>,
     },
+    /// Fuzzy-find a snippet, with a preview, and run it
+    Pick {
+        /// Initial search text
+        query: Option<String>,
+        /// Print the filled-in command instead of running it (for the shell widget)
+        #[arg(long)]
+        print: bool,
+    },
     /// Run a snippet
     Run {
         /// Name of the snippet
-        name: String,
+        #[arg(required_unless_present = "file")]
+        name: Option<String>,
+        /// Run a runbook from a TOML, YAML or JSON file instead of a saved snippet
+        #[arg(long, value_name = "FILE", conflicts_with = "name")]
+        file: Option<String>,
         /// Variables as key=value pairs
         #[arg(short, long)]
         vars: Vec<String>,
         /// Dry-run mode (don't execute, just show command)
         #[arg(long)]
         dry_run: bool,
+        /// Run runbook steps marked `confirm` without asking
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Show past runs, newest first
+    History {
+        /// Only runs of this snippet
+        name: Option<String>,
+        /// Number of runs to show
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// Import snippets from shell history or a project's Makefile, package.json, justfile, Taskfile or Cargo aliases
+    Import {
+        /// Where to import from
+        source: SnipImportSource,
+        /// File to read instead of the usual history file or the one in the current directory
+        #[arg(long, value_name = "FILE")]
+        file: Option<PathBuf>,
+        /// Most history commands to offer, most used first
+        #[arg(long, default_value_t = 50)]
+        limit: usize,
+        /// Import everything found without asking
+        #[arg(short, long)]
+        yes: bool,
+        /// Replace existing snippets with the same names
+        #[arg(long)]
+        force: bool,
+    },
+    /// Run a snippet again, in the directory and with the variables of an earlier run
+    Rerun {
+        /// Run id (from `dev snip history`)
+        id: i64,
+        /// Variables to change, as key=value pairs
+        #[arg(short, long)]
+        vars: Vec<String>,
+        /// Dry-run mode (don't execute, just show command)
+        #[arg(long)]
+        dry_run: bool,
+        /// Run runbook steps marked `confirm` without asking
+        #[arg(short, long)]
+        yes: bool,
+    },
+}
+
+/// Sources for `dev snip import`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SnipImportSource {
+    /// Frequent commands in ~/.bash_history
+    Bash,
+    /// Frequent commands in $HISTFILE or ~/.zsh_history
+    Zsh,
+    /// Frequent commands in fish's history
+    Fish,
+    /// Makefile targets
+    Make,
+    /// package.json scripts
+    Npm,
+    /// justfile recipes
+    Just,
+    /// Taskfile tasks
+    Task,
+    /// Cargo aliases in .cargo/config.toml
+    Cargo,
+}
+
+#[derive(Parser, Debug)]
+pub struct SnipTagArgs {
+    #[command(subcommand)]
+    pub command: SnipTagCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SnipTagCommand {
+    /// Add tags to a snippet
+    Add {
+        /// Name of the snippet
+        name: String,
+        /// Tags to add (space- or comma-separated)
+        #[arg(required = true)]
+        tags: Vec<String>,
+    },
+    /// Remove tags from a snippet
+    Remove {
+        /// Name of the snippet
+        name: String,
+        /// Tags to remove (space- or comma-separated)
+        #[arg(required = true)]
+        tags: Vec<String>,
     },
 }
 
@@ -247,6 +699,8 @@ pub enum GitCommand {
         /// Dry run (print message but don't commit)
         #[arg(long)]
         dry_run: bool,
+        #[command(flatten)]
+        llm: ProviderArgs,
     },
     /// Interactive code review checklist
     Review,
@@ -265,3 +719,17 @@ pub enum GitCommand {
     },
 }
 
+
+/// LLM selection shared by the AI commands
+#[derive(Args, Debug, Clone, Default)]
+pub struct ProviderArgs {
+    /// LLM provider: openai, anthropic, gemini, ollama or one from [llm.providers]
+    #[arg(long)]
+    pub provider: Option<String>,
+    /// Model to use instead of the provider's configured one
+    #[arg(long)]
+    pub model: Option<String>,
+    /// Send secrets found in the prompt as they are, after confirming
+    #[arg(long)]
+    pub no_redact: bool,
+}
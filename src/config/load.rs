@@ -22,9 +22,7 @@ pub fn load_config() -> Result<Config> {
 
 /// Get the path to the user's config file
 fn get_user_config_path() -> Result<PathBuf> {
-    let home = dirs::home_dir()
-        .context("Could not determine home directory")?;
-    Ok(home.join(".dev").join("config.toml"))
+    Ok(crate::platform::dev_home()?.join("config.toml"))
 }
 
 /// Load the bundled default configuration
@@ -2,5 +2,5 @@ pub mod load;
 pub mod model;
 
 pub use load::load_config;
-pub use model::Config;
+pub use model::{Config, Multiplexer};
 
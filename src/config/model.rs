@@ -1,15 +1,179 @@
+use clap::ValueEnum;
 use serde::Deserialize;
+use std::collections::BTreeMap;
 
 /// User configuration representing user intent
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Default, Deserialize)]
 pub struct Config {
-    // Placeholder fields for future features
-    // These will be populated as features are added
+    /// Context switching settings
+    #[serde(default)]
+    pub ctx: CtxConfig,
+    /// Cross-machine sync settings
+    #[serde(default)]
+    pub sync: SyncConfig,
+    /// Error explanation settings
+    #[serde(default)]
+    pub wtf: WtfConfig,
+    /// LLM provider settings
+    #[serde(default)]
+    pub llm: LlmConfig,
+}
+
+/// Settings for `dev ctx`
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct CtxConfig {
+    /// Terminal multiplexer used by `dev ctx switch --session`
+    /// (auto-detected from the environment when unset)
+    #[serde(default)]
+    pub multiplexer: Option<Multiplexer>,
+    /// Stop the previous context's services when switching to another one
+    #[serde(default)]
+    pub stop_services_on_switch: bool,
+}
+
+/// Settings for `dev sync`
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SyncConfig {
+    /// Sync repository directory (defaults to ~/.dev/sync)
+    #[serde(default)]
+    pub dir: Option<String>,
+    /// Named project roots whose paths are stored as `${name}/...`
+    /// so they can differ between machines
+    #[serde(default)]
+    pub roots: BTreeMap<String, String>,
+}
+
+/// Settings for `dev wtf`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct WtfConfig {
+    /// Cached explanations at or below this confidence expire
+    pub low_confidence_threshold: f64,
+    /// Days after which low-confidence explanations are regenerated
+    pub low_confidence_ttl_days: i64,
+    /// Reuse a cached explanation for an error at least this similar (0 to 1)
+    pub similarity_threshold: f64,
+    /// Fingerprint normalization layers
+    pub normalize: NormalizeConfig,
 }
 
-impl Default for Config {
+impl Default for WtfConfig {
     fn default() -> Self {
-        Config {}
+        WtfConfig {
+            low_confidence_threshold: 0.5,
+            low_confidence_ttl_days: 7,
+            similarity_threshold: 0.8,
+            normalize: NormalizeConfig::default(),
+        }
     }
 }
 
+/// Settings for the `dev wtf` fingerprint normalizer
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct NormalizeConfig {
+    /// Built-in layers to turn off, e.g. "quoted" or "number"
+    pub disable: Vec<String>,
+    /// Extra replacements, applied before the built-in layers
+    pub extra: Vec<NormalizeRule>,
+}
+
+/// A user-defined normalization layer
+#[derive(Debug, Clone, Deserialize)]
+pub struct NormalizeRule {
+    pub pattern: String,
+    pub replace: String,
+}
+
+/// Settings for the LLM used by `dev wtf`, `dev docs` and `dev git smart-commit`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LlmConfig {
+    /// Provider to use; when unset, openai, anthropic and gemini are tried in
+    /// turn, skipping those without an API key
+    pub provider: Option<String>,
+    /// Providers tried, in order, when `provider` fails
+    pub fallback: Vec<String>,
+    /// Hours an identical request's reply is reused; 0 turns the cache off
+    pub cache_ttl_hours: u64,
+    /// Spending limits
+    pub budget: BudgetConfig,
+    /// Provider settings by name. The built-in names (openai, anthropic, gemini,
+    /// ollama) can be tuned here, and other names add providers of any kind.
+    pub providers: BTreeMap<String, ProviderConfig>,
+}
+
+impl Default for LlmConfig {
+    fn default() -> Self {
+        LlmConfig {
+            provider: None,
+            fallback: Vec::new(),
+            cache_ttl_hours: 168,
+            budget: BudgetConfig::default(),
+            providers: BTreeMap::new(),
+        }
+    }
+}
+
+/// LLM spending limits in US dollars, checked before each call
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct BudgetConfig {
+    /// Limit for the current day
+    pub daily_usd: Option<f64>,
+    /// Limit for the current calendar month
+    pub monthly_usd: Option<f64>,
+    /// What to do once a limit is reached
+    pub on_exceed: BudgetAction,
+}
+
+/// Whether an exceeded budget only warns or stops further calls
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BudgetAction {
+    #[default]
+    Warn,
+    Refuse,
+}
+
+/// One LLM provider. Unset fields take the defaults of its kind.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ProviderConfig {
+    /// API flavour; defaults to the provider's name for built-in providers
+    pub kind: Option<ProviderKind>,
+    pub model: Option<String>,
+    pub base_url: Option<String>,
+    /// Environment variable holding the API key
+    pub api_key_env: Option<String>,
+    /// Key in the dev-cli keyring (`dev secrets add <name>`) holding the API key
+    pub api_key_secret: Option<String>,
+    pub timeout_secs: Option<u64>,
+    pub temperature: Option<f64>,
+    pub max_tokens: Option<u32>,
+    /// Retries for rate-limited (429), failed (5xx) or timed-out requests
+    pub max_retries: Option<u32>,
+    /// US dollars per million input tokens, for usage accounting
+    pub input_price: Option<f64>,
+    /// US dollars per million output tokens
+    pub output_price: Option<f64>,
+}
+
+/// Supported LLM APIs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderKind {
+    /// OpenAI or any OpenAI-compatible endpoint (vLLM, LM Studio, llama.cpp, ...)
+    OpenAi,
+    Anthropic,
+    Gemini,
+    Ollama,
+}
+
+/// Supported terminal multiplexers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Multiplexer {
+    Tmux,
+    Zellij,
+}
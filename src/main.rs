@@ -8,22 +8,39 @@ mod utils;
 
 use anyhow::{Context, Result};
 use clap::Parser;
-use env_logger;
 use state::store::StateStore;
 
 fn main() {
     // Load .env file if it exists
     dotenv::dotenv().ok();
-    // Initialize logging from RUST_LOG environment variable
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
     
-    if let Err(e) = run() {
-        eprintln!("Error: {}", e);
+    // Parse CLI arguments
+    let cli = cli::Cli::parse();
+    
+    // Initialize logging from -v/-q, unless RUST_LOG is set
+    let level = match (cli.quiet, cli.verbose) {
+        (true, _) => "error",
+        (false, 0) => "warn",
+        (false, 1) => "info",
+        (false, 2) => "debug",
+        _ => "trace",
+    };
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(format!("warn,dev={}", level))).init();
+    
+    if let Err(e) = run(cli) {
+        if let Some(app::ExitCode(code)) = e.downcast_ref::<app::ExitCode>() {
+            std::process::exit(*code);
+        }
+        eprintln!("Error: {:#}", e);
         std::process::exit(1);
     }
 }
 
-fn run() -> Result<()> {
+fn run(cli: cli::Cli) -> Result<()> {
+    if let Some(path) = &cli.trace_file {
+        utils::trace::init(path)?;
+    }
+    
     // Load configuration
     let config = config::load_config()
         .context("Failed to load configuration")?;
@@ -34,9 +51,6 @@ fn run() -> Result<()> {
     state_store.init()
         .context("Failed to initialize state store")?;
     
-    // Parse CLI arguments
-    let cli = cli::Cli::parse();
-    
     // Create application context
     let app_ctx = app::AppContext::new(config, Box::new(state_store));
     
@@ -1,28 +1,108 @@
+use super::project::{self, ProjectContext, LOCAL_FILE, PROJECT_FILE};
+use super::{services, session};
 use crate::app::AppContext;
-use crate::cli::{CtxArgs, CtxCommand};
-use crate::state::store::{Context, ProjectType};
+use crate::cli::{CtxArgs, CtxCommand, CtxServiceCommand};
+use crate::config::Multiplexer;
+use crate::modules::trust;
+use crate::state::store::{Context, ContextService, ProjectType, ReadinessCheck};
+use crate::utils::trace::TracedCommand;
 use anyhow::{Context as AnyhowContext, Result};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::env;
-use std::path::Path;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
 /// Run the context switching command
 pub fn run(args: CtxArgs, app_ctx: &mut AppContext) -> Result<()> {
     match args.command {
-        CtxCommand::Save { name } => save_context(name, app_ctx),
-        CtxCommand::Switch { name } => switch_context(name, app_ctx),
+        CtxCommand::Save { name, layout, capture_tmux } => save_context(name, layout, capture_tmux, app_ctx),
+        CtxCommand::Switch { name, session, multiplexer, stop_services } => {
+            switch_context(name, session, multiplexer, stop_services, app_ctx)
+        }
         CtxCommand::List => list_contexts(app_ctx),
         CtxCommand::Show { name } => show_context(name, app_ctx),
         CtxCommand::Delete { name } => delete_context(name, app_ctx),
         CtxCommand::Diff { name } => diff_context(name, app_ctx),
+        CtxCommand::Up { name, service } => services_up(name, service, app_ctx),
+        CtxCommand::Down { name, service } => services_down(name, service, app_ctx),
+        CtxCommand::Status { name } => services_status(name, app_ctx),
+        CtxCommand::Service(args) => match args.command {
+            CtxServiceCommand::Add { context, name, command, cwd, env, port, log_regex, timeout } => {
+                let ready = (port.is_some() || log_regex.is_some()).then_some(ReadinessCheck {
+                    port,
+                    log_regex,
+                    timeout_secs: timeout,
+                });
+                let service = ContextService {
+                    name,
+                    command,
+                    working_dir: cwd,
+                    env: parse_env_pairs(env)?,
+                    ready,
+                };
+                add_service(context, service, app_ctx)
+            }
+            CtxServiceCommand::Rm { context, name } => remove_service(context, name, app_ctx),
+        },
+        CtxCommand::ExportProject { name, force } => export_project(name, force, app_ctx),
+        CtxCommand::ImportProject { path, name } => import_project(path, name, app_ctx),
     }
 }
 
-fn save_context(name: String, app_ctx: &mut AppContext) -> Result<()> {
+/// Name of the context most recently switched to
+pub fn active_context() -> Option<String> {
+    let path = crate::platform::dev_home().ok()?.join("run").join("active");
+    let name = fs::read_to_string(path).ok()?.trim().to_string();
+    (!name.is_empty()).then_some(name)
+}
+
+fn set_active_context(name: &str) -> Result<()> {
+    let dir = crate::platform::dev_home()?.join("run");
+    fs::create_dir_all(&dir)
+        .with_context(|| format!("Failed to create runtime directory {:?}", dir))?;
+    fs::write(dir.join("active"), name)
+        .context("Failed to record active context")?;
+    Ok(())
+}
+
+fn save_context(
+    name: String,
+    layout: Option<String>,
+    capture_tmux: bool,
+    app_ctx: &mut AppContext,
+) -> Result<()> {
     println!("Detecting project context...");
     
-    let context = capture_context(&name)?;
+    let current_dir = env::current_dir()
+        .context("Failed to get current working directory")?;
+    let mut context = capture_context(&name, &current_dir)?;
+    let layout_given = layout.is_some();
+    
+    // Keep the hook, services and layout unless a new layout is given
+    let existing = app_ctx.state.get_context(&name)?;
+    if let Some(existing) = &existing {
+        context.on_switch = existing.on_switch.clone();
+        context.services = existing.services.clone();
+    }
+    context.layout = if let Some(path) = layout {
+        Some(session::load_layout_file(Path::new(&path))?)
+    } else if capture_tmux {
+        let captured = session::capture_tmux_layout(Path::new(&context.working_dir))?;
+        println!("Captured {} tmux window(s)", captured.windows.len());
+        Some(captured)
+    } else {
+        existing.and_then(|existing| existing.layout)
+    };
+    
+    // A layout the user supplied themselves is trusted as-is
+    if layout_given || capture_tmux {
+        for command in trust::command::context_commands(&context) {
+            if command.label.starts_with("pane") {
+                trust::command::allow(app_ctx.state.as_mut(), &context.working_dir, &command.command)?;
+            }
+        }
+    }
     
     app_ctx.state.save_context(context)
         .with_context(|| format!("Failed to save context '{}'", name))?;
@@ -31,17 +111,11 @@ fn save_context(name: String, app_ctx: &mut AppContext) -> Result<()> {
     Ok(())
 }
 
-fn capture_context(name: &str) -> Result<Context> {
-    // Get current working directory
-    let working_dir = env::current_dir()
-        .context("Failed to get current working directory")?
-        .to_string_lossy()
-        .to_string();
-    
-    let working_path = Path::new(&working_dir);
+fn capture_context(name: &str, working_path: &Path) -> Result<Context> {
+    let working_dir = working_path.to_string_lossy().to_string();
     
     // Get git branch if in a git repo
-    let git_branch = get_git_branch().ok();
+    let git_branch = get_git_branch(working_path).ok();
     
     // Get environment variables (whitelist common ones)
     let env_vars = get_env_vars();
@@ -68,6 +142,8 @@ fn capture_context(name: &str) -> Result<Context> {
         important_files,
         important_dirs,
         package_manager,
+        layout: None,
+        services: Vec::new(),
     })
 }
 
@@ -78,7 +154,9 @@ fn diff_context(name: String, app_ctx: &mut AppContext) -> Result<()> {
         .ok_or_else(|| anyhow::anyhow!("Context '{}' not found", name))?;
         
     // 2. Capture current state
-    let current_ctx = capture_context("current")?;
+    let current_dir = env::current_dir()
+        .context("Failed to get current working directory")?;
+    let current_ctx = capture_context("current", &current_dir)?;
     
     println!("Context Diff: Current vs Saved ('{}')\n", name);
     
@@ -117,13 +195,13 @@ fn diff_context(name: String, app_ctx: &mut AppContext) -> Result<()> {
     let mut added_deps = Vec::new();
     let mut removed_deps = Vec::new();
     
-    for (dep, _) in &current_ctx.dependencies {
+    for dep in current_ctx.dependencies.keys() {
         if !saved_ctx.dependencies.contains_key(dep) {
             added_deps.push(dep);
         }
     }
     
-    for (dep, _) in &saved_ctx.dependencies {
+    for dep in saved_ctx.dependencies.keys() {
         if !current_ctx.dependencies.contains_key(dep) {
             removed_deps.push(dep);
         }
@@ -169,7 +247,7 @@ fn check_uncommitted_changes(working_dir: &Path) -> Result<usize> {
         .current_dir(working_dir)
         .arg("status")
         .arg("--porcelain")
-        .output()
+        .traced_output()
         .context("Failed to check git status")?;
         
     if output.status.success() {
@@ -181,11 +259,39 @@ fn check_uncommitted_changes(working_dir: &Path) -> Result<usize> {
     }
 }
 
-fn switch_context(name: String, app_ctx: &mut AppContext) -> Result<()> {
-    let context = app_ctx.state.get_context(&name)
+fn switch_context(
+    name: String,
+    session: bool,
+    multiplexer: Option<Multiplexer>,
+    stop_services: bool,
+    app_ctx: &mut AppContext,
+) -> Result<()> {
+    let mut context = app_ctx.state.get_context(&name)
         .with_context(|| format!("Failed to get context '{}'", name))?
         .ok_or_else(|| anyhow::anyhow!("Context '{}' not found", name))?;
     
+    // stdout is eval'd by the shell, so progress goes to stderr
+    context = discover_project_env(context, app_ctx)?;
+    
+    // Only emit the hook, venv activation (and pane commands) the user has trusted for this path
+    let mut commands = trust::command::context_commands(&context);
+    commands.retain(|c| c.label == "on_switch" || c.label == "virtual_env" || (session && c.label.starts_with("pane")));
+    let allowed = trust::command::review(app_ctx.state.as_mut(), &context.working_dir, &commands)?;
+    trust::command::retain_commands(&mut context, &allowed);
+    
+    if stop_services || app_ctx.config.ctx.stop_services_on_switch {
+        if let Some(previous) = active_context().filter(|previous| *previous != name) {
+            if let Some(previous_ctx) = app_ctx.state.get_context(&previous)? {
+                for service in &previous_ctx.services {
+                    if services::stop_service(&previous, &service.name)? {
+                        eprintln!("Stopped {}/{}", previous, service.name);
+                    }
+                }
+            }
+        }
+    }
+    set_active_context(&name)?;
+    
     // Output shell-executable code
     // This is designed to be eval'd: eval "$(dev ctx switch demo)"
     let shell = crate::platform::detect_shell().unwrap_or(crate::platform::Shell::Bash);
@@ -205,15 +311,50 @@ fn switch_context(name: String, app_ctx: &mut AppContext) -> Result<()> {
         }
     }
     
+    // Attach last so the session inherits everything exported above
+    if session {
+        let multiplexer = session::resolve_multiplexer(multiplexer, &app_ctx.config.ctx);
+        let attach = session::open_session(&context, multiplexer)?;
+        let escape = if shell == crate::platform::Shell::PowerShell { escape_powershell } else { escape_shell };
+        let line: Vec<String> = attach.iter().map(|arg| escape(arg)).collect();
+        println!("{}", line.join(" "));
+    }
+    
     Ok(())
 }
 
+/// Env vars to set on switch; names a shell would run as code are skipped
+fn switch_env(context: &Context) -> impl Iterator<Item = (&String, &String)> {
+    context.env_vars.iter().filter(|(key, _)| {
+        let valid = project::is_valid_env_name(key);
+        if !valid {
+            eprintln!("⚠️  Skipping invalid env var name {:?}", key);
+        }
+        valid
+    })
+}
+
+/// The line that sources the context's virtual environment in `shell`, if it has one.
+/// The script comes from the project, so it runs only once trusted.
+pub fn activate_command(context: &Context, shell: crate::platform::Shell) -> Option<String> {
+    use crate::platform::Shell;
+    let venv_path = Path::new(&context.working_dir).join(context.virtual_env.as_ref()?);
+    let script = |name: &str| Some(venv_path.join(name)).filter(|path| path.exists()).map(|path| path.to_string_lossy().to_string());
+    match shell {
+        Shell::Fish => script("bin/activate.fish").map(|path| format!("source {}", escape_shell(&path))),
+        Shell::PowerShell => script("Scripts/Activate.ps1").map(|path| format!("& {}", escape_powershell(&path))),
+        _ => script("bin/activate")
+            .or_else(|| script("Scripts/activate"))
+            .map(|path| format!("source {}", escape_shell(&path))),
+    }
+}
+
 fn output_bash_switch(context: &Context) {
     // Change directory
     println!("cd {}", escape_shell(&context.working_dir));
     
     // Set environment variables
-    for (key, value) in &context.env_vars {
+    for (key, value) in switch_env(context) {
         println!("export {}={}", key, escape_shell(value));
     }
     
@@ -223,13 +364,8 @@ fn output_bash_switch(context: &Context) {
     }
     
     // Activate virtual environment if present
-    if let Some(venv) = &context.virtual_env {
-        let venv_path = Path::new(&context.working_dir).join(venv);
-        if venv_path.join("bin/activate").exists() {
-            println!("source {}", escape_shell(&venv_path.join("bin/activate").to_string_lossy()));
-        } else if venv_path.join("Scripts/activate").exists() {
-            println!("source {}", escape_shell(&venv_path.join("Scripts/activate").to_string_lossy()));
-        }
+    if let Some(activate) = activate_command(context, crate::platform::Shell::Bash) {
+        println!("{}", activate);
     }
     
     // Install dependencies if needed (check if node_modules/cargo.lock exists)
@@ -272,17 +408,14 @@ fn output_bash_switch(context: &Context) {
 
 fn output_fish_switch(context: &Context) {
     println!("cd {}", escape_shell(&context.working_dir));
-    for (key, value) in &context.env_vars {
+    for (key, value) in switch_env(context) {
         println!("set -gx {} {}", key, escape_shell(value));
     }
     if let Some(branch) = &context.git_branch {
         println!("git checkout {} 2>/dev/null; or true", escape_shell(branch));
     }
-    if let Some(venv) = &context.virtual_env {
-        let venv_path = Path::new(&context.working_dir).join(venv);
-        if venv_path.join("bin/activate.fish").exists() {
-            println!("source {}", escape_shell(&venv_path.join("bin/activate.fish").to_string_lossy()));
-        }
+    if let Some(activate) = activate_command(context, crate::platform::Shell::Fish) {
+        println!("{}", activate);
     }
     if let Some(hook) = &context.on_switch {
         println!("{}", hook);
@@ -291,17 +424,14 @@ fn output_fish_switch(context: &Context) {
 
 fn output_powershell_switch(context: &Context) {
     println!("Set-Location {}", escape_powershell(&context.working_dir));
-    for (key, value) in &context.env_vars {
+    for (key, value) in switch_env(context) {
         println!("$env:{} = {}", key, escape_powershell(value));
     }
     if let Some(branch) = &context.git_branch {
         println!("git checkout {} 2>$null; if ($?) {{ }}", escape_powershell(branch));
     }
-    if let Some(venv) = &context.virtual_env {
-        let venv_path = Path::new(&context.working_dir).join(venv);
-        if venv_path.join("Scripts/Activate.ps1").exists() {
-            println!("& {}", escape_powershell(&venv_path.join("Scripts/Activate.ps1").to_string_lossy()));
-        }
+    if let Some(activate) = activate_command(context, crate::platform::Shell::PowerShell) {
+        println!("{}", activate);
     }
     if let Some(hook) = &context.on_switch {
         println!("{}", hook);
@@ -414,11 +544,315 @@ fn show_context(name: String, app_ctx: &mut AppContext) -> Result<()> {
         println!("  {}", hook);
     }
     
+    // Services
+    if !context.services.is_empty() {
+        println!("\n⚙️  Services:");
+        for service in &context.services {
+            println!("  • {}: {}", service.name, service.command);
+        }
+    }
+    
+    // Session Layout
+    if let Some(layout) = &context.layout {
+        println!("\n🪟 Session Layout:");
+        for window in &layout.windows {
+            println!("  • {} ({} pane(s))", window.name, window.panes.len().max(1));
+            for pane in &window.panes {
+                let cwd = pane.cwd.as_deref().unwrap_or(".");
+                match &pane.command {
+                    Some(command) => println!("      {} $ {}", cwd, command),
+                    None => println!("      {}", cwd),
+                }
+            }
+        }
+    }
+    
     println!("\n{}", "━".repeat(50));
     
     Ok(())
 }
 
+fn resolve_context(name: Option<String>, app_ctx: &mut AppContext) -> Result<Context> {
+    let name = name
+        .or_else(active_context)
+        .ok_or_else(|| anyhow::anyhow!("No context given and no active context. Pass a context name."))?;
+    
+    app_ctx.state.get_context(&name)
+        .with_context(|| format!("Failed to get context '{}'", name))?
+        .ok_or_else(|| anyhow::anyhow!("Context '{}' not found", name))
+}
+
+fn select_services<'a>(context: &'a Context, filter: &[String]) -> Result<Vec<&'a ContextService>> {
+    for wanted in filter {
+        if !context.services.iter().any(|s| &s.name == wanted) {
+            return Err(anyhow::anyhow!("Context '{}' has no service '{}'", context.name, wanted));
+        }
+    }
+    
+    Ok(context.services
+        .iter()
+        .filter(|s| filter.is_empty() || filter.contains(&s.name))
+        .collect())
+}
+
+fn services_up(name: Option<String>, filter: Vec<String>, app_ctx: &mut AppContext) -> Result<()> {
+    let context = resolve_context(name, app_ctx)?;
+    let selected = select_services(&context, &filter)?;
+    
+    if selected.is_empty() {
+        println!("Context '{}' has no services.", context.name);
+        println!("\nTo add one, run: dev ctx service add {} <name> --command <cmd>", context.name);
+        return Ok(());
+    }
+    
+    let commands: Vec<_> = trust::command::context_commands(&context)
+        .into_iter()
+        .filter(|c| selected.iter().any(|s| c.label == format!("service {}", s.name)))
+        .collect();
+    let allowed = trust::command::review(app_ctx.state.as_mut(), &context.working_dir, &commands)?;
+    
+    for service in selected {
+        if !allowed.contains(&service.command) {
+            println!("⚠️  Skipping untrusted service {}", service.name);
+            continue;
+        }
+        println!("▶ Starting {}...", service.name);
+        let (pid, already_running) = services::start_service(&context, service)?;
+        if already_running {
+            println!("  already running (pid {})", pid);
+        } else {
+            println!("  ✓ ready (pid {})", pid);
+        }
+    }
+    
+    Ok(())
+}
+
+fn services_down(name: Option<String>, filter: Vec<String>, app_ctx: &mut AppContext) -> Result<()> {
+    let context = resolve_context(name, app_ctx)?;
+    
+    for service in select_services(&context, &filter)? {
+        if services::stop_service(&context.name, &service.name)? {
+            println!("■ Stopped {}", service.name);
+        } else {
+            println!("  {} is not running", service.name);
+        }
+    }
+    
+    Ok(())
+}
+
+fn services_status(name: Option<String>, app_ctx: &mut AppContext) -> Result<()> {
+    let context = resolve_context(name, app_ctx)?;
+    
+    if context.services.is_empty() {
+        println!("Context '{}' has no services.", context.name);
+        return Ok(());
+    }
+    
+    let rows = context.services
+        .iter()
+        .map(|service| services::service_status(&context, service))
+        .collect::<Result<Vec<_>>>()?;
+    
+    println!("Services for '{}':", context.name);
+    println!("{}", tabled::Table::new(rows));
+    Ok(())
+}
+
+fn add_service(context_name: String, service: ContextService, app_ctx: &mut AppContext) -> Result<()> {
+    let mut context = app_ctx.state.get_context(&context_name)
+        .with_context(|| format!("Failed to get context '{}'", context_name))?
+        .ok_or_else(|| anyhow::anyhow!("Context '{}' not found", context_name))?;
+    
+    // Commands typed by the user are trusted
+    trust::command::allow(app_ctx.state.as_mut(), &context.working_dir, &service.command)?;
+    
+    let service_name = service.name.clone();
+    context.services.retain(|s| s.name != service_name);
+    context.services.push(service);
+    
+    app_ctx.state.save_context(context)
+        .with_context(|| format!("Failed to save context '{}'", context_name))?;
+    
+    println!("✓ Saved service '{}' on context '{}'", service_name, context_name);
+    Ok(())
+}
+
+fn remove_service(context_name: String, service_name: String, app_ctx: &mut AppContext) -> Result<()> {
+    let mut context = app_ctx.state.get_context(&context_name)
+        .with_context(|| format!("Failed to get context '{}'", context_name))?
+        .ok_or_else(|| anyhow::anyhow!("Context '{}' not found", context_name))?;
+    
+    if !context.services.iter().any(|s| s.name == service_name) {
+        return Err(anyhow::anyhow!("Context '{}' has no service '{}'", context_name, service_name));
+    }
+    
+    if services::stop_service(&context_name, &service_name)? {
+        println!("■ Stopped {}", service_name);
+    }
+    context.services.retain(|s| s.name != service_name);
+    
+    app_ctx.state.save_context(context)
+        .with_context(|| format!("Failed to save context '{}'", context_name))?;
+    
+    println!("✓ Removed service '{}' from context '{}'", service_name, context_name);
+    Ok(())
+}
+
+/// Pick up env changes from the project's shared context file on switch.
+/// Commands are only ever taken from it through `import-project`.
+fn discover_project_env(context: Context, app_ctx: &mut AppContext) -> Result<Context> {
+    let dir = PathBuf::from(&context.working_dir);
+    let load = |file| ProjectContext::load(&dir, file).unwrap_or_else(|e| {
+        eprintln!("⚠️  Ignoring {}: {:#}", file, e);
+        None
+    });
+    
+    let Some(project) = load(PROJECT_FILE) else {
+        return Ok(context);
+    };
+    let local = load(LOCAL_FILE);
+    
+    let candidate = project.apply_to(context.clone());
+    for command in trust::command::context_commands(&candidate) {
+        if !trust::command::is_trusted(app_ctx.state.as_ref(), &context.working_dir, &command.command)? {
+            eprintln!("⚠️  {} has new or changed commands. Run `dev ctx import-project` to review them.", PROJECT_FILE);
+            break;
+        }
+    }
+    
+    let files = [(PROJECT_FILE, Some(project)), (LOCAL_FILE, local)];
+    let env_commands: Vec<_> = files.iter()
+        .filter_map(|(file, project)| project.as_ref()?.env_command(file))
+        .collect();
+    let allowed = trust::command::review(app_ctx.state.as_mut(), &context.working_dir, &env_commands)?;
+    
+    Ok(apply_trusted_env(context, files, &allowed))
+}
+
+/// Apply the env of each context file whose env the user trusts
+fn apply_trusted_env(
+    mut context: Context,
+    files: [(&str, Option<ProjectContext>); 2],
+    allowed: &HashSet<String>,
+) -> Context {
+    for (file, project) in files {
+        let Some(project) = project else { continue };
+        match project.env_command(file) {
+            Some(command) if !allowed.contains(&command.command) => {
+                eprintln!("⚠️  Not applying env from untrusted {}", file);
+            }
+            _ => context = project.apply_env(context),
+        }
+    }
+    context
+}
+
+fn export_project(name: Option<String>, force: bool, app_ctx: &mut AppContext) -> Result<()> {
+    let context = resolve_context(name, app_ctx)?;
+    let (project, skipped) = ProjectContext::from_context(&context);
+    let dir = Path::new(&context.working_dir);
+    
+    let content = project.to_file_content()?;
+    let path = dir.join(PROJECT_FILE);
+    if let Ok(existing) = fs::read_to_string(&path) {
+        if existing == content {
+            println!("✓ {} is up to date", path.display());
+            return Ok(());
+        }
+        if !force {
+            print_line_diff(&existing, &content);
+            anyhow::bail!("{} already exists. Pass --force to overwrite it with the changes above", path.display());
+        }
+    }
+    
+    project.write(dir)?;
+    println!("✓ Wrote {}", path.display());
+    
+    for (key, value) in &project.env {
+        if let project::ProjectEnvValue::Secret { secret } = value {
+            println!("  🔒 {} is stored as a reference to secret '{}'", key, secret);
+        }
+    }
+    if !skipped.is_empty() {
+        println!("  Skipped machine-specific or secret values: {}", skipped.join(", "));
+    }
+    println!("\nCommit {} and add {} to .gitignore for personal overrides.", PROJECT_FILE, LOCAL_FILE);
+    Ok(())
+}
+
+/// Lines only in `old` prefixed with `-`, lines only in `new` with `+`
+fn print_line_diff(old: &str, new: &str) {
+    let old_lines: HashSet<&str> = old.lines().collect();
+    let new_lines: HashSet<&str> = new.lines().collect();
+    for line in old.lines().filter(|line| !new_lines.contains(line)) {
+        println!("- {}", line);
+    }
+    for line in new.lines().filter(|line| !old_lines.contains(line)) {
+        println!("+ {}", line);
+    }
+}
+
+fn import_project(path: Option<String>, name: Option<String>, app_ctx: &mut AppContext) -> Result<()> {
+    let dir = match path {
+        Some(path) => PathBuf::from(path),
+        None => env::current_dir().context("Failed to get current working directory")?,
+    };
+    let dir = dir.canonicalize()
+        .with_context(|| format!("Project directory {:?} not found", dir))?;
+    
+    let project = ProjectContext::load(&dir, PROJECT_FILE)?
+        .ok_or_else(|| anyhow::anyhow!("No {} in {}", PROJECT_FILE, dir.display()))?;
+    let overrides = ProjectContext::load(&dir, LOCAL_FILE)?;
+    
+    let name = name
+        .or_else(|| (!project.name.is_empty()).then(|| project.name.clone()))
+        .or_else(|| dir.file_name().map(|n| n.to_string_lossy().to_string()))
+        .ok_or_else(|| anyhow::anyhow!("Could not determine a context name. Pass --name."))?;
+    
+    let mut base = match app_ctx.state.get_context(&name)? {
+        Some(existing) => existing,
+        None => capture_context(&name, &dir)?,
+    };
+    base.working_dir = dir.to_string_lossy().to_string();
+    
+    let mut context = project.apply_to(base);
+    if let Some(overrides) = &overrides {
+        context = overrides.apply_to(context);
+    }
+    context.name = name.clone();
+    
+    // Commands are stored either way; untrusted ones are skipped when the context runs.
+    // Env is only stored once trusted.
+    let files = [(PROJECT_FILE, Some(project)), (LOCAL_FILE, overrides)];
+    let mut commands = trust::command::context_commands(&context);
+    commands.extend(files.iter().filter_map(|(file, project)| project.as_ref()?.env_command(file)));
+    let allowed = trust::command::review(app_ctx.state.as_mut(), &context.working_dir, &commands)?;
+    let context = apply_trusted_env(context, files, &allowed);
+    
+    app_ctx.state.save_context(context)
+        .with_context(|| format!("Failed to save context '{}'", name))?;
+    
+    println!("✓ Imported context '{}' from {}", name, dir.join(PROJECT_FILE).display());
+    Ok(())
+}
+
+fn parse_env_pairs(pairs: Vec<String>) -> Result<HashMap<String, String>> {
+    let mut env_vars = HashMap::new();
+    
+    for pair in pairs {
+        let (key, value) = pair.split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("Invalid environment variable: {}. Expected key=value", pair))?;
+        if !project::is_valid_env_name(key) {
+            anyhow::bail!("Invalid environment variable name: {:?}", key);
+        }
+        env_vars.insert(key.to_string(), value.to_string());
+    }
+    
+    Ok(env_vars)
+}
+
 fn delete_context(name: String, app_ctx: &mut AppContext) -> Result<()> {
     app_ctx.state.delete_context(&name)
         .with_context(|| format!("Failed to delete context '{}'", name))?;
@@ -427,7 +861,10 @@ fn delete_context(name: String, app_ctx: &mut AppContext) -> Result<()> {
     Ok(())
 }
 
-fn detect_project(working_dir: &Path) -> Result<(Option<ProjectType>, HashMap<String, String>, Option<String>)> {
+/// Detected project type, dependency files and package manager
+pub(crate) type ProjectDetection = (Option<ProjectType>, HashMap<String, String>, Option<String>);
+
+pub(crate) fn detect_project(working_dir: &Path) -> Result<ProjectDetection> {
     let mut dependencies = HashMap::new();
     let mut package_manager = None;
     let mut project_type = None;
@@ -530,11 +967,10 @@ fn detect_virtual_env(working_dir: &Path) -> Result<Option<String>> {
     if let Some(parent) = working_dir.parent() {
         for venv_dir in &["venv", ".venv", "env", ".env"] {
             let venv_path = parent.join(venv_dir);
-            if venv_path.exists() {
-                if venv_path.join("bin/activate").exists() ||
-                   venv_path.join("Scripts/activate").exists() {
-                    return Ok(Some(format!("../{}", venv_dir)));
-                }
+            if venv_path.exists() &&
+               (venv_path.join("bin/activate").exists() ||
+                venv_path.join("Scripts/activate").exists()) {
+                return Ok(Some(format!("../{}", venv_dir)));
             }
         }
     }
@@ -576,10 +1012,8 @@ fn find_important_files(working_dir: &Path, project_type: &Option<ProjectType>)
                     files.push(".node-version".to_string());
                 }
             }
-            ProjectType::Python => {
-                if working_dir.join(".python-version").exists() {
-                    files.push(".python-version".to_string());
-                }
+            ProjectType::Python if working_dir.join(".python-version").exists() => {
+                files.push(".python-version".to_string());
             }
             _ => {}
         }
@@ -619,10 +1053,8 @@ fn find_important_dirs(working_dir: &Path, project_type: &Option<ProjectType>) -
                     dirs.push("components".to_string());
                 }
             }
-            ProjectType::Python => {
-                if working_dir.join("app").is_dir() {
-                    dirs.push("app".to_string());
-                }
+            ProjectType::Python if working_dir.join("app").is_dir() => {
+                dirs.push("app".to_string());
             }
             _ => {}
         }
@@ -631,12 +1063,13 @@ fn find_important_dirs(working_dir: &Path, project_type: &Option<ProjectType>) -
     Ok(dirs)
 }
 
-fn get_git_branch() -> Result<String> {
+fn get_git_branch(working_dir: &Path) -> Result<String> {
     let output = Command::new("git")
+        .current_dir(working_dir)
         .arg("rev-parse")
         .arg("--abbrev-ref")
         .arg("HEAD")
-        .output()
+        .traced_output()
         .context("Failed to run git command")?;
     
     if !output.status.success() {
@@ -1,4 +1,7 @@
 pub mod command;
+pub mod project;
+pub mod services;
+pub mod session;
 
 pub use command::run;
 
@@ -0,0 +1,327 @@
+use crate::modules::trust::command::ContextCommand;
+use crate::state::store::{Context, ContextService, SessionLayout};
+use anyhow::{Context as AnyhowContext, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Location of the shared context file inside a project
+pub const PROJECT_FILE: &str = ".dev/context.toml";
+
+/// Personal, uncommitted overrides of the shared file
+pub const LOCAL_FILE: &str = ".dev/context.local.toml";
+
+/// Token for the project root in exported env values
+const PROJECT_ROOT: &str = "${PROJECT_ROOT}";
+
+/// Variables that describe the machine rather than the project
+const MACHINE_ENV: &[&str] = &[
+    "PATH", "HOME", "USER", "SHELL", "EDITOR", "LANG", "LC_ALL",
+    "GOPATH", "GOROOT", "JAVA_HOME", "PYTHONPATH", "NODE_PATH",
+    "CARGO_HOME", "RUSTUP_HOME",
+];
+
+/// Variables that make shells or programs run code, never taken from a project file
+const DANGEROUS_ENV: &[&str] = &[
+    "PROMPT_COMMAND", "BASH_ENV", "ENV", "CDPATH", "GLOBIGNORE", "IFS", "PS0", "PS1", "PS2",
+    "PS3", "PS4", "SHELLOPTS", "BASHOPTS", "ZDOTDIR", "HISTFILE", "INPUTRC", "PERL5OPT",
+    "PERL5LIB", "PYTHONSTARTUP", "PYTHONHOME", "NODE_OPTIONS", "RUBYOPT", "GIT_SSH_COMMAND",
+    "GIT_EXEC_PATH", "GIT_CONFIG_GLOBAL", "GIT_CONFIG_SYSTEM",
+];
+
+/// Prefixes of dynamic-loader and exported-function variables
+const DANGEROUS_ENV_PREFIXES: &[&str] = &["LD_", "DYLD_", "BASH_FUNC_"];
+
+/// Key fragments that mark an env var as a secret
+const SECRET_MARKERS: &[&str] = &["KEY", "TOKEN", "SECRET", "PASSWORD", "PASSWD", "CREDENTIAL"];
+
+/// Portable, team-shared context committed to a repository
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProjectContext {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub virtual_env: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_switch: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, ProjectEnvValue>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub services: Vec<ContextService>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layout: Option<SessionLayout>,
+    /// sha256 of the file this was loaded from
+    #[serde(skip)]
+    pub source_hash: String,
+}
+
+/// An env value, or a reference to a secret in the dev-cli keyring
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ProjectEnvValue {
+    Plain(String),
+    Secret { secret: String },
+}
+
+impl ProjectContext {
+    /// Build the shareable form of a context. Returns the file and the
+    /// names of env vars that were left out as machine-specific or secret.
+    pub fn from_context(context: &Context) -> (Self, Vec<String>) {
+        let root = context.working_dir.trim_end_matches('/');
+        let mut env = BTreeMap::new();
+        let mut skipped = Vec::new();
+
+        for (key, value) in &context.env_vars {
+            match portable_value(root, key, value) {
+                Portable::Value(value) => {
+                    env.insert(key.clone(), ProjectEnvValue::Plain(value));
+                }
+                Portable::Secret => {
+                    env.insert(key.clone(), ProjectEnvValue::Secret { secret: key.to_lowercase() });
+                }
+                Portable::Machine => {}
+                Portable::Local => skipped.push(key.clone()),
+            }
+        }
+
+        let mut services = Vec::new();
+        for service in &context.services {
+            let mut service = service.clone();
+            // Service env can't hold secret references, so secrets are left out too
+            let mut env = HashMap::new();
+            for (key, value) in &service.env {
+                match portable_value(root, key, value) {
+                    Portable::Value(value) => {
+                        env.insert(key.clone(), value);
+                    }
+                    Portable::Machine => {}
+                    Portable::Secret | Portable::Local => skipped.push(format!("{}.{}", service.name, key)),
+                }
+            }
+            service.env = env;
+
+            if let Some(dir) = service.working_dir.take() {
+                match relative_dir(root, &dir) {
+                    Some(dir) => service.working_dir = Some(dir),
+                    None => skipped.push(format!("{}.cwd", service.name)),
+                }
+            }
+            services.push(service);
+        }
+
+        let project = ProjectContext {
+            name: context.name.clone(),
+            virtual_env: context.virtual_env.clone(),
+            on_switch: context.on_switch.clone(),
+            env,
+            services,
+            layout: context.layout.clone(),
+            source_hash: String::new(),
+        };
+        (project, skipped)
+    }
+
+    /// Load a context file (`PROJECT_FILE` or `LOCAL_FILE`) from a project directory, if present
+    pub fn load(project_dir: &Path, file: &str) -> Result<Option<Self>> {
+        let path = project_dir.join(file);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {:?}", path))?;
+        let mut project: ProjectContext = toml::from_str(&content)
+            .with_context(|| format!("Failed to parse {:?}", path))?;
+
+        let service_keys = project.services.iter().flat_map(|service| service.env.keys());
+        if let Some(key) = project.env.keys().chain(service_keys).find(|key| !is_valid_env_name(key)) {
+            anyhow::bail!("Invalid env var name {:?} in {:?}", key, path);
+        }
+
+        project.source_hash = format!("{:x}", Sha256::digest(content.as_bytes()));
+        Ok(Some(project))
+    }
+
+    /// The env of this file as an entry for the trust review. The file's hash is
+    /// part of it, so any edit to the file is reviewed again before its env applies.
+    pub fn env_command(&self, file: &str) -> Option<ContextCommand> {
+        if self.env.is_empty() {
+            return None;
+        }
+
+        let vars: Vec<String> = self
+            .env
+            .iter()
+            .map(|(key, value)| match value {
+                ProjectEnvValue::Plain(value) => format!("{}={}", key, value),
+                ProjectEnvValue::Secret { secret } => format!("{}=<secret {}>", key, secret),
+            })
+            .collect();
+        Some(ContextCommand {
+            label: format!("env from {}", file),
+            command: format!("{}  (sha256 {})", vars.join(" "), self.source_hash),
+        })
+    }
+
+    /// The text `write` puts in `.dev/context.toml`
+    pub fn to_file_content(&self) -> Result<String> {
+        Ok(format!(
+            "# Shared dev context. Import with `dev ctx import-project`.\n\
+             # Secrets are references to `dev secrets` keys, never values.\n\n{}",
+            toml::to_string_pretty(self)?
+        ))
+    }
+
+    /// Write `.dev/context.toml` into a project directory
+    pub fn write(&self, project_dir: &Path) -> Result<PathBuf> {
+        let path = project_dir.join(PROJECT_FILE);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {:?}", parent))?;
+        }
+
+        fs::write(&path, self.to_file_content()?)
+            .with_context(|| format!("Failed to write {:?}", path))?;
+        Ok(path)
+    }
+
+    /// Overlay the hooks, services and layout of this file onto a context;
+    /// values defined here win. Env is left to `apply_env` once it is trusted.
+    pub fn apply_to(&self, mut context: Context) -> Context {
+        let root = context.working_dir.trim_end_matches('/').to_string();
+
+        for service in &self.services {
+            let mut service = service.clone();
+            service.env = service
+                .env
+                .into_iter()
+                .filter(|(key, _)| importable(key))
+                .map(|(key, value)| (key, value.replace(PROJECT_ROOT, &root)))
+                .collect();
+            context.services.retain(|s| s.name != service.name);
+            context.services.push(service);
+        }
+        if self.layout.is_some() {
+            context.layout = self.layout.clone();
+        }
+        if self.virtual_env.is_some() {
+            context.virtual_env = self.virtual_env.clone();
+        }
+        if self.on_switch.is_some() {
+            context.on_switch = self.on_switch.clone();
+        }
+
+        context
+    }
+
+    /// Overlay only the env vars of this file, resolving secret references
+    pub fn apply_env(&self, mut context: Context) -> Context {
+        let root = context.working_dir.trim_end_matches('/').to_string();
+
+        for (key, value) in &self.env {
+            if !importable(key) {
+                continue;
+            }
+            match value {
+                ProjectEnvValue::Plain(value) => {
+                    context.env_vars.insert(key.clone(), value.replace(PROJECT_ROOT, &root));
+                }
+                ProjectEnvValue::Secret { secret } => match read_secret(secret) {
+                    Some(value) => {
+                        context.env_vars.insert(key.clone(), value);
+                    }
+                    None => eprintln!(
+                        "⚠️  {} needs secret '{}'. Add it with: dev secrets add {}",
+                        key, secret, secret
+                    ),
+                },
+            }
+        }
+
+        context
+    }
+}
+
+/// The env of both context files in a project, as trust review entries
+pub fn env_commands(project_dir: &Path) -> Vec<ContextCommand> {
+    [PROJECT_FILE, LOCAL_FILE]
+        .into_iter()
+        .filter_map(|file| {
+            let project = ProjectContext::load(project_dir, file).ok()??;
+            project.env_command(file)
+        })
+        .collect()
+}
+
+/// Whether `name` can be exported by every supported shell
+pub fn is_valid_env_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Whether a project file may set `key`: machine variables and ones that
+/// make shells or programs run code are dropped with a warning
+fn importable(key: &str) -> bool {
+    let upper = key.to_uppercase();
+    let denied = MACHINE_ENV.contains(&upper.as_str())
+        || DANGEROUS_ENV.contains(&upper.as_str())
+        || DANGEROUS_ENV_PREFIXES.iter().any(|prefix| upper.starts_with(prefix));
+    if denied {
+        eprintln!("⚠️  Ignoring {} from the project context file", key);
+    }
+    !denied
+}
+
+/// How an env value can be shared
+enum Portable {
+    Value(String),
+    Secret,
+    /// Describes the machine; dropped silently
+    Machine,
+    /// An absolute path outside the project
+    Local,
+}
+
+fn portable_value(root: &str, key: &str, value: &str) -> Portable {
+    if MACHINE_ENV.contains(&key) {
+        return Portable::Machine;
+    }
+
+    let upper = key.to_uppercase();
+    if SECRET_MARKERS.iter().any(|marker| upper.contains(marker)) {
+        return Portable::Secret;
+    }
+
+    let value = if root.is_empty() { value.to_string() } else { value.replace(root, PROJECT_ROOT) };
+    // Any other absolute path only makes sense on this machine
+    if is_absolute(&value) {
+        return Portable::Local;
+    }
+    Portable::Value(value)
+}
+
+/// A service directory relative to the project root, or `None` if it is outside it
+fn relative_dir(root: &str, dir: &str) -> Option<String> {
+    if !is_absolute(dir) {
+        return Some(dir.to_string());
+    }
+    if root.is_empty() {
+        return None;
+    }
+    let relative = Path::new(dir).strip_prefix(root).ok()?;
+    Some(match relative.to_string_lossy() {
+        rest if rest.is_empty() => ".".to_string(),
+        rest => rest.to_string(),
+    })
+}
+
+fn is_absolute(value: &str) -> bool {
+    value.starts_with('/') || value.get(1..3) == Some(":\\")
+}
+
+fn read_secret(key: &str) -> Option<String> {
+    keyring::Entry::new("dev-cli", key).ok()?.get_password().ok()
+}
@@ -0,0 +1,231 @@
+use crate::platform::process;
+use crate::state::store::{Context, ContextService};
+use crate::utils::trace::TracedCommand;
+use anyhow::{Context as AnyhowContext, Result};
+use std::fs::{self, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+use tabled::Tabled;
+
+/// How long a service gets to exit after SIGTERM before it is killed
+const STOP_GRACE: Duration = Duration::from_secs(5);
+
+/// Runtime files of a service under `~/.dev/run/<ctx>/`, named after the
+/// service. The PID file holds the PID and the process start time.
+struct ServiceFiles {
+    pid: PathBuf,
+    log: PathBuf,
+}
+
+fn service_files(context_name: &str, service_name: &str) -> Result<ServiceFiles> {
+    let dir = crate::platform::context_run_dir(context_name)?;
+    let name = crate::platform::safe_file_name(service_name);
+    Ok(ServiceFiles {
+        pid: dir.join(format!("{}.pid", name)),
+        log: dir.join(format!("{}.log", name)),
+    })
+}
+
+/// PID of the service if it is running; stale PID files are cleaned up.
+/// A process only counts as the service if it started when the recorded one
+/// did, so a reused PID is never mistaken for it (and never signalled).
+pub fn running_pid(context_name: &str, service_name: &str) -> Result<Option<u32>> {
+    let files = service_files(context_name, service_name)?;
+    let Ok(content) = fs::read_to_string(&files.pid) else {
+        return Ok(None);
+    };
+
+    let mut lines = content.lines();
+    let pid = lines.next().and_then(|line| line.trim().parse::<u32>().ok());
+    let recorded_start = lines.next().map(str::trim).filter(|start| !start.is_empty());
+    if let Some(pid) = pid.filter(|&pid| process::is_running(pid)) {
+        match (recorded_start, process::start_time(pid)) {
+            (Some(recorded), Some(actual)) if recorded == actual => return Ok(Some(pid)),
+            // No start time to compare on this platform
+            (None, None) => return Ok(Some(pid)),
+            _ => log::warn!(
+                "PID {} of service '{}' now belongs to another process; forgetting it",
+                pid, service_name
+            ),
+        }
+    }
+    let _ = fs::remove_file(&files.pid);
+    Ok(None)
+}
+
+/// Start a service in the background and wait for its readiness check.
+/// Returns the PID and whether it was already running.
+pub fn start_service(context: &Context, service: &ContextService) -> Result<(u32, bool)> {
+    if let Some(pid) = running_pid(&context.name, &service.name)? {
+        return Ok((pid, true));
+    }
+
+    let files = service_files(&context.name, &service.name)?;
+    let mut log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&files.log)
+        .with_context(|| format!("Failed to open service log {:?}", files.log))?;
+    writeln!(log, "--- {} started {} ---", service.name, chrono::Local::now().to_rfc3339())?;
+    let log_offset = log.metadata()?.len();
+
+    let working_dir = match &service.working_dir {
+        Some(dir) => Path::new(&context.working_dir).join(dir),
+        None => PathBuf::from(&context.working_dir),
+    };
+
+    let mut command = shell_command(&service.command);
+    command
+        .current_dir(&working_dir)
+        .envs(&context.env_vars)
+        .envs(&service.env)
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log);
+
+    // Own process group so the service outlives the terminal and can be stopped as a unit
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+
+    let mut child = command
+        .traced_spawn()
+        .with_context(|| format!("Failed to start service '{}'", service.name))?;
+    let pid = child.id();
+    let started = process::start_time(pid).unwrap_or_default();
+    fs::write(&files.pid, format!("{}\n{}\n", pid, started))
+        .with_context(|| format!("Failed to write PID file {:?}", files.pid))?;
+
+    if let Err(e) = wait_ready(&mut child, service, &files.log, log_offset) {
+        let _ = process::terminate(pid, STOP_GRACE);
+        let _ = fs::remove_file(&files.pid);
+        return Err(e);
+    }
+
+    Ok((pid, false))
+}
+
+fn shell_command(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C").arg(command);
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(command);
+        cmd
+    }
+}
+
+fn wait_ready(child: &mut Child, service: &ContextService, log_path: &Path, log_offset: u64) -> Result<()> {
+    let Some(check) = &service.ready else {
+        return Ok(());
+    };
+
+    let log_regex = check.log_regex.as_deref()
+        .map(regex::Regex::new)
+        .transpose()
+        .with_context(|| format!("Invalid readiness regex for service '{}'", service.name))?;
+    let deadline = Instant::now() + Duration::from_secs(check.timeout_secs);
+
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Err(anyhow::anyhow!(
+                "Service '{}' exited ({}) before becoming ready. See {:?}",
+                service.name, status, log_path
+            ));
+        }
+
+        let port_ready = check.port.is_none_or(|port| {
+            let addr = SocketAddr::from(([127, 0, 0, 1], port));
+            TcpStream::connect_timeout(&addr, Duration::from_millis(200)).is_ok()
+        });
+        let log_ready = match &log_regex {
+            Some(re) => re.is_match(&read_log_from(log_path, log_offset)?),
+            None => true,
+        };
+
+        if port_ready && log_ready {
+            return Ok(());
+        }
+
+        if Instant::now() >= deadline {
+            return Err(anyhow::anyhow!(
+                "Service '{}' was not ready after {}s. See {:?}",
+                service.name, check.timeout_secs, log_path
+            ));
+        }
+
+        thread::sleep(Duration::from_millis(250));
+    }
+}
+
+fn read_log_from(path: &Path, offset: u64) -> Result<String> {
+    let mut file = fs::File::open(path)?;
+    file.seek(SeekFrom::Start(offset))?;
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes)?;
+    Ok(String::from_utf8_lossy(&bytes).to_string())
+}
+
+/// Stop a running service. Returns false if it was not running.
+pub fn stop_service(context_name: &str, service_name: &str) -> Result<bool> {
+    let Some(pid) = running_pid(context_name, service_name)? else {
+        return Ok(false);
+    };
+
+    process::terminate(pid, STOP_GRACE)
+        .with_context(|| format!("Failed to stop service '{}'", service_name))?;
+
+    let files = service_files(context_name, service_name)?;
+    let _ = fs::remove_file(&files.pid);
+    Ok(true)
+}
+
+/// One row of `dev ctx status`
+#[derive(Tabled)]
+pub struct ServiceStatus {
+    #[tabled(rename = "Service")]
+    pub name: String,
+    #[tabled(rename = "State")]
+    pub state: String,
+    #[tabled(rename = "PID")]
+    pub pid: String,
+    #[tabled(rename = "Ready check")]
+    pub ready: String,
+    #[tabled(rename = "Log")]
+    pub log: String,
+}
+
+pub fn service_status(context: &Context, service: &ContextService) -> Result<ServiceStatus> {
+    let pid = running_pid(&context.name, &service.name)?;
+    let files = service_files(&context.name, &service.name)?;
+
+    let ready = match &service.ready {
+        Some(check) => {
+            let mut parts = Vec::new();
+            if let Some(port) = check.port {
+                parts.push(format!("port {}", port));
+            }
+            if let Some(re) = &check.log_regex {
+                parts.push(format!("log /{}/", re));
+            }
+            parts.join(", ")
+        }
+        None => "-".to_string(),
+    };
+
+    Ok(ServiceStatus {
+        name: service.name.clone(),
+        state: if pid.is_some() { "running" } else { "stopped" }.to_string(),
+        pid: pid.map(|p| p.to_string()).unwrap_or_else(|| "-".to_string()),
+        ready,
+        log: files.log.to_string_lossy().to_string(),
+    })
+}
@@ -0,0 +1,279 @@
+use crate::config::{model::CtxConfig, Multiplexer};
+use crate::state::store::{Context, LayoutPane, LayoutWindow, SessionLayout};
+use crate::utils::trace::TracedCommand;
+use anyhow::{Context as AnyhowContext, Result};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Shells that are not recorded as pane commands when capturing a layout
+const SHELLS: &[&str] = &["bash", "zsh", "fish", "sh", "dash", "ksh", "pwsh", "nu"];
+
+/// Load a session layout from a TOML file
+pub fn load_layout_file(path: &Path) -> Result<SessionLayout> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read layout from {:?}", path))?;
+    let layout: SessionLayout = toml::from_str(&content)
+        .with_context(|| format!("Failed to parse layout from {:?}", path))?;
+    Ok(layout)
+}
+
+/// Record the windows and panes of the tmux session we are running in
+pub fn capture_tmux_layout(working_dir: &Path) -> Result<SessionLayout> {
+    if env::var_os("TMUX").is_none() {
+        return Err(anyhow::anyhow!("--capture-tmux must be run inside a tmux session"));
+    }
+
+    let mut windows = Vec::new();
+    let window_list = tmux(&["list-windows", "-F", "#{window_id}\t#{window_name}\t#{window_layout}"])?;
+
+    for line in window_list.lines() {
+        let mut fields = line.splitn(3, '\t');
+        let (Some(id), Some(name), Some(layout)) = (fields.next(), fields.next(), fields.next()) else {
+            continue;
+        };
+
+        let pane_list = tmux(&["list-panes", "-t", id, "-F", "#{pane_current_path}\t#{pane_current_command}"])?;
+        let panes = pane_list
+            .lines()
+            .map(|pane| {
+                let (path, command) = pane.split_once('\t').unwrap_or((pane, ""));
+                LayoutPane {
+                    cwd: relative_cwd(working_dir, Path::new(path)),
+                    // Only the program name is available from tmux, not its arguments
+                    command: (!command.is_empty() && !SHELLS.contains(&command))
+                        .then(|| command.to_string()),
+                }
+            })
+            .collect();
+
+        windows.push(LayoutWindow {
+            name: name.to_string(),
+            layout: Some(layout.to_string()),
+            panes,
+        });
+    }
+
+    Ok(SessionLayout { windows })
+}
+
+fn relative_cwd(working_dir: &Path, path: &Path) -> Option<String> {
+    match path.strip_prefix(working_dir) {
+        Ok(rel) if rel.as_os_str().is_empty() => None,
+        Ok(rel) => Some(rel.to_string_lossy().to_string()),
+        Err(_) => Some(path.to_string_lossy().to_string()),
+    }
+}
+
+/// Pick the multiplexer: explicit flag, then config, then the one we are running inside
+pub fn resolve_multiplexer(requested: Option<Multiplexer>, config: &CtxConfig) -> Multiplexer {
+    requested
+        .or(config.multiplexer)
+        .unwrap_or_else(|| {
+            if env::var_os("ZELLIJ").is_some() {
+                Multiplexer::Zellij
+            } else {
+                Multiplexer::Tmux
+            }
+        })
+}
+
+/// Session name derived from the context name (tmux forbids '.' and ':')
+pub fn session_name(context: &Context) -> String {
+    context.name.replace(['.', ':'], "_")
+}
+
+/// Create the context's session if needed and return the command
+/// (as argv) the calling shell should run to attach to it
+pub fn open_session(context: &Context, multiplexer: Multiplexer) -> Result<Vec<String>> {
+    match multiplexer {
+        Multiplexer::Tmux => open_tmux_session(context),
+        Multiplexer::Zellij => open_zellij_session(context),
+    }
+}
+
+fn open_tmux_session(context: &Context) -> Result<Vec<String>> {
+    let name = session_name(context);
+    let target = format!("={}", name);
+
+    let exists = Command::new("tmux")
+        .args(["has-session", "-t", &target])
+        .traced_output()
+        .context("Failed to run tmux. Is it installed?")?
+        .status
+        .success();
+
+    if !exists {
+        eprintln!("Creating tmux session '{}'...", name);
+        create_tmux_session(context, &name)?;
+    }
+
+    let command = if env::var_os("TMUX").is_some() { "switch-client" } else { "attach-session" };
+    Ok(vec!["tmux".to_string(), command.to_string(), "-t".to_string(), target])
+}
+
+fn create_tmux_session(context: &Context, name: &str) -> Result<()> {
+    let env_args: Vec<String> = context.env_vars
+        .iter()
+        .flat_map(|(key, value)| ["-e".to_string(), format!("{}={}", key, value)])
+        .collect();
+
+    let mut first_window_pane = None;
+
+    for (index, window) in layout_windows(context).iter().enumerate() {
+        let panes = if window.panes.is_empty() {
+            vec![LayoutPane::default()]
+        } else {
+            window.panes.clone()
+        };
+
+        let mut pane_ids: Vec<String> = Vec::new();
+        for (pane_index, pane) in panes.iter().enumerate() {
+            let cwd = pane_dir(context, pane).to_string_lossy().to_string();
+            let mut args: Vec<String> = if pane_index > 0 {
+                vec!["split-window".into(), "-d".into(), "-t".into(), pane_ids[0].clone()]
+            } else if index == 0 {
+                vec!["new-session".into(), "-d".into(), "-s".into(), name.to_string(), "-n".into(), window.name.clone()]
+            } else {
+                vec!["new-window".into(), "-d".into(), "-t".into(), format!("={}:", name), "-n".into(), window.name.clone()]
+            };
+            args.extend(["-c".to_string(), cwd, "-P".to_string(), "-F".to_string(), "#{pane_id}".to_string()]);
+            args.extend(env_args.iter().cloned());
+
+            let arg_refs: Vec<&str> = args.iter().map(String::as_str).collect();
+            pane_ids.push(tmux(&arg_refs)?);
+        }
+
+        let layout = window.layout.as_deref()
+            .or_else(|| (panes.len() > 1).then_some("tiled"));
+        if let Some(layout) = layout {
+            tmux(&["select-layout", "-t", &pane_ids[0], layout])?;
+        }
+
+        for (pane, pane_id) in panes.iter().zip(&pane_ids) {
+            if let Some(command) = &pane.command {
+                tmux(&["send-keys", "-t", pane_id, command, "Enter"])?;
+            }
+        }
+
+        if first_window_pane.is_none() {
+            first_window_pane = pane_ids.first().cloned();
+        }
+    }
+
+    if let Some(pane) = first_window_pane {
+        tmux(&["select-window", "-t", &pane])?;
+    }
+
+    Ok(())
+}
+
+fn open_zellij_session(context: &Context) -> Result<Vec<String>> {
+    let name = session_name(context);
+
+    let layout_path = crate::platform::context_run_dir(&context.name)?.join("layout.kdl");
+    fs::write(&layout_path, zellij_layout(context))
+        .with_context(|| format!("Failed to write zellij layout to {:?}", layout_path))?;
+    let layout_arg = layout_path.to_string_lossy().to_string();
+
+    if env::var_os("ZELLIJ").is_some() {
+        // Zellij sessions cannot be nested, so open the layout as new tabs instead
+        eprintln!("Already inside zellij; opening the layout in new tabs.");
+        return Ok(vec!["zellij".into(), "action".into(), "new-tab".into(), "--layout".into(), layout_arg]);
+    }
+
+    let sessions = Command::new("zellij")
+        .args(["list-sessions", "--short", "--no-formatting"])
+        .traced_output()
+        .context("Failed to run zellij. Is it installed?")?;
+    let exists = String::from_utf8_lossy(&sessions.stdout)
+        .lines()
+        .any(|line| line.trim() == name);
+
+    if exists {
+        Ok(vec!["zellij".into(), "attach".into(), name])
+    } else {
+        // Panes inherit the environment exported by the switch script
+        Ok(vec!["zellij".into(), "--session".into(), name, "--layout".into(), layout_arg])
+    }
+}
+
+fn zellij_layout(context: &Context) -> String {
+    let mut kdl = String::from("layout {\n");
+    kdl.push_str("    default_tab_template {\n");
+    kdl.push_str("        pane size=1 borderless=true { plugin location=\"zellij:tab-bar\"; }\n");
+    kdl.push_str("        children\n");
+    kdl.push_str("        pane size=2 borderless=true { plugin location=\"zellij:status-bar\"; }\n");
+    kdl.push_str("    }\n");
+
+    for window in layout_windows(context) {
+        kdl.push_str(&format!("    tab name={} {{\n", kdl_string(&window.name)));
+
+        let panes = if window.panes.is_empty() {
+            vec![LayoutPane::default()]
+        } else {
+            window.panes
+        };
+
+        for pane in &panes {
+            let cwd = kdl_string(&pane_dir(context, pane).to_string_lossy());
+            match &pane.command {
+                Some(command) => {
+                    // Drop back to a shell when the command exits, like a tmux pane would
+                    let script = format!("{}; exec \"${{SHELL:-sh}}\"", command);
+                    kdl.push_str(&format!("        pane cwd={} command=\"sh\" {{\n", cwd));
+                    kdl.push_str(&format!("            args \"-c\" {}\n", kdl_string(&script)));
+                    kdl.push_str("        }\n");
+                }
+                None => kdl.push_str(&format!("        pane cwd={}\n", cwd)),
+            }
+        }
+
+        kdl.push_str("    }\n");
+    }
+
+    kdl.push_str("}\n");
+    kdl
+}
+
+fn kdl_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Windows to create; a context without a layout gets a single shell window
+fn layout_windows(context: &Context) -> Vec<LayoutWindow> {
+    match &context.layout {
+        Some(layout) if !layout.windows.is_empty() => layout.windows.clone(),
+        _ => vec![LayoutWindow {
+            name: context.name.clone(),
+            layout: None,
+            panes: Vec::new(),
+        }],
+    }
+}
+
+fn pane_dir(context: &Context, pane: &LayoutPane) -> PathBuf {
+    let working_dir = Path::new(&context.working_dir);
+    match &pane.cwd {
+        Some(cwd) => working_dir.join(cwd),
+        None => working_dir.to_path_buf(),
+    }
+}
+
+fn tmux(args: &[&str]) -> Result<String> {
+    let output = Command::new("tmux")
+        .args(args)
+        .traced_output()
+        .context("Failed to run tmux. Is it installed?")?;
+
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "tmux {} failed: {}",
+            args.first().unwrap_or(&""),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
@@ -1,5 +1,6 @@
 use crate::app::AppContext;
 use crate::cli::{DbArgs, DbCommand};
+use crate::utils::trace::TracedCommand;
 use anyhow::Result;
 use std::process::Command;
 
@@ -25,7 +26,7 @@ fn list_dbs() -> Result<()> {
 fn connect(name: &str) -> Result<()> {
     println!("🔌 Connecting to '{}'...", name);
     if name == "local" {
-        Command::new("sqlite3").arg("dev.db").status()?;
+        Command::new("sqlite3").arg("dev.db").traced_status()?;
     } else if name == "staging" {
         println!("Assume postgres...");
          // Command::new("psql")... 
@@ -49,7 +50,7 @@ fn migrate() -> Result<()> {
     println!("🏗️  Running migrations...");
     if std::path::Path::new("sqlx-data.json").exists() {
          println!("Detected sqlx project. Running sqlx migrate run...");
-         Command::new("sqlx").arg("migrate").arg("run").status()?;
+         Command::new("sqlx").arg("migrate").arg("run").traced_status()?;
     } else {
         println!("No migration system detected.");
     }
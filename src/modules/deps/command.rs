@@ -1,5 +1,6 @@
 use crate::app::AppContext;
 use crate::cli::{DepsArgs, DepsCommand};
+use crate::utils::trace::TracedCommand;
 use anyhow::Result;
 use std::path::Path;
 use std::process::Command;
@@ -20,10 +21,10 @@ fn scan() -> Result<()> {
     if Path::new("Cargo.toml").exists() {
         println!("🦀 Detected Rust project");
         // Check if cargo-audit is installed
-        let check = Command::new("cargo").arg("audit").arg("--version").output();
+        let check = Command::new("cargo").arg("audit").arg("--version").traced_output();
         if let Ok(output) = check {
             if output.status.success() {
-                let status = Command::new("cargo").arg("audit").status()?;
+                let status = Command::new("cargo").arg("audit").traced_status()?;
                  if !status.success() {
                     println!("⚠️  Vulnerabilities found!");
                 } else {
@@ -39,7 +40,7 @@ fn scan() -> Result<()> {
     
     if Path::new("package.json").exists() {
         println!("📦 Detected Node.js project");
-        let status = Command::new("npm").arg("audit").status()?;
+        let status = Command::new("npm").arg("audit").traced_status()?;
         if !status.success() {
             println!("⚠️  Vulnerabilities found!");
         } else {
@@ -55,10 +56,10 @@ fn outdated() -> Result<()> {
     
     if Path::new("Cargo.toml").exists() {
         // cargo outdated
-        let check = Command::new("cargo").arg("outdated").arg("--version").output();
+        let check = Command::new("cargo").arg("outdated").arg("--version").traced_output();
         if let Ok(output) = check {
              if output.status.success() {
-                Command::new("cargo").arg("outdated").status()?;
+                Command::new("cargo").arg("outdated").traced_status()?;
              } else {
                  println!("⚠️  cargo-outdated not installed. Run `cargo install cargo-outdated`");
              }
@@ -68,7 +69,7 @@ fn outdated() -> Result<()> {
     }
     
     if Path::new("package.json").exists() {
-        Command::new("npm").arg("outdated").status()?;
+        Command::new("npm").arg("outdated").traced_status()?;
     }
     
     Ok(())
@@ -82,20 +83,20 @@ fn update(safe: bool) -> Result<()> {
             // Rust doesn't have a direct "safe only" update like npm strictly,
             // but `cargo update` usually updates to latest compatible (semver).
             println!("Running cargo update...");
-            Command::new("cargo").arg("update").status()?;
+            Command::new("cargo").arg("update").traced_status()?;
         } else {
             println!("Running cargo update...");
-            Command::new("cargo").arg("update").status()?;
+            Command::new("cargo").arg("update").traced_status()?;
         }
     }
     
     if Path::new("package.json").exists() {
         if safe {
             println!("Running npm update...");
-             Command::new("npm").arg("update").status()?;
+             Command::new("npm").arg("update").traced_status()?;
         } else {
              println!("To update major versions, consider using `npm install <pkg>@latest` or `ncu`");
-             Command::new("npm").arg("update").status()?;
+             Command::new("npm").arg("update").traced_status()?;
         }
     }
     
@@ -1,13 +1,23 @@
 use crate::app::AppContext;
-use crate::cli::{DocsArgs, DocsCommand};
-use crate::utils::llm::call_llm_json;
+use crate::cli::{DocsArgs, DocsCommand, ProviderArgs};
+use crate::utils::llm::{stream_llm, LlmResponse, Prompt};
 use anyhow::{Context, Result};
 use std::fs;
 
-pub fn run(args: DocsArgs, _app_ctx: &mut AppContext) -> Result<()> {
+/// Generated Markdown
+#[derive(serde::Deserialize)]
+struct MarkdownResponse {
+    markdown: String,
+}
+
+impl LlmResponse for MarkdownResponse {
+    const SCHEMA: &'static str = r#"{"markdown": string}"#;
+}
+
+pub fn run(args: DocsArgs, app_ctx: &mut AppContext) -> Result<()> {
     match args.command {
-        DocsCommand::Generate { file } => generate_docs(&file),
-        DocsCommand::Readme => generate_readme(),
+        DocsCommand::Generate { file, llm } => generate_docs(&file, &llm, app_ctx),
+        DocsCommand::Readme { llm } => generate_readme(&llm, app_ctx),
         DocsCommand::Api => {
             println!("API documentation generation not implemented yet.");
             Ok(())
@@ -23,7 +33,7 @@ pub fn run(args: DocsArgs, _app_ctx: &mut AppContext) -> Result<()> {
     }
 }
 
-fn generate_docs(file_path: &str) -> Result<()> {
+fn generate_docs(file_path: &str, llm: &ProviderArgs, app_ctx: &mut AppContext) -> Result<()> {
     println!("📖 Generating documentation for {}...", file_path);
     
     let content = fs::read_to_string(file_path)
@@ -34,21 +44,23 @@ fn generate_docs(file_path: &str) -> Result<()> {
         content
     );
     
-    let _response = call_llm_json(
-        "You are a technical writer. Generate markdown documentation. Return JSON with 'markdown' field.", 
-        &prompt
-    )?;
-    
-    // Actually call_llm_json returns a Value.
-    let markdown = _response["markdown"].as_str().unwrap_or("Failed to generate documentation.").to_string();
-    
     println!("\nGenerated Documentation:\n");
-    println!("{}", markdown);
+    let prompt = Prompt {
+        command: "docs generate",
+        system: "You are a technical writer. Generate markdown documentation.",
+        user: &prompt,
+        project_dir: None,
+        no_cache: false,
+    };
+    let response: MarkdownResponse = stream_llm(app_ctx, llm, &prompt, "markdown")?;
+    if response.markdown.trim().is_empty() {
+        return Err(anyhow::anyhow!("The model returned no documentation"));
+    }
     
     Ok(())
 }
 
-fn generate_readme() -> Result<()> {
+fn generate_readme(llm: &ProviderArgs, app_ctx: &mut AppContext) -> Result<()> {
     println!("📖 Generating README.md...");
     
     // Simple tree structure (limited depth)
@@ -68,15 +80,18 @@ fn generate_readme() -> Result<()> {
         tree_str
     );
     
-     let _response = call_llm_json(
-        "You are a technical writer. Generate README.md. Return JSON with 'markdown' field.", 
-        &prompt
-    )?;
-    
-    let markdown = _response["markdown"].as_str().unwrap_or("Failed to generate README.").to_string();
-    
     println!("\nGenerated README:\n");
-    println!("{}", markdown);
+    let prompt = Prompt {
+        command: "docs readme",
+        system: "You are a technical writer. Generate README.md.",
+        user: &prompt,
+        project_dir: None,
+        no_cache: false,
+    };
+    let response: MarkdownResponse = stream_llm(app_ctx, llm, &prompt, "markdown")?;
+    if response.markdown.trim().is_empty() {
+        return Err(anyhow::anyhow!("The model returned an empty README"));
+    }
     
     Ok(())
 }
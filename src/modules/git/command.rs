@@ -1,13 +1,24 @@
 use crate::app::AppContext;
-use crate::cli::{GitArgs, GitCommand};
-use crate::utils::llm::call_llm_json;
+use crate::cli::{GitArgs, GitCommand, ProviderArgs};
+use crate::utils::llm::{stream_llm, LlmResponse, Prompt};
+use crate::utils::trace::TracedCommand;
 use anyhow::{Context, Result};
 use dialoguer::{theme::ColorfulTheme, MultiSelect, Confirm};
 use std::process::Command;
 
-pub fn run(args: GitArgs, _app_ctx: &mut AppContext) -> Result<()> {
+/// A generated commit message
+#[derive(serde::Deserialize)]
+struct CommitResponse {
+    commit_message: String,
+}
+
+impl LlmResponse for CommitResponse {
+    const SCHEMA: &'static str = r#"{"commit_message": string}"#;
+}
+
+pub fn run(args: GitArgs, app_ctx: &mut AppContext) -> Result<()> {
     match args.command {
-        GitCommand::SmartCommit { hint, dry_run } => smart_commit(hint, dry_run),
+        GitCommand::SmartCommit { hint, dry_run, llm } => smart_commit(hint, dry_run, &llm, app_ctx),
         GitCommand::Review => review(),
         GitCommand::Cleanup { dry_run } => cleanup(dry_run),
         GitCommand::Stats => stats(),
@@ -15,19 +26,19 @@ pub fn run(args: GitArgs, _app_ctx: &mut AppContext) -> Result<()> {
     }
 }
 
-fn smart_commit(hint: Option<String>, dry_run: bool) -> Result<()> {
+fn smart_commit(hint: Option<String>, dry_run: bool, llm: &ProviderArgs, app_ctx: &mut AppContext) -> Result<()> {
     // 1. Get staged changes
     let mut diff_output = Command::new("git")
-        .args(&["diff", "--staged"])
-        .output()
+        .args(["diff", "--staged"])
+        .traced_output()
         .context("Failed to run git diff --staged")?;
         
     // If no staged changes, try unstaged to be helpful (but warn)
     if diff_output.stdout.is_empty() {
         println!("No staged changes found. using unstaged changes for context...");
         diff_output = Command::new("git")
-            .args(&["diff"])
-            .output()
+            .args(["diff"])
+            .traced_output()
             .context("Failed to run git diff")?;
             
         if diff_output.stdout.is_empty() {
@@ -44,22 +55,22 @@ fn smart_commit(hint: Option<String>, dry_run: bool) -> Result<()> {
     // 2. Call LLM
     let hint_text = hint.map(|h| format!("User hint: '{}'", h)).unwrap_or_default();
     let prompt = format!(
-        "Generate a conventional commit message for these changes. {} \n\nOutput JSON with 'commit_message' field.\n\nDiff:\n{}", 
+        "Generate a conventional commit message for these changes. {} \n\nDiff:\n{}", 
         hint_text, truncated_diff
     );
     
     println!("🤖 Generating commit message...");
-    let response = call_llm_json(
-        "You are an expert developer. Generate valid conventional commits. Return JSON.", 
-        &prompt
-    ).context("Failed to generate commit message")?;
-    
-    let message = response["commit_message"]
-        .as_str()
-        .ok_or_else(|| anyhow::anyhow!("Invalid response"))?
-        .to_string();
-        
-    println!("\nSuggested Commit Message:\n{}", message);
+    println!("\nSuggested Commit Message:");
+    let prompt = Prompt {
+        command: "git smart-commit",
+        system: "You are an expert developer. Generate valid conventional commits.",
+        user: &prompt,
+        project_dir: None,
+        no_cache: false,
+    };
+    let response: CommitResponse = stream_llm(app_ctx, llm, &prompt, "commit_message")
+        .context("Failed to generate commit message")?;
+    let message = response.commit_message;
     
     if dry_run {
         return Ok(());
@@ -74,14 +85,14 @@ fn smart_commit(hint: Option<String>, dry_run: bool) -> Result<()> {
         // If we were looking at unstaged, stage them now? No, that's dangerous.
         // But if `git diff --staged` was empty, we can't commit.
         // Let's re-verify staged.
-        let check = Command::new("git").args(&["diff", "--staged", "--quiet"]).status()?;
+        let check = Command::new("git").args(["diff", "--staged", "--quiet"]).traced_status()?;
         if check.success() { 
              if Confirm::with_theme(&ColorfulTheme::default())
                 .with_prompt("No staged changes. Stage all changes and commit?")
                 .default(false)
                 .interact()? 
             {
-                Command::new("git").arg("add").arg(".").status()?;
+                Command::new("git").arg("add").arg(".").traced_status()?;
             } else {
                 return Ok(());
             }
@@ -91,7 +102,7 @@ fn smart_commit(hint: Option<String>, dry_run: bool) -> Result<()> {
             .arg("commit")
             .arg("-m")
             .arg(&message)
-            .status()
+            .traced_status()
             .context("Failed to commit")?;
             
         if status.success() {
@@ -134,14 +145,14 @@ fn cleanup(dry_run: bool) -> Result<()> {
     // 1. Prune remotes
     println!("🧹 Pruning stale remote branches...");
     if !dry_run {
-        Command::new("git").args(&["remote", "prune", "origin"]).status()?;
+        Command::new("git").args(["remote", "prune", "origin"]).traced_status()?;
     }
     
     // 2. Delete merged branches
     println!("🧹 Checking for merged branches...");
     let output = Command::new("git")
-        .args(&["branch", "--merged"])
-        .output()?;
+        .args(["branch", "--merged"])
+        .traced_output()?;
         
     let branches = String::from_utf8_lossy(&output.stdout);
     let mut to_delete = Vec::new();
@@ -167,7 +178,7 @@ fn cleanup(dry_run: bool) -> Result<()> {
                 .interact()?
             {
                 for branch in to_delete {
-                    Command::new("git").args(&["branch", "-d", &branch]).status()?;
+                    Command::new("git").args(["branch", "-d", &branch]).traced_status()?;
                     println!("Deleted {}", branch);
                 }
             }
@@ -180,8 +191,8 @@ fn cleanup(dry_run: bool) -> Result<()> {
 fn stats() -> Result<()> {
     println!("📊 Contribution Stats:\n");
     Command::new("git")
-        .args(&["shortlog", "-sn", "--all", "--no-merges"])
-        .spawn()?
+        .args(["shortlog", "-sn", "--all", "--no-merges"])
+        .traced_spawn()?
         .wait()?;
     Ok(())
 }
@@ -192,8 +203,8 @@ fn worktree(name: String) -> Result<()> {
     let path = format!("../{}", name);
     
     let status = Command::new("git")
-        .args(&["worktree", "add", &path, &name])
-        .status()?;
+        .args(["worktree", "add", &path, &name])
+        .traced_status()?;
         
     if status.success() {
         println!("✅ Worktree created at {}", path);
@@ -0,0 +1,229 @@
+use crate::app::AppContext;
+use crate::cli::{InitArgs, InitShell};
+use crate::platform::Shell;
+use anyhow::Result;
+
+/// Placeholder for the quoted `~/.dev/run` directory in the scripts below
+const RUN_DIR: &str = "__DEV_RUN_DIR__";
+
+/// Records failed commands into `last` as `status=`, `cwd=` and `cmd=` lines.
+/// With bash-preexec loaded its `preexec`/`precmd` hooks are used. Otherwise a
+/// DEBUG trap, chained in front of any existing one, marks the start of the
+/// first command typed at the prompt: `__dev_at_prompt` is 1 while waiting at
+/// the prompt and 0 once a command started, so `__dev_precmd` only records when
+/// something ran. The trap skips PROMPT_COMMAND, which starts with `__dev_precmd`.
+const BASH_HOOK: &str = r#"__dev_run=__DEV_RUN_DIR__
+__dev_record() {
+    command mkdir -p "$__dev_run"
+    {
+        printf 'status=%s\n' "$1"
+        printf 'cwd=%s\n' "$__dev_cwd"
+        printf 'cmd=%s\n' "$2"
+    } > "$__dev_run/last"
+    if [ -n "$__dev_live" ]; then
+        command tail -c 65536 "$__dev_live" > "$__dev_run/last.stderr"
+    else
+        command rm -f "$__dev_run/last.stderr"
+    fi
+}
+__dev_start() {
+    __dev_cwd=$PWD
+    [ -z "$__dev_live" ] || : > "$__dev_live"
+}
+if [ -n "${bash_preexec_imported:-}${__bp_imported:-}" ]; then
+    __dev_preexec() {
+        __dev_cmd=$1
+        __dev_start
+    }
+    __dev_precmd() {
+        local status=$?
+        if [ -n "$__dev_cmd" ] && [ "$status" -ne 0 ]; then
+            __dev_record "$status" "$__dev_cmd"
+        fi
+        __dev_cmd=
+    }
+    preexec_functions+=(__dev_preexec)
+    # First, so it still sees the command's exit status
+    precmd_functions=(__dev_precmd "${precmd_functions[@]}")
+else
+    # Returns the status it is given so a chained trap still sees $?
+    __dev_preexec() {
+        if [ "$__dev_at_prompt" = 1 ] && [ "$BASH_COMMAND" != __dev_precmd ]; then
+            __dev_at_prompt=0
+            __dev_start
+        fi
+        return "$1"
+    }
+    __dev_precmd() {
+        local status=$?
+        if [ "$__dev_at_prompt" = 0 ] && [ "$status" -ne 0 ]; then
+            __dev_record "$status" "$(HISTTIMEFORMAT= builtin history 1 | command sed 's/^ *[0-9]* *//')"
+        fi
+    }
+    __dev_prompt_ready() {
+        __dev_at_prompt=1
+    }
+    __dev_at_prompt=1
+    __dev_trap=$(trap -p DEBUG)
+    __dev_trap=${__dev_trap#"trap -- "}
+    __dev_trap=${__dev_trap%" DEBUG"}
+    eval "__dev_trap=${__dev_trap:-''}"
+    case $__dev_trap in
+        *__dev_preexec*) ;;
+        *) __dev_prev_debug=$__dev_trap ;;
+    esac
+    unset __dev_trap
+    if [ -n "${__dev_prev_debug:-}" ]; then
+        trap '__dev_preexec "$?"; eval "$__dev_prev_debug"' DEBUG
+    else
+        trap '__dev_preexec "$?"' DEBUG
+    fi
+    PROMPT_COMMAND="__dev_precmd${PROMPT_COMMAND:+;$PROMPT_COMMAND};__dev_prompt_ready"
+fi
+"#;
+
+/// Sends the whole shell's stderr through `tee`, so commands see a pipe rather
+/// than a terminal on stderr
+const BASH_CAPTURE: &str = r#"# stderr is a pipe from here on: programs that check for a terminal on it
+# (colours, progress bars, full-screen tools) behave as if redirected
+command mkdir -p "$__dev_run"
+__dev_live="$__dev_run/stderr.$$"
+exec 2> >(command tee -a "$__dev_live" >&2)
+trap 'command rm -f "$__dev_live"' EXIT
+"#;
+
+const ZSH_HOOK: &str = r#"__dev_run=__DEV_RUN_DIR__
+__dev_record() {
+    command mkdir -p "$__dev_run"
+    {
+        printf 'status=%s\n' "$1"
+        printf 'cwd=%s\n' "$__dev_cwd"
+        printf 'cmd=%s\n' "$2"
+    } > "$__dev_run/last"
+    if [[ -n $__dev_live ]]; then
+        command tail -c 65536 "$__dev_live" > "$__dev_run/last.stderr"
+    else
+        command rm -f "$__dev_run/last.stderr"
+    fi
+}
+__dev_preexec() {
+    __dev_cmd=$1
+    __dev_cwd=$PWD
+    [[ -z $__dev_live ]] || : > "$__dev_live"
+}
+__dev_precmd() {
+    local st=$?
+    if [[ -n $__dev_cmd && $st -ne 0 ]]; then
+        __dev_record "$st" "$__dev_cmd"
+    fi
+    __dev_cmd=
+}
+autoload -Uz add-zsh-hook
+add-zsh-hook preexec __dev_preexec
+# First, so it still sees the command's exit status
+precmd_functions=(__dev_precmd ${precmd_functions:#__dev_precmd})
+"#;
+
+const ZSH_CAPTURE: &str = r#"# stderr is a pipe from here on: programs that check for a terminal on it
+# (colours, progress bars, full-screen tools) behave as if redirected
+command mkdir -p "$__dev_run"
+__dev_live="$__dev_run/stderr.$$"
+exec 2> >(command tee -a "$__dev_live" >&2)
+__dev_cleanup() { command rm -f "$__dev_live"; }
+add-zsh-hook zshexit __dev_cleanup
+"#;
+
+const FISH_HOOK: &str = r#"set -g __dev_run __DEV_RUN_DIR__
+function __dev_preexec --on-event fish_preexec
+    set -g __dev_cwd $PWD
+end
+function __dev_postexec --on-event fish_postexec
+    set -l st $status
+    test $st -ne 0; or return
+    command mkdir -p $__dev_run
+    printf 'status=%s\ncwd=%s\ncmd=%s\n' $st $__dev_cwd "$argv" > $__dev_run/last
+    command rm -f $__dev_run/last.stderr
+end
+"#;
+
+/// Ctrl-X Ctrl-P opens `dev snip pick` and inserts the chosen command at the
+/// cursor, to edit before running it
+const BASH_SNIP_WIDGET: &str = r#"__dev_snip_widget() {
+    local cmd
+    cmd=$(command dev snip pick --print </dev/tty) || return
+    READLINE_LINE="${READLINE_LINE:0:READLINE_POINT}$cmd${READLINE_LINE:READLINE_POINT}"
+    READLINE_POINT=$((READLINE_POINT + ${#cmd}))
+}
+bind -x '"\C-x\C-p": __dev_snip_widget'
+"#;
+
+const ZSH_SNIP_WIDGET: &str = r#"__dev_snip_widget() {
+    local cmd
+    cmd=$(command dev snip pick --print </dev/tty)
+    [[ -n $cmd ]] && LBUFFER+=$cmd
+    zle reset-prompt
+}
+zle -N __dev_snip_widget
+bindkey '^X^P' __dev_snip_widget
+"#;
+
+const FISH_SNIP_WIDGET: &str = r#"function __dev_snip_widget
+    set -l cmd (command dev snip pick --print </dev/tty | string collect)
+    test -n "$cmd"; and commandline -i -- $cmd
+    commandline -f repaint
+end
+bind \cx\cp __dev_snip_widget
+"#;
+
+/// Print the shell integration script for the requested (or current) shell
+pub fn run(args: InitArgs, _app_ctx: &mut AppContext) -> Result<()> {
+    let shell = match args.shell {
+        Some(shell) => shell,
+        None => match crate::platform::detect_shell() {
+            Some(Shell::Bash) => InitShell::Bash,
+            Some(Shell::Zsh) => InitShell::Zsh,
+            Some(Shell::Fish) => InitShell::Fish,
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "Could not detect a supported shell. Pass one of: bash, zsh, fish"
+                ));
+            }
+        },
+    };
+
+    let run_dir = crate::platform::dev_home()?.join("run");
+    let run_dir = match shell {
+        InitShell::Fish => escape_fish(&run_dir.to_string_lossy()),
+        _ => escape_shell(&run_dir.to_string_lossy()),
+    };
+
+    let mut script = match shell {
+        InitShell::Bash => BASH_HOOK.to_string(),
+        InitShell::Zsh => ZSH_HOOK.to_string(),
+        InitShell::Fish => FISH_HOOK.to_string(),
+    };
+    script.push_str(match shell {
+        InitShell::Bash => BASH_SNIP_WIDGET,
+        InitShell::Zsh => ZSH_SNIP_WIDGET,
+        InitShell::Fish => FISH_SNIP_WIDGET,
+    });
+
+    if args.capture_stderr {
+        match shell {
+            InitShell::Bash => script.push_str(BASH_CAPTURE),
+            InitShell::Zsh => script.push_str(ZSH_CAPTURE),
+            InitShell::Fish => eprintln!("⚠️  --capture-stderr is not supported for fish; only commands are recorded."),
+        }
+    }
+
+    print!("{}", script.replace(RUN_DIR, &run_dir));
+    Ok(())
+}
+
+fn escape_shell(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+fn escape_fish(s: &str) -> String {
+    format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'"))
+}
@@ -0,0 +1,104 @@
+use crate::app::AppContext;
+use crate::cli::{LlmArgs, LlmCommand};
+use crate::state::store::LlmUsage;
+use crate::utils::llm::usage;
+use anyhow::Result;
+use std::collections::BTreeMap;
+use tabled::{Table, Tabled};
+
+/// Run the LLM usage command
+pub fn run(args: LlmArgs, app_ctx: &mut AppContext) -> Result<()> {
+    match args.command {
+        LlmCommand::Usage { since } => show_usage(&since, app_ctx),
+        LlmCommand::ClearCache => {
+            let deleted = app_ctx.state.clear_llm_cache()?;
+            println!("✓ Deleted {} cached LLM repl{}", deleted, if deleted == 1 { "y" } else { "ies" });
+            Ok(())
+        }
+    }
+}
+
+/// A period such as `30m`, `24h`, `7d` or `4w`
+fn parse_period(period: &str) -> Result<chrono::Duration> {
+    let invalid = || anyhow::anyhow!("Invalid period '{}': use a number followed by m, h, d or w, e.g. 7d", period);
+    let period = period.trim();
+    let unit = period.chars().last().ok_or_else(invalid)?;
+    let amount: i64 = period[..period.len() - unit.len_utf8()].parse().map_err(|_| invalid())?;
+    match unit {
+        'm' => Ok(chrono::Duration::minutes(amount)),
+        'h' => Ok(chrono::Duration::hours(amount)),
+        'd' => Ok(chrono::Duration::days(amount)),
+        'w' => Ok(chrono::Duration::weeks(amount)),
+        _ => Err(invalid()),
+    }
+}
+
+#[derive(Tabled)]
+struct UsageRow {
+    #[tabled(rename = "Command")]
+    command: String,
+    #[tabled(rename = "Calls")]
+    calls: usize,
+    #[tabled(rename = "Cached")]
+    cached: usize,
+    #[tabled(rename = "Input tokens")]
+    input_tokens: u64,
+    #[tabled(rename = "Output tokens")]
+    output_tokens: u64,
+    #[tabled(rename = "Cost")]
+    cost: String,
+}
+
+impl UsageRow {
+    fn new(command: &str, calls: &[&LlmUsage]) -> Self {
+        UsageRow {
+            command: command.to_string(),
+            calls: calls.len(),
+            cached: calls.iter().filter(|usage| usage.cached).count(),
+            input_tokens: calls.iter().map(|usage| usage.input_tokens).sum(),
+            output_tokens: calls.iter().map(|usage| usage.output_tokens).sum(),
+            cost: format!("${:.4}", usage::total_cost(calls.iter().copied())),
+        }
+    }
+}
+
+fn show_usage(since: &str, app_ctx: &AppContext) -> Result<()> {
+    let start = chrono::Utc::now() - parse_period(since)?;
+    let calls = app_ctx.state.list_llm_usage(&usage::timestamp(start))?;
+    if calls.is_empty() {
+        println!("No LLM calls in the last {}.", since);
+    } else {
+        let mut by_command: BTreeMap<&str, Vec<&LlmUsage>> = BTreeMap::new();
+        for call in &calls {
+            by_command.entry(call.command.as_str()).or_default().push(call);
+        }
+
+        let mut rows: Vec<UsageRow> = by_command.iter().map(|(command, calls)| UsageRow::new(command, calls)).collect();
+        rows.push(UsageRow::new("Total", &calls.iter().collect::<Vec<_>>()));
+        println!("LLM usage in the last {}:\n", since);
+        println!("{}", Table::new(rows));
+
+        let unpriced = calls.iter().filter(|usage| usage.cost.is_none()).count();
+        if unpriced > 0 {
+            println!(
+                "\n{} call(s) to models with unknown prices are not in the cost. \
+                 Set input_price and output_price under [llm.providers.<name>].",
+                unpriced
+            );
+        }
+    }
+
+    let budget = &app_ctx.config.llm.budget;
+    if budget.daily_usd.is_some() || budget.monthly_usd.is_some() {
+        let (today, month) = usage::budget_periods();
+        println!();
+        if let Some(limit) = budget.daily_usd {
+            println!("Today: ${:.2} of ${:.2} daily budget", usage::spent_since(app_ctx.state.as_ref(), &today)?, limit);
+        }
+        if let Some(limit) = budget.monthly_usd {
+            println!("This month: ${:.2} of ${:.2} monthly budget", usage::spent_since(app_ctx.state.as_ref(), &month)?, limit);
+        }
+    }
+
+    Ok(())
+}
@@ -6,6 +6,10 @@ pub mod deps;
 pub mod docs;
 pub mod secrets;
 pub mod db;
+pub mod sync;
+pub mod trust;
+pub mod init;
+pub mod llm;
 
 
 
@@ -4,12 +4,12 @@ use anyhow::Result;
 use keyring::Entry;
 use dialoguer::{theme::ColorfulTheme, Password};
 
-pub fn run(args: SecretsArgs, _app_ctx: &mut AppContext) -> Result<()> {
+pub fn run(args: SecretsArgs, app_ctx: &mut AppContext) -> Result<()> {
     match args.command {
-        SecretsCommand::Add { key, value } => add_secret(&key, value),
+        SecretsCommand::Add { key, value } => add_secret(&key, value, app_ctx),
         SecretsCommand::Get { key } => get_secret(&key),
-        SecretsCommand::List => list_secrets(),
-        SecretsCommand::Rotate { key } => rotate_secret(&key),
+        SecretsCommand::List => list_secrets(app_ctx),
+        SecretsCommand::Rotate { key } => rotate_secret(&key, app_ctx),
         SecretsCommand::Sync => {
             println!("Secrets sync not implemented.");
             Ok(())
@@ -17,7 +17,7 @@ pub fn run(args: SecretsArgs, _app_ctx: &mut AppContext) -> Result<()> {
     }
 }
 
-fn add_secret(key: &str, value: Option<String>) -> Result<()> {
+fn add_secret(key: &str, value: Option<String>, app_ctx: &mut AppContext) -> Result<()> {
     let secret = match value {
         Some(v) => v,
         None => {
@@ -29,6 +29,8 @@ fn add_secret(key: &str, value: Option<String>) -> Result<()> {
     
     let entry = Entry::new("dev-cli", key)?;
     entry.set_password(&secret)?;
+    // Remembered so `list` can show it and LLM requests can redact its value
+    app_ctx.state.save_secret_name(key)?;
     println!("✅ Secret '{}' stored securey.", key);
     Ok(())
 }
@@ -45,17 +47,20 @@ fn get_secret(key: &str) -> Result<()> {
     Ok(())
 }
 
-fn list_secrets() -> Result<()> {
+fn list_secrets(app_ctx: &AppContext) -> Result<()> {
     println!("🔑 Stored Secrets (Keys only):");
-    // keyring crate generic interface doesn't easily support listing all keys for a service 
-    // across all platforms comfortably without platform specific code.
-    // For this MVP, we might not be able to list them easily unless we store the keys list in our sqlite db.
-    
-    println!("  (Listing keys is platform dependent and not fully supported in this version. Check your system keychain for 'dev-cli' entries.)");
+    // The keyring cannot list entries portably, so the names are kept in the state database
+    let names = app_ctx.state.list_secret_names()?;
+    if names.is_empty() {
+        println!("  (none added with `dev secrets add`; check your system keychain for other 'dev-cli' entries)");
+    }
+    for name in names {
+        println!("  {}", name);
+    }
     Ok(())
 }
 
-fn rotate_secret(key: &str) -> Result<()> {
+fn rotate_secret(key: &str, app_ctx: &mut AppContext) -> Result<()> {
     // Generate new random value
     use rand::{distributions::Alphanumeric, Rng};
     let new_secret: String = rand::thread_rng()
@@ -66,6 +71,7 @@ fn rotate_secret(key: &str) -> Result<()> {
         
     let entry = Entry::new("dev-cli", key)?;
     entry.set_password(&new_secret)?;
+    app_ctx.state.save_secret_name(key)?;
     println!("🔄 Secret '{}' rotated. New value: {}", key, new_secret);
     Ok(())
 }
@@ -1,50 +1,302 @@
-use crate::app::AppContext;
-use crate::cli::{SnipArgs, SnipCommand};
-use crate::state::store::Snippet;
+use crate::app::{AppContext, ExitCode};
+use crate::cli::{SnipArgs, SnipCommand, SnipImportSource, SnipTagCommand};
+use crate::modules::snip::{import, picker};
+use crate::modules::snip::runbook::{self, RunbookFile};
+use crate::modules::snip::template::{self, Template};
+use crate::platform::Shell;
+use crate::state::store::{Snippet, SnippetRun};
+use crate::utils::redact::redact_secrets;
+use crate::utils::trace::TracedCommand;
 use anyhow::{Context as AnyhowContext, Result};
-use regex;
+use console::style;
+use dialoguer::{theme::ColorfulTheme, MultiSelect};
 use std::collections::HashMap;
-use std::io::{self, Write};
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::time::Instant;
+use tabled::{Table, Tabled};
+
+/// Earlier runs of a snippet whose variable values are offered when prompting
+const HISTORY_SUGGESTION_RUNS: usize = 20;
 
 /// Run the snippet management command
 pub fn run(args: SnipArgs, app_ctx: &mut AppContext) -> Result<()> {
     match args.command {
-        SnipCommand::Add { name, command, tags } => add_snippet(name, command, tags, app_ctx),
+        SnipCommand::Add { name, command, file, tags, description, force } => {
+            add_snippet(name, command, file, tags, description, force, app_ctx)
+        }
+        SnipCommand::Show { name } => show_snippet(&name, app_ctx),
+        SnipCommand::Edit { name, description } => match description {
+            Some(description) => describe_snippet(&name, description, app_ctx),
+            None => edit_snippet(&name, app_ctx),
+        },
+        SnipCommand::Rename { name, new_name, force } => rename_snippet(&name, new_name, force, app_ctx),
+        SnipCommand::Copy { name, new_name, force } => copy_snippet(&name, new_name, force, app_ctx),
+        SnipCommand::Rm { name } => remove_snippet(&name, app_ctx),
+        SnipCommand::Tag(tag_args) => match tag_args.command {
+            SnipTagCommand::Add { name, tags } => add_tags(&name, &tags, app_ctx),
+            SnipTagCommand::Remove { name, tags } => remove_tags(&name, &tags, app_ctx),
+        },
         SnipCommand::List { tag } => list_snippets(tag, app_ctx),
-        SnipCommand::Run { name, vars, dry_run } => run_snippet(name, vars, dry_run, app_ctx),
+        SnipCommand::Pick { query, print } => pick_snippet(query, print, app_ctx),
+        SnipCommand::Run { name, file, vars, dry_run, yes } => run_snippet(name, file, vars, dry_run, yes, app_ctx),
+        SnipCommand::History { name, limit } => show_history(name, limit, app_ctx),
+        SnipCommand::Import { source, file, limit, yes, force } => import_snippets(source, file, limit, yes, force, app_ctx),
+        SnipCommand::Rerun { id, vars, dry_run, yes } => rerun(id, vars, dry_run, yes, app_ctx),
     }
 }
 
 fn add_snippet(
     name: String,
-    command: String,
+    command: Option<String>,
+    file: Option<String>,
     tags: Option<String>,
+    description: Option<String>,
+    force: bool,
     app_ctx: &mut AppContext,
 ) -> Result<()> {
-    let tag_list = if let Some(tags_str) = tags {
-        tags_str
-            .split(',')
-            .map(|s| s.trim().to_string())
-            .filter(|s| !s.is_empty())
-            .collect()
-    } else {
-        Vec::new()
-    };
-    
-    let snippet = Snippet {
-        name: name.clone(),
-        command,
-        tags: tag_list,
+    ensure_name_free(&name, force, app_ctx)?;
+
+    let snippet = match file {
+        Some(file) => {
+            let runbook = RunbookFile::load(Path::new(&file))?;
+            Snippet {
+                name: name.clone(),
+                command: String::new(),
+                tags: parse_tags(runbook.tags.iter().chain(tags.iter())),
+                description: description.or(runbook.description),
+                steps: runbook.steps,
+            }
+        }
+        None => {
+            let command = command.unwrap_or_default();
+            Template::parse(&command, snippet_shell())?;
+            Snippet {
+                name: name.clone(),
+                command,
+                tags: parse_tags(tags.iter()),
+                description,
+                steps: Vec::new(),
+            }
+        }
     };
+    let steps = snippet.steps.len();
     
     app_ctx.state.save_snippet(snippet)
         .with_context(|| format!("Failed to save snippet '{}'", name))?;
     
+    if steps > 0 {
+        println!("Saved runbook '{}' ({} steps)", name, steps);
+    } else {
+        println!("Saved snippet '{}'", name);
+    }
+    Ok(())
+}
+
+/// Tags from comma-separated lists, without blanks or duplicates
+fn parse_tags<'a>(lists: impl IntoIterator<Item = &'a String>) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for tag in lists.into_iter().flat_map(|list| list.split(',')).map(str::trim) {
+        if !tag.is_empty() && !tags.iter().any(|t| t == tag) {
+            tags.push(tag.to_string());
+        }
+    }
+    tags
+}
+
+fn get_snippet(name: &str, app_ctx: &AppContext) -> Result<Snippet> {
+    app_ctx.state.get_snippet(name)
+        .context("Failed to get snippet")?
+        .ok_or_else(|| anyhow::anyhow!("Snippet '{}' not found", name))
+}
+
+/// Saving a snippet replaces any snippet with the same name, so only allow it with --force
+fn ensure_name_free(name: &str, force: bool, app_ctx: &AppContext) -> Result<()> {
+    if !force && app_ctx.state.get_snippet(name)?.is_some() {
+        anyhow::bail!("Snippet '{}' already exists. Use --force to replace it", name);
+    }
+    Ok(())
+}
+
+fn show_snippet(name: &str, app_ctx: &AppContext) -> Result<()> {
+    let snippet = get_snippet(name, app_ctx)?;
+    println!("Name:    {}", snippet.name);
+    if let Some(description) = &snippet.description {
+        println!("About:   {}", description);
+    }
+    if !snippet.tags.is_empty() {
+        println!("Tags:    {}", snippet.tags.join(", "));
+    }
+    if snippet.steps.is_empty() {
+        println!("Command:");
+        for line in snippet.command.lines() {
+            println!("  {}", line);
+        }
+    } else {
+        println!("Steps:");
+        let commands: Vec<String> = snippet.steps.iter().map(|step| step.command.clone()).collect();
+        runbook::print_plan(&snippet.steps, &commands);
+    }
+    Ok(())
+}
+
+fn edit_snippet(name: &str, app_ctx: &mut AppContext) -> Result<()> {
+    let mut snippet = get_snippet(name, app_ctx)?;
+    if !snippet.steps.is_empty() {
+        return edit_runbook(snippet, app_ctx);
+    }
+    let command = edit_in_editor(name, &snippet.command, "sh")?;
+
+    if command.trim().is_empty() {
+        anyhow::bail!("The command is empty; snippet '{}' was not changed", name);
+    }
+    Template::parse(&command, snippet_shell())
+        .with_context(|| format!("Snippet '{}' was not changed", name))?;
+    if command == snippet.command {
+        println!("No changes to snippet '{}'", name);
+        return Ok(());
+    }
+
+    snippet.command = command;
+    app_ctx.state.save_snippet(snippet)
+        .with_context(|| format!("Failed to save snippet '{}'", name))?;
     println!("Saved snippet '{}'", name);
     Ok(())
 }
 
+/// Set a snippet's description, or remove it when empty
+fn describe_snippet(name: &str, description: String, app_ctx: &mut AppContext) -> Result<()> {
+    let mut snippet = get_snippet(name, app_ctx)?;
+    snippet.description = Some(description.trim().to_string()).filter(|description| !description.is_empty());
+    app_ctx.state.save_snippet(snippet)
+        .with_context(|| format!("Failed to save snippet '{}'", name))?;
+    println!("Saved snippet '{}'", name);
+    Ok(())
+}
+
+/// Edit a runbook's description, tags and steps as TOML
+fn edit_runbook(mut snippet: Snippet, app_ctx: &mut AppContext) -> Result<()> {
+    let name = snippet.name.clone();
+    let current = RunbookFile {
+        description: snippet.description.clone(),
+        tags: snippet.tags.clone(),
+        steps: snippet.steps.clone(),
+    };
+    let edited = edit_in_editor(&name, &current.to_toml()?, "toml")?;
+    let edited = RunbookFile::parse_toml(&edited)
+        .with_context(|| format!("Runbook '{}' was not changed", name))?;
+    if edited.description == current.description && edited.tags == current.tags && edited.steps == current.steps {
+        println!("No changes to runbook '{}'", name);
+        return Ok(());
+    }
+
+    snippet.description = edited.description;
+    snippet.tags = parse_tags(edited.tags.iter());
+    snippet.steps = edited.steps;
+    app_ctx.state.save_snippet(snippet)
+        .with_context(|| format!("Failed to save snippet '{}'", name))?;
+    println!("Saved runbook '{}'", name);
+    Ok(())
+}
+
+/// Let the user edit `text` in $VISUAL or $EDITOR and return the result,
+/// without the trailing newline editors add
+fn edit_in_editor(name: &str, text: &str, extension: &str) -> Result<String> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .ok()
+        .filter(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| if cfg!(windows) { "notepad" } else { "vi" }.to_string());
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().unwrap_or("vi");
+
+    let file_name: String = name.chars().map(|c| if c.is_alphanumeric() || c == '-' { c } else { '_' }).collect();
+    let path = std::env::temp_dir().join(format!("dev-snip-{}-{}.{}", file_name, std::process::id(), extension));
+    fs::write(&path, format!("{}\n", text))
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+
+    let status = Command::new(program).args(parts).arg(&path).traced_status();
+    let edited = fs::read_to_string(&path);
+    let _ = fs::remove_file(&path);
+
+    let status = status.with_context(|| format!("Failed to start editor '{}'", editor))?;
+    if !status.success() {
+        anyhow::bail!("Editor '{}' exited with {}; snippet '{}' was not changed", editor, status, name);
+    }
+    let edited = edited.with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(edited.trim_end_matches(['\n', '\r']).to_string())
+}
+
+fn rename_snippet(name: &str, new_name: String, force: bool, app_ctx: &mut AppContext) -> Result<()> {
+    let mut snippet = get_snippet(name, app_ctx)?;
+    if new_name == name {
+        return Ok(());
+    }
+    ensure_name_free(&new_name, force, app_ctx)?;
+
+    snippet.name = new_name.clone();
+    app_ctx.state.save_snippet(snippet)
+        .with_context(|| format!("Failed to save snippet '{}'", new_name))?;
+    app_ctx.state.delete_snippet(name)
+        .with_context(|| format!("Failed to delete snippet '{}'", name))?;
+    println!("Renamed snippet '{}' to '{}'", name, new_name);
+    Ok(())
+}
+
+fn copy_snippet(name: &str, new_name: String, force: bool, app_ctx: &mut AppContext) -> Result<()> {
+    let mut snippet = get_snippet(name, app_ctx)?;
+    if new_name == name {
+        anyhow::bail!("A snippet cannot be copied onto itself");
+    }
+    ensure_name_free(&new_name, force, app_ctx)?;
+
+    snippet.name = new_name.clone();
+    app_ctx.state.save_snippet(snippet)
+        .with_context(|| format!("Failed to save snippet '{}'", new_name))?;
+    println!("Copied snippet '{}' to '{}'", name, new_name);
+    Ok(())
+}
+
+fn remove_snippet(name: &str, app_ctx: &mut AppContext) -> Result<()> {
+    get_snippet(name, app_ctx)?;
+    app_ctx.state.delete_snippet(name)
+        .with_context(|| format!("Failed to delete snippet '{}'", name))?;
+    println!("Deleted snippet '{}'", name);
+    Ok(())
+}
+
+fn add_tags(name: &str, tags: &[String], app_ctx: &mut AppContext) -> Result<()> {
+    let mut snippet = get_snippet(name, app_ctx)?;
+    for tag in parse_tags(tags) {
+        if !snippet.tags.contains(&tag) {
+            snippet.tags.push(tag);
+        }
+    }
+    save_tags(snippet, app_ctx)
+}
+
+fn remove_tags(name: &str, tags: &[String], app_ctx: &mut AppContext) -> Result<()> {
+    let mut snippet = get_snippet(name, app_ctx)?;
+    for tag in parse_tags(tags) {
+        if !snippet.tags.contains(&tag) {
+            eprintln!("Snippet '{}' has no tag '{}'", name, tag);
+        }
+        snippet.tags.retain(|t| *t != tag);
+    }
+    save_tags(snippet, app_ctx)
+}
+
+fn save_tags(snippet: Snippet, app_ctx: &mut AppContext) -> Result<()> {
+    let name = snippet.name.clone();
+    let tags = if snippet.tags.is_empty() { "(none)".to_string() } else { snippet.tags.join(", ") };
+    app_ctx.state.save_snippet(snippet)
+        .with_context(|| format!("Failed to save snippet '{}'", name))?;
+    println!("Tags of '{}': {}", name, tags);
+    Ok(())
+}
+
 fn list_snippets(tag_filter: Option<String>, app_ctx: &mut AppContext) -> Result<()> {
     let snippets = app_ctx.state.list_snippets(tag_filter.as_deref())
         .context("Failed to list snippets")?;
@@ -61,71 +313,332 @@ fn list_snippets(tag_filter: Option<String>, app_ctx: &mut AppContext) -> Result
                 write!(handle, " [{}]", snippet.tags.join(", "))?;
             }
             writeln!(handle)?;
-            writeln!(handle, "  {}", snippet.command)?;
+            if snippet.steps.is_empty() {
+                writeln!(handle, "  {}", snippet.command)?;
+            } else {
+                let steps: Vec<String> = snippet.steps.iter().enumerate().map(|(i, step)| runbook::step_name(step, i)).collect();
+                writeln!(handle, "  runbook: {}", steps.join(" → "))?;
+            }
         }
     }
     
     Ok(())
 }
 
+fn pick_snippet(query: Option<String>, print: bool, app_ctx: &mut AppContext) -> Result<()> {
+    let snippets = app_ctx.state.list_snippets(None).context("Failed to list snippets")?;
+    if snippets.is_empty() {
+        anyhow::bail!("No snippets yet. Add one with `dev snip add`");
+    }
+    let Some(snippet) = picker::pick(snippets, query.as_deref().unwrap_or(""))? else {
+        return Err(ExitCode(130).into());
+    };
+
+    if !print {
+        eprintln!("{} {} {} {}", style("✔").green(), style("Snippet").bold(), style("·").dim(), style(&snippet.name).green());
+        return execute(&snippet, None, &HashMap::new(), false, false, app_ctx);
+    }
+    if !snippet.steps.is_empty() {
+        anyhow::bail!("'{}' is a runbook and can't go on the command line; run it with `dev snip run {}`", snippet.name, snippet.name);
+    }
+    let Filled { commands, .. } = fill_commands(&snippet, &HashMap::new(), snippet_shell(), app_ctx)?;
+    println!("{}", commands[0]);
+    Ok(())
+}
+
+/// Offer the snippets found in a source for selection and save the chosen ones.
+/// Project targets start selected; history commands have to be picked.
+fn import_snippets(
+    source: SnipImportSource,
+    file: Option<PathBuf>,
+    limit: usize,
+    yes: bool,
+    force: bool,
+    app_ctx: &mut AppContext,
+) -> Result<()> {
+    let found = import::find(source, file.as_deref(), limit)?;
+    let total = found.snippets.len();
+    let mut snippets = Vec::new();
+    for snippet in found.snippets {
+        if force || app_ctx.state.get_snippet(&snippet.name)?.is_none() {
+            snippets.push(snippet);
+        }
+    }
+    let existing = total - snippets.len();
+
+    if snippets.is_empty() {
+        if existing > 0 {
+            println!("All {} snippet(s) from {} already exist. Use --force to replace them.", existing, found.path.display());
+        } else {
+            println!("No snippets found in {}.", found.path.display());
+        }
+        return Ok(());
+    }
+
+    let chosen: Vec<Snippet> = if yes {
+        snippets
+    } else if io::stdin().is_terminal() && io::stderr().is_terminal() {
+        let history = matches!(source, SnipImportSource::Bash | SnipImportSource::Zsh | SnipImportSource::Fish);
+        let items: Vec<String> = snippets
+            .iter()
+            .map(|snippet| format!("{}  {}", snippet.name, style(snippet.command.replace('\n', " ⏎ ")).dim()))
+            .collect();
+        let selected = MultiSelect::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("Snippets to import from {} (space toggles, enter imports)", found.path.display()))
+            .items(&items)
+            .defaults(&vec![!history; items.len()])
+            .max_length(15)
+            .interact()?;
+        let mut selected_snippets = Vec::new();
+        for (index, snippet) in snippets.into_iter().enumerate() {
+            if selected.contains(&index) {
+                selected_snippets.push(snippet);
+            }
+        }
+        selected_snippets
+    } else {
+        anyhow::bail!(
+            "Found {} snippet(s) in {}, but there is no terminal to choose in. Pass --yes to import them all",
+            snippets.len(),
+            found.path.display()
+        );
+    };
+
+    for snippet in &chosen {
+        app_ctx.state.save_snippet(snippet.clone()).context("Failed to save snippet")?;
+    }
+    println!("✓ Imported {} snippet(s) from {}", chosen.len(), found.path.display());
+    if existing > 0 {
+        println!("  Skipped {} that already exist; use --force to replace them.", existing);
+    }
+    Ok(())
+}
+
 fn run_snippet(
-    name: String,
+    name: Option<String>,
+    file: Option<String>,
     vars: Vec<String>,
     dry_run: bool,
+    yes: bool,
     app_ctx: &mut AppContext,
 ) -> Result<()> {
-    let snippet = app_ctx.state.get_snippet(&name)
-        .context("Failed to get snippet")?
-        .ok_or_else(|| anyhow::anyhow!("Snippet '{}' not found", name))?;
+    let (snippet, file) = match (name, file) {
+        (_, Some(file)) => (load_runbook_file(&file)?, Some(file)),
+        (Some(name), None) => (get_snippet(&name, app_ctx)?, None),
+        (None, None) => anyhow::bail!("Give a snippet name or --file"),
+    };
     
     // Parse variables
     let var_map = parse_vars(vars)?;
-    
-    // Substitute variables in command
-    let command = substitute_variables(&snippet.command, &var_map)?;
+    execute(&snippet, file, &var_map, dry_run, yes, app_ctx)
+}
+
+/// A runbook file as an unsaved snippet named after the file
+fn load_runbook_file(file: &str) -> Result<Snippet> {
+    let path = Path::new(file);
+    let runbook = RunbookFile::load(path)?;
+    Ok(Snippet {
+        name: path.file_stem().map_or(file.to_string(), |stem| stem.to_string_lossy().to_string()),
+        command: String::new(),
+        tags: runbook.tags,
+        description: runbook.description,
+        steps: runbook.steps,
+    })
+}
+
+/// A snippet's command, or each runbook step, with the variables filled in
+struct Filled {
+    commands: Vec<String>,
+    /// The commands as recorded in the history, with `{env.*}` left unfilled
+    recorded: Vec<String>,
+    /// The value of every variable
+    values: HashMap<String, String>,
+}
+
+/// Fill in the variables of a snippet's command, or of each runbook step,
+/// quoting values for the shell that runs them
+fn fill_commands(
+    snippet: &Snippet,
+    vars: &HashMap<String, String>,
+    shell: Shell,
+    app_ctx: &AppContext,
+) -> Result<Filled> {
+    let templates = if snippet.steps.is_empty() {
+        vec![Template::parse(&snippet.command, shell)?]
+    } else {
+        snippet.steps
+            .iter()
+            .enumerate()
+            .map(|(i, step)| Template::parse(&step.command, shell).with_context(|| format!("Step '{}'", runbook::step_name(step, i))))
+            .collect::<Result<Vec<_>>>()?
+    };
+    let suggestions = last_values(&snippet.name, app_ctx)?;
+    let values = template::resolve(templates.iter().flat_map(Template::placeholders), vars, &suggestions)?;
+    let commands = templates.iter().map(|template| template.fill(&values, shell)).collect();
+    let recorded = templates.iter().map(|template| template.fill_masked(&values, shell)).collect();
+    Ok(Filled { commands, recorded, values })
+}
+
+/// Fill in a snippet's variables, run it and record the run in the history.
+/// A failed run returns an `ExitCode` error with the command's exit code.
+fn execute(
+    snippet: &Snippet,
+    file: Option<String>,
+    vars: &HashMap<String, String>,
+    dry_run: bool,
+    yes: bool,
+    app_ctx: &mut AppContext,
+) -> Result<()> {
+    let shell = snippet_shell();
+    let Filled { commands, recorded, mut values } = fill_commands(snippet, vars, shell, app_ctx)?;
     
     if dry_run {
-        println!("Would run: {}", command);
+        if snippet.steps.is_empty() {
+            println!("Would run: {}", commands[0]);
+        } else {
+            println!("Would run runbook '{}' ({} steps):", snippet.name, snippet.steps.len());
+            runbook::print_plan(&snippet.steps, &commands);
+        }
         return Ok(());
     }
     
+    let cwd = std::env::current_dir().context("Failed to get current directory")?;
+    let started_at = chrono::Local::now().to_rfc3339();
+    let started = Instant::now();
+    
     // Execute command via system shell
-    let shell = crate::platform::detect_shell().unwrap_or(crate::platform::Shell::Bash);
-    
-    let status = match shell {
-        crate::platform::Shell::Bash | crate::platform::Shell::Zsh => {
-            Command::new("sh")
-                .arg("-c")
-                .arg(&command)
-                .status()
-        }
-        crate::platform::Shell::Fish => {
-            Command::new("fish")
-                .arg("-c")
-                .arg(&command)
-                .status()
-        }
-        crate::platform::Shell::PowerShell => {
-            Command::new("powershell")
-                .arg("-Command")
-                .arg(&command)
-                .status()
-        }
-        _ => {
-            Command::new("sh")
-                .arg("-c")
-                .arg(&command)
-                .status()
+    let result = if snippet.steps.is_empty() {
+        shell_command(shell, &commands[0])
+            .traced_status()
+            .map(|status| status.code())
+            .context("Failed to run snippet")
+    } else {
+        runbook::run(&snippet.name, &snippet.steps, &commands, shell, yes).map(|failed| Some(failed.unwrap_or(0)))
+    };
+    
+    // Values from the environment are looked up again on a rerun rather than
+    // kept, and anything else that looks like a secret is redacted
+    values.retain(|name, _| !name.starts_with("env."));
+    let run = SnippetRun {
+        id: 0,
+        name: snippet.name.clone(),
+        file: file.map(|file| fs::canonicalize(&file).map_or(file, |path| path.to_string_lossy().to_string())),
+        command: redact_secrets(&recorded.join("\n")),
+        vars: values,
+        cwd: cwd.to_string_lossy().to_string(),
+        context: crate::modules::ctx::command::active_context(),
+        started_at,
+        duration_ms: started.elapsed().as_millis() as u64,
+        exit_code: result.as_ref().ok().copied().flatten(),
+    };
+    let id = app_ctx.state.save_snippet_run(run)
+        .context("Failed to record snippet run")?;
+    log::debug!("Recorded snippet run #{}", id);
+    
+    match result? {
+        Some(0) => Ok(()),
+        code => Err(ExitCode(code.unwrap_or(1)).into()),
+    }
+}
+
+/// Most recent value of each variable in earlier runs of the snippet
+fn last_values(name: &str, app_ctx: &AppContext) -> Result<HashMap<String, String>> {
+    let mut values = HashMap::new();
+    for run in app_ctx.state.list_snippet_runs(Some(name), HISTORY_SUGGESTION_RUNS)? {
+        for (var, value) in run.vars {
+            values.entry(var).or_insert(value);
         }
-    }?;
+    }
+    Ok(values)
+}
+
+#[derive(Tabled)]
+struct HistoryRow {
+    #[tabled(rename = "ID")]
+    id: i64,
+    #[tabled(rename = "Started")]
+    started_at: String,
+    #[tabled(rename = "Snippet")]
+    name: String,
+    #[tabled(rename = "Exit code")]
+    exit_code: String,
+    #[tabled(rename = "Duration")]
+    duration: String,
+    #[tabled(rename = "Directory")]
+    cwd: String,
+    #[tabled(rename = "Command")]
+    command: String,
+}
+
+fn show_history(name: Option<String>, limit: usize, app_ctx: &AppContext) -> Result<()> {
+    let runs = app_ctx.state.list_snippet_runs(name.as_deref(), limit)
+        .context("Failed to list snippet runs")?;
     
-    if !status.success() {
-        std::process::exit(status.code().unwrap_or(1));
+    if runs.is_empty() {
+        match name {
+            Some(name) => println!("No runs of '{}' recorded.", name),
+            None => println!("No snippet runs recorded."),
+        }
+        return Ok(());
     }
     
+    let rows: Vec<HistoryRow> = runs
+        .into_iter()
+        .map(|run| HistoryRow {
+            id: run.id,
+            started_at: run.started_at.get(..16).unwrap_or(&run.started_at).replace('T', " "),
+            name: run.name,
+            exit_code: run.exit_code.map_or("-".to_string(), |code| code.to_string()),
+            duration: format!("{:.1}s", run.duration_ms as f64 / 1000.0),
+            cwd: run.cwd,
+            command: truncate(&run.command.replace('\n', "; "), 50),
+        })
+        .collect();
+    println!("{}", Table::new(rows));
     Ok(())
 }
 
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        text.to_string()
+    } else {
+        format!("{}…", text.chars().take(max - 1).collect::<String>())
+    }
+}
+
+/// Run the snippet of an earlier run again, in the same directory and with
+/// the same variables (unless overridden with --vars)
+fn rerun(id: i64, vars: Vec<String>, dry_run: bool, yes: bool, app_ctx: &mut AppContext) -> Result<()> {
+    let run = app_ctx.state.get_snippet_run(id)?
+        .ok_or_else(|| anyhow::anyhow!("No snippet run #{}. See `dev snip history`", id))?;
+    
+    let snippet = match &run.file {
+        Some(file) => load_runbook_file(file)?,
+        None => app_ctx.state.get_snippet(&run.name)?.ok_or_else(|| {
+            anyhow::anyhow!("Snippet '{}' no longer exists. Run #{} ran:\n{}", run.name, id, run.command)
+        })?,
+    };
+    
+    let mut var_map = run.vars;
+    var_map.extend(parse_vars(vars)?);
+    std::env::set_current_dir(&run.cwd)
+        .with_context(|| format!("Failed to change to {}, where run #{} ran", run.cwd, id))?;
+    println!("Re-running #{} '{}' in {}", id, run.name, run.cwd);
+    execute(&snippet, run.file, &var_map, dry_run, yes, app_ctx)
+}
+
+/// A process that runs `command` in `shell`
+pub(crate) fn shell_command(shell: Shell, command: &str) -> Command {
+    let mut process = match shell {
+        Shell::Fish => Command::new("fish"),
+        Shell::PowerShell => Command::new("powershell"),
+        _ => Command::new("sh"),
+    };
+    let flag = if shell == Shell::PowerShell { "-Command" } else { "-c" };
+    process.arg(flag).arg(command);
+    process
+}
+
 fn parse_vars(vars: Vec<String>) -> Result<HashMap<String, String>> {
     let mut var_map = HashMap::new();
     
@@ -140,19 +653,7 @@ fn parse_vars(vars: Vec<String>) -> Result<HashMap<String, String>> {
     Ok(var_map)
 }
 
-fn substitute_variables(command: &str, vars: &HashMap<String, String>) -> Result<String> {
-    let mut result = command.to_string();
-    
-    // Find all {var} patterns
-    let re = regex::Regex::new(r"\{([^}]+)\}")?;
-    
-    for cap in re.captures_iter(command) {
-        let var_name = cap.get(1).unwrap().as_str();
-        let replacement = vars.get(var_name)
-            .ok_or_else(|| anyhow::anyhow!("Variable '{}' not provided", var_name))?;
-        
-        result = result.replace(&format!("{{{}}}", var_name), replacement);
-    }
-    
-    Ok(result)
+/// Shell that snippets run in
+fn snippet_shell() -> Shell {
+    crate::platform::detect_shell().unwrap_or(Shell::Bash)
 }
@@ -0,0 +1,421 @@
+use crate::cli::SnipImportSource;
+use crate::state::store::Snippet;
+use anyhow::{Context as AnyhowContext, Result};
+use regex::Regex;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+/// History commands used fewer times than this are not offered
+const MIN_HISTORY_COUNT: usize = 2;
+
+/// Commands not worth keeping as snippets
+const TRIVIAL_COMMANDS: &[&str] = &["cd", "ls", "ll", "la", "pwd", "clear", "exit", "history", "man"];
+
+/// `target: deps`, but not `VAR := value`
+static MAKE_TARGET: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^([A-Za-z0-9_./ -]*[A-Za-z0-9_])\s*::?([^=].*)?$").unwrap());
+
+/// `recipe param param=default: deps`, but not `name := value`
+static JUST_RECIPE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^@?([A-Za-z_][A-Za-z0-9_-]*)((?:\s+[^:]*?)?)\s*:(?:[^=].*)?$").unwrap());
+
+/// Snippets found in a source, not yet saved
+pub struct Found {
+    /// File they were read from
+    pub path: PathBuf,
+    pub snippets: Vec<Snippet>,
+}
+
+/// Read snippets from `source`, at its usual place or in `file`. History
+/// sources offer the `limit` most used commands.
+pub fn find(source: SnipImportSource, file: Option<&Path>, limit: usize) -> Result<Found> {
+    let path = match file {
+        Some(file) => file.to_path_buf(),
+        None => default_path(source)?,
+    };
+    let content = fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let content = String::from_utf8_lossy(&content);
+
+    let snippets = match source {
+        SnipImportSource::Bash | SnipImportSource::Zsh | SnipImportSource::Fish => {
+            let commands = match source {
+                SnipImportSource::Bash => bash_history(&content),
+                SnipImportSource::Zsh => zsh_history(&content),
+                _ => fish_history(&content),
+            };
+            from_history(source, commands, limit)
+        }
+        _ => {
+            let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+            let project = project_name(dir);
+            let tasks = match source {
+                SnipImportSource::Make => make_targets(&content),
+                SnipImportSource::Npm => npm_scripts(&content, dir)?,
+                SnipImportSource::Just => just_recipes(&content),
+                SnipImportSource::Task => taskfile_tasks(&content)?,
+                _ => cargo_aliases(&content)?,
+            };
+            tasks
+                .into_iter()
+                .map(|task| Snippet {
+                    name: format!("{}-{}-{}", project, source_tag(source), slug(&task.name)),
+                    command: task.command,
+                    tags: vec![source_tag(source).to_string(), project.clone()],
+                    description: task.description,
+                    steps: Vec::new(),
+                })
+                .collect()
+        }
+    };
+    Ok(Found { path, snippets })
+}
+
+fn source_tag(source: SnipImportSource) -> &'static str {
+    match source {
+        SnipImportSource::Bash => "bash",
+        SnipImportSource::Zsh => "zsh",
+        SnipImportSource::Fish => "fish",
+        SnipImportSource::Make => "make",
+        SnipImportSource::Npm => "npm",
+        SnipImportSource::Just => "just",
+        SnipImportSource::Task => "task",
+        SnipImportSource::Cargo => "cargo",
+    }
+}
+
+/// Where each source is usually kept; project files are looked for in the current directory
+fn default_path(source: SnipImportSource) -> Result<PathBuf> {
+    let home = || dirs::home_dir().context("Could not determine home directory");
+    let in_cwd = |names: &[&str], what: &str| {
+        names
+            .iter()
+            .map(PathBuf::from)
+            .find(|path| path.is_file())
+            .ok_or_else(|| anyhow::anyhow!("No {} in the current directory. Pass its path with --file", what))
+    };
+    match source {
+        SnipImportSource::Bash => Ok(home()?.join(".bash_history")),
+        SnipImportSource::Zsh => match std::env::var_os("HISTFILE") {
+            Some(file) => Ok(PathBuf::from(file)),
+            None => Ok(home()?.join(".zsh_history")),
+        },
+        SnipImportSource::Fish => {
+            let data = match std::env::var_os("XDG_DATA_HOME") {
+                Some(dir) => PathBuf::from(dir),
+                None => home()?.join(".local").join("share"),
+            };
+            Ok(data.join("fish").join("fish_history"))
+        }
+        SnipImportSource::Make => in_cwd(&["GNUmakefile", "makefile", "Makefile"], "Makefile"),
+        SnipImportSource::Npm => in_cwd(&["package.json"], "package.json"),
+        SnipImportSource::Just => in_cwd(&["justfile", "Justfile", ".justfile"], "justfile"),
+        SnipImportSource::Task => {
+            in_cwd(&["Taskfile.yml", "Taskfile.yaml", "taskfile.yml", "taskfile.yaml"], "Taskfile")
+        }
+        SnipImportSource::Cargo => in_cwd(&[".cargo/config.toml", ".cargo/config"], ".cargo/config.toml"),
+    }
+}
+
+/// Name of the directory a project file is in
+fn project_name(dir: &Path) -> String {
+    let dir = fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf());
+    // .cargo/config.toml belongs to the directory above
+    let dir = if dir.file_name().is_some_and(|name| name == ".cargo") { dir.parent().unwrap_or(&dir) } else { &dir };
+    dir.file_name().map_or("project".to_string(), |name| slug(&name.to_string_lossy()))
+}
+
+/// Lowercase letters, digits, `_`, `.` and `-`, so the name is also a safe file name for sync
+fn slug(text: &str) -> String {
+    let mut slug = String::new();
+    for c in text.chars() {
+        if c.is_ascii_alphanumeric() || matches!(c, '_' | '.') {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').to_string()
+}
+
+/// Imported commands are stored as they are, so braces are not placeholders
+fn escape_braces(command: &str) -> String {
+    command.replace('{', "{{").replace('}', "}}")
+}
+
+/// One command per line, skipping `#<timestamp>` lines from HISTTIMEFORMAT
+fn bash_history(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .filter(|line| !(line.starts_with('#') && line[1..].chars().all(|c| c.is_ascii_digit())))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Plain lines or `: <time>:<duration>;command`, continued with a trailing backslash
+fn zsh_history(content: &str) -> Vec<String> {
+    let mut commands: Vec<String> = Vec::new();
+    let mut continued = false;
+    for line in content.lines() {
+        let line = if continued {
+            line
+        } else {
+            match line.strip_prefix(": ").and_then(|rest| rest.split_once(';')) {
+                Some((_, command)) => command,
+                None => line,
+            }
+        };
+        let (line, next_continues) = match line.strip_suffix('\\') {
+            Some(line) => (line, true),
+            None => (line, false),
+        };
+        match commands.last_mut() {
+            Some(command) if continued => {
+                command.push('\n');
+                command.push_str(line);
+            }
+            _ => commands.push(line.to_string()),
+        }
+        continued = next_continues;
+    }
+    commands
+}
+
+/// `- cmd: <command>` entries, with `\n` and `\\` escapes
+fn fish_history(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .filter_map(|line| line.strip_prefix("- cmd: "))
+        .map(|command| {
+            let mut unescaped = String::new();
+            let mut chars = command.chars();
+            while let Some(c) = chars.next() {
+                match (c, chars.clone().next()) {
+                    ('\\', Some('n')) => {
+                        chars.next();
+                        unescaped.push('\n');
+                    }
+                    ('\\', Some('\\')) => {
+                        chars.next();
+                        unescaped.push('\\');
+                    }
+                    _ => unescaped.push(c),
+                }
+            }
+            unescaped
+        })
+        .collect()
+}
+
+/// The `limit` most used commands, most used (then most recent) first
+fn from_history(source: SnipImportSource, commands: Vec<String>, limit: usize) -> Vec<Snippet> {
+    let mut counts: HashMap<String, (usize, usize)> = HashMap::new();
+    for (index, command) in commands.into_iter().enumerate() {
+        let command = command.trim().to_string();
+        let first = command.split_whitespace().next().unwrap_or_default();
+        if !command.contains(char::is_whitespace) || TRIVIAL_COMMANDS.contains(&first) || command.starts_with("dev snip ") {
+            continue;
+        }
+        let entry = counts.entry(command).or_default();
+        entry.0 += 1;
+        entry.1 = index;
+    }
+
+    let mut frequent: Vec<(String, usize, usize)> = counts
+        .into_iter()
+        .filter(|(_, (count, _))| *count >= MIN_HISTORY_COUNT)
+        .map(|(command, (count, last))| (command, count, last))
+        .collect();
+    frequent.sort_by(|a, b| b.1.cmp(&a.1).then(b.2.cmp(&a.2)));
+    frequent.truncate(limit);
+
+    let mut names: Vec<String> = Vec::new();
+    frequent
+        .into_iter()
+        .map(|(command, count, _)| {
+            let base = history_name(&command);
+            let mut name = base.clone();
+            let mut n = 2;
+            while names.contains(&name) {
+                name = format!("{}-{}", base, n);
+                n += 1;
+            }
+            names.push(name.clone());
+            Snippet {
+                name,
+                command: escape_braces(&command),
+                tags: vec!["history".to_string(), source_tag(source).to_string()],
+                description: Some(format!("Used {} times ({} history)", count, source_tag(source))),
+                steps: Vec::new(),
+            }
+        })
+        .collect()
+}
+
+/// The program and up to two subcommands, e.g. `docker-compose-up` for `docker compose up -d`
+fn history_name(command: &str) -> String {
+    let words: Vec<&str> = command
+        .split_whitespace()
+        .take_while(|word| !word.starts_with('-') && word.chars().all(|c| c.is_ascii_alphanumeric() || "_.-/".contains(c)))
+        .take(3)
+        .collect();
+    let program = words.first().map(|word| word.rsplit('/').next().unwrap_or(word));
+    let name = slug(&program.into_iter().chain(words.iter().skip(1).copied()).collect::<Vec<_>>().join("-"));
+    if name.is_empty() { "command".to_string() } else { name }
+}
+
+/// A task from a project file, before it is named after the project
+struct Task {
+    name: String,
+    command: String,
+    description: Option<String>,
+}
+
+/// Targets of a Makefile, described by a `## text` after the target or a comment above it
+fn make_targets(content: &str) -> Vec<Task> {
+    let mut tasks: Vec<Task> = Vec::new();
+    let mut comment: Option<String> = None;
+    for line in content.lines() {
+        if let Some(text) = line.strip_prefix('#') {
+            comment = Some(text.trim_start_matches('#').trim().to_string()).filter(|text| !text.is_empty());
+            continue;
+        }
+        let Some(captures) = MAKE_TARGET.captures(line).filter(|captures| !line[captures[1].len()..].contains(":=")) else {
+            comment = None;
+            continue;
+        };
+        let inline = captures.get(2).and_then(|rest| rest.as_str().split_once("##")).map(|(_, text)| text.trim().to_string());
+        let description = inline.or(comment.take());
+        for target in captures[1].split_whitespace() {
+            // Special (.PHONY), pattern and path targets are not run by hand
+            if target.starts_with('.') || target.contains('/') || tasks.iter().any(|task| task.name == target) {
+                continue;
+            }
+            tasks.push(Task {
+                name: target.to_string(),
+                command: format!("make {}", escape_braces(target)),
+                description: description.clone(),
+            });
+        }
+    }
+    tasks
+}
+
+/// `scripts` of a package.json, run with the package manager whose lockfile is next to it
+fn npm_scripts(content: &str, dir: &Path) -> Result<Vec<Task>> {
+    let package: serde_json::Value = serde_json::from_str(content).context("Invalid package.json")?;
+    let Some(scripts) = package.get("scripts").and_then(|scripts| scripts.as_object()) else {
+        return Ok(Vec::new());
+    };
+    let runner = [("pnpm-lock.yaml", "pnpm"), ("yarn.lock", "yarn"), ("bun.lockb", "bun"), ("bun.lock", "bun")]
+        .iter()
+        .find(|(lockfile, _)| dir.join(lockfile).exists())
+        .map_or("npm", |(_, runner)| runner);
+
+    Ok(scripts
+        .iter()
+        // pre<name> and post<name> run around <name> by themselves
+        .filter(|(name, _)| {
+            !["pre", "post"].iter().any(|hook| name.strip_prefix(hook).is_some_and(|rest| scripts.contains_key(rest)))
+        })
+        .map(|(name, script)| Task {
+            name: name.clone(),
+            command: format!("{} run {}", runner, escape_braces(name)),
+            description: script.as_str().map(str::to_string),
+        })
+        .collect())
+}
+
+/// Public recipes of a justfile; their parameters become placeholders
+fn just_recipes(content: &str) -> Vec<Task> {
+    let mut tasks = Vec::new();
+    let mut comment: Option<String> = None;
+    let mut private = false;
+    for line in content.lines() {
+        if let Some(text) = line.strip_prefix('#') {
+            comment = Some(text.trim().to_string()).filter(|text| !text.is_empty() && !text.starts_with('!'));
+            continue;
+        }
+        if line.starts_with('[') {
+            private |= line.contains("private");
+            continue;
+        }
+        let recipe = JUST_RECIPE.captures(line).filter(|_| !line.starts_with(char::is_whitespace));
+        let (comment, was_private) = (comment.take(), std::mem::take(&mut private));
+        let Some(captures) = recipe else {
+            continue;
+        };
+        let name = &captures[1];
+        if was_private || name.starts_with('_') {
+            continue;
+        }
+
+        let mut command = format!("just {}", name);
+        // `*args` may be left out, so it is; `+args` needs a value
+        for param in captures[2].split_whitespace().filter(|param| !param.starts_with('*')) {
+            let param = param.trim_start_matches(['$', '+']);
+            let (param, default) = match param.split_once('=') {
+                Some((param, default)) => (param, quoted(default)),
+                None => (param, None),
+            };
+            match default {
+                Some(default) if !default.contains(['{', '}']) => command.push_str(&format!(" {{{}:{}}}", param, default)),
+                _ => command.push_str(&format!(" {{{}}}", param)),
+            }
+        }
+        tasks.push(Task { name: name.to_string(), command, description: comment });
+    }
+    tasks
+}
+
+/// A default given as a string literal; other defaults are expressions only just can evaluate
+fn quoted(default: &str) -> Option<&str> {
+    ['"', '\'']
+        .iter()
+        .find_map(|quote| default.strip_prefix(*quote)?.strip_suffix(*quote))
+}
+
+/// Tasks of a Taskfile, leaving out internal ones
+fn taskfile_tasks(content: &str) -> Result<Vec<Task>> {
+    let taskfile: serde_norway::Value = serde_norway::from_str(content).context("Invalid Taskfile")?;
+    let Some(tasks) = taskfile.get("tasks").and_then(|tasks| tasks.as_mapping()) else {
+        return Ok(Vec::new());
+    };
+    Ok(tasks
+        .iter()
+        .filter_map(|(name, task)| {
+            let name = name.as_str()?;
+            if task.get("internal").and_then(|internal| internal.as_bool()) == Some(true) {
+                return None;
+            }
+            let description = ["desc", "summary"]
+                .iter()
+                .find_map(|key| task.get(key).and_then(|text| text.as_str()))
+                .map(|text| text.lines().next().unwrap_or_default().to_string());
+            Some(Task { name: name.to_string(), command: format!("task {}", escape_braces(name)), description })
+        })
+        .collect())
+}
+
+/// `[alias]` entries of a Cargo config, given as a string or a list of arguments
+fn cargo_aliases(content: &str) -> Result<Vec<Task>> {
+    let config: toml::Value = toml::from_str(content).context("Invalid Cargo config")?;
+    let Some(aliases) = config.get("alias").and_then(|aliases| aliases.as_table()) else {
+        return Ok(Vec::new());
+    };
+    Ok(aliases
+        .iter()
+        .map(|(name, expansion)| {
+            let expansion = match expansion {
+                toml::Value::Array(args) => args.iter().filter_map(|arg| arg.as_str()).collect::<Vec<_>>().join(" "),
+                other => other.as_str().unwrap_or_default().to_string(),
+            };
+            Task {
+                name: name.clone(),
+                command: format!("cargo {}", escape_braces(name)),
+                description: Some(format!("cargo {}", expansion)),
+            }
+        })
+        .collect())
+}
@@ -1,4 +1,7 @@
 pub mod command;
+pub mod import;
+pub mod picker;
+pub mod runbook;
+pub mod template;
 
 pub use command::run;
-
@@ -0,0 +1,243 @@
+use crate::modules::snip::runbook;
+use crate::modules::snip::template::Template;
+use crate::platform::Shell;
+use crate::state::store::Snippet;
+use anyhow::Result;
+use console::{style, truncate_str, Key, Term};
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+use std::io::IsTerminal;
+
+/// Most snippets listed at once
+const LIST_ROWS: usize = 10;
+/// Most lines in the preview pane
+const PREVIEW_ROWS: usize = 10;
+
+/// Let the user fuzzy-search snippets by name, tags, description and command,
+/// with a preview of the highlighted one. Returns `None` when cancelled.
+pub fn pick(snippets: Vec<Snippet>, query: &str) -> Result<Option<Snippet>> {
+    let term = Term::stderr();
+    if !term.is_term() || !std::io::stdin().is_terminal() {
+        anyhow::bail!("`dev snip pick` needs a terminal. Use `dev snip list` and `dev snip run <name>` instead");
+    }
+
+    let mut picker = Picker {
+        haystacks: snippets.iter().map(haystack).collect(),
+        snippets,
+        matcher: SkimMatcherV2::default(),
+        query: query.to_string(),
+        matches: Vec::new(),
+        selected: 0,
+        offset: 0,
+        drawn: 0,
+    };
+    picker.filter();
+
+    term.hide_cursor()?;
+    let result = picker.interact(&term);
+    let _ = term.clear_last_lines(picker.drawn);
+    let _ = term.show_cursor();
+    Ok(result?.map(|index| picker.snippets.swap_remove(index)))
+}
+
+/// Text searched for each snippet: name, tags, description and command, with
+/// a weight for how much a match in each counts
+fn haystack(snippet: &Snippet) -> [(String, i64); 4] {
+    let command = if snippet.steps.is_empty() {
+        snippet.command.clone()
+    } else {
+        snippet.steps.iter().map(|step| step.command.as_str()).collect::<Vec<_>>().join("\n")
+    };
+    [
+        (snippet.name.clone(), 4),
+        (snippet.tags.join(" "), 2),
+        (snippet.description.clone().unwrap_or_default(), 1),
+        (command, 1),
+    ]
+}
+
+struct Picker {
+    snippets: Vec<Snippet>,
+    haystacks: Vec<[(String, i64); 4]>,
+    matcher: SkimMatcherV2,
+    query: String,
+    /// Indexes into `snippets` of the matches, best first
+    matches: Vec<usize>,
+    /// Position in `matches` of the highlighted snippet
+    selected: usize,
+    /// Position in `matches` of the first listed snippet
+    offset: usize,
+    /// Lines on screen from the last draw
+    drawn: usize,
+}
+
+impl Picker {
+    fn interact(&mut self, term: &Term) -> Result<Option<usize>> {
+        loop {
+            self.draw(term)?;
+            match term.read_key_raw()? {
+                Key::Enter => {
+                    if let Some(&index) = self.matches.get(self.selected) {
+                        return Ok(Some(index));
+                    }
+                }
+                Key::Escape | Key::CtrlC | Key::Char('\u{3}') | Key::Char('\u{4}') => return Ok(None),
+                // Ctrl-P and Ctrl-N as in most line editors
+                Key::ArrowUp | Key::BackTab | Key::Char('\u{10}') => self.move_by(-1),
+                Key::ArrowDown | Key::Tab | Key::Char('\u{e}') => self.move_by(1),
+                Key::PageUp => self.move_by(-(LIST_ROWS as isize)),
+                Key::PageDown => self.move_by(LIST_ROWS as isize),
+                Key::Backspace => {
+                    self.query.pop();
+                    self.filter();
+                }
+                // Ctrl-U clears the query
+                Key::Char('\u{15}') => {
+                    self.query.clear();
+                    self.filter();
+                }
+                Key::Char(c) if !c.is_control() => {
+                    self.query.push(c);
+                    self.filter();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn move_by(&mut self, delta: isize) {
+        if !self.matches.is_empty() {
+            self.selected = self.selected.saturating_add_signed(delta).min(self.matches.len() - 1);
+        }
+    }
+
+    /// Rank the snippets against the query; all of them, by name, when it is empty
+    fn filter(&mut self) {
+        let query = self.query.trim();
+        if query.is_empty() {
+            self.matches = (0..self.snippets.len()).collect();
+        } else {
+            let mut scored: Vec<(usize, i64)> = self
+                .haystacks
+                .iter()
+                .enumerate()
+                .filter_map(|(index, fields)| {
+                    fields
+                        .iter()
+                        .filter_map(|(text, weight)| self.matcher.fuzzy_match(text, query).map(|score| score * weight))
+                        .max()
+                        .map(|score| (index, score))
+                })
+                .collect();
+            scored.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+            self.matches = scored.into_iter().map(|(index, _)| index).collect();
+        }
+        self.selected = 0;
+        self.offset = 0;
+    }
+
+    fn draw(&mut self, term: &Term) -> Result<()> {
+        let (rows, cols) = term.size();
+        let width = cols as usize;
+        // Leave a line spare so the picker never scrolls the screen
+        let available = (rows as usize).saturating_sub(1);
+        let preview_rows = PREVIEW_ROWS.min(available / 3);
+        let list_rows = available.saturating_sub(preview_rows + 3).clamp(1, LIST_ROWS);
+
+        if self.selected < self.offset {
+            self.offset = self.selected;
+        } else if self.selected >= self.offset + list_rows {
+            self.offset = self.selected + 1 - list_rows;
+        }
+
+        let mut lines = vec![format!(
+            "{} {} {} {}{}",
+            style("?").yellow(),
+            style("Snippet").bold(),
+            style("›").dim(),
+            self.query,
+            style(" ").reverse()
+        )];
+        for (position, &index) in self.matches.iter().enumerate().skip(self.offset).take(list_rows) {
+            lines.push(self.list_line(&self.snippets[index], position == self.selected));
+        }
+        if self.matches.is_empty() {
+            lines.push(style("  No matching snippets").dim().to_string());
+        }
+        lines.push(style(format!("  {}/{}", self.matches.len(), self.snippets.len())).dim().to_string());
+
+        if let Some(&index) = self.matches.get(self.selected) {
+            lines.push(style("─".repeat(width.saturating_sub(1).min(80))).dim().to_string());
+            let mut preview = preview(&self.snippets[index]);
+            if preview.len() > preview_rows {
+                preview.truncate(preview_rows.saturating_sub(1));
+                preview.push(style("  …").dim().to_string());
+            }
+            lines.extend(preview);
+        }
+
+        term.clear_last_lines(self.drawn)?;
+        for line in &lines {
+            term.write_line(&truncate_str(line, width.saturating_sub(1), "…"))?;
+        }
+        term.flush()?;
+        self.drawn = lines.len();
+        Ok(())
+    }
+
+    fn list_line(&self, snippet: &Snippet, selected: bool) -> String {
+        let marker = if selected { style("❯").green().to_string() } else { " ".to_string() };
+        let name = if selected { style(&snippet.name).bold().to_string() } else { snippet.name.clone() };
+        let tags = if snippet.tags.is_empty() {
+            String::new()
+        } else {
+            format!(" {}", style(format!("[{}]", snippet.tags.join(", "))).cyan())
+        };
+        let detail = match &snippet.description {
+            Some(description) => description.clone(),
+            None if snippet.steps.is_empty() => snippet.command.lines().next().unwrap_or_default().to_string(),
+            None => format!("runbook, {} steps", snippet.steps.len()),
+        };
+        format!("{} {}{}  {}", marker, name, tags, style(detail).dim())
+    }
+}
+
+/// The template and its variables
+fn preview(snippet: &Snippet) -> Vec<String> {
+    let mut lines = vec![match &snippet.description {
+        Some(description) => format!("{} {}", style(&snippet.name).bold(), style(format!("— {}", description)).dim()),
+        None => style(&snippet.name).bold().to_string(),
+    }];
+
+    let commands: Vec<&str> = if snippet.steps.is_empty() {
+        lines.extend(snippet.command.lines().map(|line| format!("  {}", line)));
+        vec![snippet.command.as_str()]
+    } else {
+        for (index, step) in snippet.steps.iter().enumerate() {
+            lines.push(format!("  {}. {}: {}", index + 1, runbook::step_name(step, index), step.command));
+        }
+        snippet.steps.iter().map(|step| step.command.as_str()).collect()
+    };
+
+    let mut variables: Vec<String> = Vec::new();
+    let mut seen: Vec<String> = Vec::new();
+    for template in commands.iter().filter_map(|command| Template::parse(command, Shell::Bash).ok()) {
+        for placeholder in template.placeholders() {
+            if seen.contains(&placeholder.name) {
+                continue;
+            }
+            seen.push(placeholder.name.clone());
+            variables.push(if !placeholder.choices.is_empty() {
+                format!("{} ({})", placeholder.name, placeholder.choices.join("|"))
+            } else if let Some(default) = &placeholder.default {
+                format!("{} (default {:?})", placeholder.name, default)
+            } else {
+                placeholder.name.clone()
+            });
+        }
+    }
+    if !variables.is_empty() {
+        lines.push(format!("{} {}", style("Variables:").cyan(), variables.join(", ")));
+    }
+    lines
+}
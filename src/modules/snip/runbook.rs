@@ -0,0 +1,493 @@
+use crate::modules::snip::command::shell_command;
+use crate::modules::snip::template::Template;
+use crate::platform::Shell;
+use crate::state::store::SnippetStep;
+use crate::utils::trace::TracedCommand;
+use anyhow::{Context as AnyhowContext, Result};
+use dialoguer::{theme::ColorfulTheme, Confirm};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::IsTerminal;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tabled::{Table, Tabled};
+
+/// A runbook as written in a TOML, YAML or JSON file
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RunbookFile {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    pub steps: Vec<SnippetStep>,
+}
+
+impl RunbookFile {
+    /// Read a runbook file, choosing the format by its extension
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let runbook: RunbookFile = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => toml::from_str(&content).map_err(anyhow::Error::from),
+            Some("yaml" | "yml") => serde_norway::from_str(&content).map_err(anyhow::Error::from),
+            Some("json") => serde_json::from_str(&content).map_err(anyhow::Error::from),
+            _ => anyhow::bail!("Unsupported runbook file {}: use .toml, .yaml, .yml or .json", path.display()),
+        }
+        .with_context(|| format!("Invalid runbook {}", path.display()))?;
+        validate(&runbook.steps).with_context(|| format!("Invalid runbook {}", path.display()))?;
+        Ok(runbook)
+    }
+
+    pub fn parse_toml(content: &str) -> Result<Self> {
+        let runbook: RunbookFile = toml::from_str(content)?;
+        validate(&runbook.steps)?;
+        Ok(runbook)
+    }
+
+    pub fn to_toml(&self) -> Result<String> {
+        Ok(toml::to_string_pretty(self)?)
+    }
+}
+
+/// When a step runs
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Condition {
+    /// No earlier step failed (failures with `continue_on_error` do not count)
+    Success,
+    /// An earlier step failed and stopped the runbook
+    Failure,
+    Always,
+    /// An earlier step ran and its exit code is (`equal`) or is not `code`
+    ExitCode { step: String, equal: bool, code: i32 },
+}
+
+impl Condition {
+    fn parse(text: &str) -> Result<Self> {
+        let text = text.trim();
+        match text {
+            "success" => return Ok(Condition::Success),
+            "failure" => return Ok(Condition::Failure),
+            "always" => return Ok(Condition::Always),
+            _ => {}
+        }
+
+        let invalid = || {
+            anyhow::anyhow!(
+                "Invalid condition '{}': use success, failure, always, or <step> == <code> / <step> != <code>",
+                text
+            )
+        };
+        let (step, equal, code) = if let Some((step, code)) = text.split_once("==") {
+            (step, true, code)
+        } else if let Some((step, code)) = text.split_once("!=") {
+            (step, false, code)
+        } else {
+            return Err(invalid());
+        };
+        let step = step.trim();
+        if step.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Condition::ExitCode {
+            step: step.to_string(),
+            equal,
+            code: code.trim().parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+/// Name shown for a step and used in conditions
+pub fn step_name(step: &SnippetStep, index: usize) -> String {
+    step.name.clone().unwrap_or_else(|| format!("step {}", index + 1))
+}
+
+/// Check that a runbook has steps, unique names, valid templates and
+/// conditions that only refer to earlier steps
+pub fn validate(steps: &[SnippetStep]) -> Result<()> {
+    if steps.is_empty() {
+        anyhow::bail!("A runbook needs at least one step");
+    }
+
+    let mut names: Vec<String> = Vec::new();
+    for (index, step) in steps.iter().enumerate() {
+        let name = step_name(step, index);
+        if names.contains(&name) {
+            anyhow::bail!("Two steps are named '{}'", name);
+        }
+        if step.command.trim().is_empty() {
+            anyhow::bail!("Step '{}' has no command", name);
+        }
+        Template::parse(&step.command, Shell::Bash).with_context(|| format!("Step '{}'", name))?;
+        if let Some(when) = &step.when {
+            if let Condition::ExitCode { step: other, .. } = Condition::parse(when).with_context(|| format!("Step '{}'", name))? {
+                if !names.contains(&other) {
+                    anyhow::bail!("Step '{}' depends on '{}', which is not an earlier step", name, other);
+                }
+            }
+        }
+        names.push(name);
+    }
+    Ok(())
+}
+
+/// Print each step with its options, as `show` and `--dry-run` do
+pub fn print_plan(steps: &[SnippetStep], commands: &[String]) {
+    for (index, (step, command)) in steps.iter().zip(commands).enumerate() {
+        println!("  {}. {}: {}", index + 1, step_name(step, index), command);
+
+        let mut options = Vec::new();
+        if let Some(cwd) = &step.cwd {
+            options.push(format!("cwd: {}", cwd));
+        }
+        if !step.env.is_empty() {
+            let mut keys: Vec<&String> = step.env.keys().collect();
+            keys.sort();
+            options.push(format!("env: {}", keys.iter().map(|key| key.as_str()).collect::<Vec<_>>().join(", ")));
+        }
+        if let Some(when) = &step.when {
+            options.push(format!("when: {}", when));
+        }
+        if step.confirm {
+            options.push("confirm".to_string());
+        }
+        if step.continue_on_error {
+            options.push("continue on error".to_string());
+        }
+        if !options.is_empty() {
+            println!("     [{}]", options.join("] ["));
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Succeeded,
+    Failed,
+    /// Failed with `continue_on_error`
+    FailedContinued,
+    Skipped,
+    Declined,
+}
+
+struct StepResult {
+    name: String,
+    status: Status,
+    exit_code: Option<i32>,
+    duration: Duration,
+}
+
+#[derive(Tabled)]
+struct SummaryRow {
+    #[tabled(rename = "Step")]
+    step: String,
+    #[tabled(rename = "Status")]
+    status: String,
+    #[tabled(rename = "Exit code")]
+    exit_code: String,
+    #[tabled(rename = "Duration")]
+    duration: String,
+}
+
+/// Run a runbook's steps in order, given their filled-in commands. Returns the
+/// exit code to fail with, if a step failed without `continue_on_error` or
+/// was declined.
+pub fn run(name: &str, steps: &[SnippetStep], commands: &[String], shell: Shell, yes: bool) -> Result<Option<i32>> {
+    let interactive = std::io::stdin().is_terminal() && std::io::stderr().is_terminal();
+    println!("▶ Runbook '{}' ({} steps)", name, steps.len());
+    let (results, exit_code) = run_steps(steps, commands, shell, yes, interactive)?;
+    print_summary(&results);
+    Ok(exit_code)
+}
+
+/// The steps' results and the exit code to fail with; `confirm` steps are
+/// only asked about when `interactive`
+fn run_steps(
+    steps: &[SnippetStep],
+    commands: &[String],
+    shell: Shell,
+    yes: bool,
+    interactive: bool,
+) -> Result<(Vec<StepResult>, Option<i32>)> {
+    let mut results: Vec<StepResult> = Vec::new();
+    let mut exit_code = None;
+
+    for (index, (step, command)) in steps.iter().zip(commands).enumerate() {
+        let step_name = step_name(step, index);
+        let condition = match &step.when {
+            Some(when) => Condition::parse(when)?,
+            None => Condition::Success,
+        };
+        let should_run = match &condition {
+            Condition::Success => exit_code.is_none(),
+            Condition::Failure => exit_code.is_some(),
+            Condition::Always => true,
+            Condition::ExitCode { step, equal, code } => results
+                .iter()
+                .find(|result| result.name == *step)
+                .and_then(|result| result.exit_code)
+                .is_some_and(|actual| (actual == *code) == *equal),
+        };
+
+        println!("\n[{}/{}] {}: {}", index + 1, steps.len(), step_name, command);
+        if !should_run {
+            println!("  ⏭  Skipped (when: {})", step.when.as_deref().unwrap_or("success"));
+            results.push(StepResult { name: step_name, status: Status::Skipped, exit_code: None, duration: Duration::ZERO });
+            continue;
+        }
+
+        if step.confirm && !yes {
+            let confirmed = interactive
+                && Confirm::with_theme(&ColorfulTheme::default())
+                    .with_prompt(format!("Run step '{}'?", step_name))
+                    .default(false)
+                    .interact()?;
+            if !confirmed {
+                if !interactive {
+                    println!("  Needs confirmation, but there is no terminal to ask in. Pass --yes to run it anyway.");
+                }
+                println!("  ✗ Declined");
+                exit_code.get_or_insert(1);
+                results.push(StepResult { name: step_name, status: Status::Declined, exit_code: None, duration: Duration::ZERO });
+                continue;
+            }
+        }
+
+        let started = Instant::now();
+        let mut process = shell_command(shell, command);
+        if let Some(cwd) = &step.cwd {
+            process.current_dir(cwd);
+        }
+        process.envs(&step.env);
+        let code = match process.traced_status() {
+            Ok(status) => status.code(),
+            Err(e) => {
+                println!("  Failed to start: {}", e);
+                None
+            }
+        };
+        let duration = started.elapsed();
+
+        let status = if code == Some(0) {
+            println!("  ✓ Done in {}", format_duration(duration));
+            Status::Succeeded
+        } else {
+            let reason = code.map_or("was interrupted".to_string(), |code| format!("exited with {}", code));
+            if step.continue_on_error {
+                println!("  ✗ Step {} after {}; continuing", reason, format_duration(duration));
+                Status::FailedContinued
+            } else {
+                println!("  ✗ Step {} after {}", reason, format_duration(duration));
+                exit_code.get_or_insert(code.unwrap_or(1));
+                Status::Failed
+            }
+        };
+        results.push(StepResult { name: step_name, status, exit_code: code, duration });
+    }
+
+    Ok((results, exit_code))
+}
+
+fn print_summary(results: &[StepResult]) {
+    let rows: Vec<SummaryRow> = results
+        .iter()
+        .map(|result| SummaryRow {
+            step: result.name.clone(),
+            status: match result.status {
+                Status::Succeeded => "✓ succeeded",
+                Status::Failed => "✗ failed",
+                Status::FailedContinued => "✗ failed (continued)",
+                Status::Skipped => "skipped",
+                Status::Declined => "declined",
+            }
+            .to_string(),
+            exit_code: result.exit_code.map(|code| code.to_string()).unwrap_or_default(),
+            duration: if result.status == Status::Skipped || result.status == Status::Declined {
+                String::new()
+            } else {
+                format_duration(result.duration)
+            },
+        })
+        .collect();
+
+    let total: Duration = results.iter().map(|result| result.duration).sum();
+    println!("\nSummary ({} total):", format_duration(total));
+    println!("{}", Table::new(rows));
+}
+
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs_f64();
+    if secs < 60.0 {
+        format!("{:.1}s", secs)
+    } else {
+        format!("{}m {:02}s", duration.as_secs() / 60, duration.as_secs() % 60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    type Outcome = (String, Status, Option<i32>);
+
+    fn step(name: &str, command: &str) -> SnippetStep {
+        SnippetStep {
+            name: Some(name.to_string()),
+            command: command.to_string(),
+            cwd: None,
+            env: HashMap::new(),
+            confirm: false,
+            continue_on_error: false,
+            when: None,
+        }
+    }
+
+    fn when(mut step: SnippetStep, condition: &str) -> SnippetStep {
+        step.when = Some(condition.to_string());
+        step
+    }
+
+    /// Run the steps without a terminal and return each step's status and exit code
+    fn outcomes(steps: &[SnippetStep], yes: bool) -> (Vec<Outcome>, Option<i32>) {
+        let commands: Vec<String> = steps.iter().map(|step| step.command.clone()).collect();
+        let (results, exit_code) = run_steps(steps, &commands, Shell::Bash, yes, false).unwrap();
+        let outcomes = results.into_iter().map(|result| (result.name, result.status, result.exit_code)).collect();
+        (outcomes, exit_code)
+    }
+
+    fn outcome(name: &str, status: Status, exit_code: Option<i32>) -> Outcome {
+        (name.to_string(), status, exit_code)
+    }
+
+    #[test]
+    fn conditions_parse() {
+        assert_eq!(Condition::parse(" success ").unwrap(), Condition::Success);
+        assert_eq!(Condition::parse("failure").unwrap(), Condition::Failure);
+        assert_eq!(Condition::parse("always").unwrap(), Condition::Always);
+        assert_eq!(
+            Condition::parse("migrate == 0").unwrap(),
+            Condition::ExitCode { step: "migrate".to_string(), equal: true, code: 0 }
+        );
+        assert_eq!(
+            Condition::parse("step 2!=-1").unwrap(),
+            Condition::ExitCode { step: "step 2".to_string(), equal: false, code: -1 }
+        );
+        for invalid in ["sometimes", "== 0", "migrate == zero", "migrate > 0", ""] {
+            let error = Condition::parse(invalid).unwrap_err().to_string();
+            assert!(error.starts_with("Invalid condition"), "{}: {}", invalid, error);
+        }
+    }
+
+    #[test]
+    fn validation_rejects_bad_steps() {
+        let error = |steps: &[SnippetStep]| format!("{:#}", validate(steps).unwrap_err());
+
+        assert_eq!(error(&[]), "A runbook needs at least one step");
+        assert_eq!(error(&[step("build", "make"), step("build", "make test")]), "Two steps are named 'build'");
+        assert_eq!(error(&[step("build", "  ")]), "Step 'build' has no command");
+        assert!(error(&[step("build", "make {target")]).starts_with("Step 'build': Unclosed"));
+        assert!(error(&[when(step("build", "make"), "sometimes")]).starts_with("Step 'build': Invalid condition"));
+        assert_eq!(
+            error(&[when(step("build", "make"), "test != 0"), step("test", "make test")]),
+            "Step 'build' depends on 'test', which is not an earlier step"
+        );
+        assert_eq!(
+            error(&[step("build", "make"), when(step("notify", "echo"), "deploy == 0")]),
+            "Step 'notify' depends on 'deploy', which is not an earlier step"
+        );
+
+        // Unnamed steps are referred to by position
+        let mut unnamed = step("", "make");
+        unnamed.name = None;
+        validate(&[unnamed, when(step("report", "echo"), "step 1 != 0")]).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn a_failure_skips_later_steps_but_runs_cleanup() {
+        let steps = [
+            step("build", "true"),
+            step("test", "exit 3"),
+            step("deploy", "true"),
+            when(step("rollback", "true"), "failure"),
+            when(step("cleanup", "true"), "always"),
+            when(step("report", "true"), "test == 3"),
+        ];
+        let (outcomes, exit_code) = outcomes(&steps, false);
+        assert_eq!(
+            outcomes,
+            [
+                outcome("build", Status::Succeeded, Some(0)),
+                outcome("test", Status::Failed, Some(3)),
+                outcome("deploy", Status::Skipped, None),
+                outcome("rollback", Status::Succeeded, Some(0)),
+                outcome("cleanup", Status::Succeeded, Some(0)),
+                outcome("report", Status::Succeeded, Some(0)),
+            ]
+        );
+        assert_eq!(exit_code, Some(3));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn continue_on_error_does_not_fail_the_runbook() {
+        let mut lint = step("lint", "exit 2");
+        lint.continue_on_error = true;
+        let steps = [
+            lint,
+            step("build", "true"),
+            when(step("fix", "true"), "lint != 0"),
+            when(step("rollback", "true"), "failure"),
+        ];
+        let (outcomes, exit_code) = outcomes(&steps, false);
+        assert_eq!(
+            outcomes,
+            [
+                outcome("lint", Status::FailedContinued, Some(2)),
+                outcome("build", Status::Succeeded, Some(0)),
+                outcome("fix", Status::Succeeded, Some(0)),
+                outcome("rollback", Status::Skipped, None),
+            ]
+        );
+        assert_eq!(exit_code, None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unconfirmed_steps_are_declined_without_a_terminal() {
+        let mut deploy = step("deploy", "true");
+        deploy.confirm = true;
+        let steps = [deploy, step("announce", "true"), when(step("cleanup", "true"), "always")];
+
+        let (declined, exit_code) = outcomes(&steps, false);
+        assert_eq!(
+            declined,
+            [
+                outcome("deploy", Status::Declined, None),
+                outcome("announce", Status::Skipped, None),
+                outcome("cleanup", Status::Succeeded, Some(0)),
+            ]
+        );
+        assert_eq!(exit_code, Some(1));
+
+        // --yes runs it without asking
+        let (confirmed, exit_code) = outcomes(&steps, true);
+        assert_eq!(confirmed[0], outcome("deploy", Status::Succeeded, Some(0)));
+        assert_eq!(exit_code, None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn steps_get_their_cwd_and_env() {
+        let dir = std::env::temp_dir();
+        let mut check = step("check", r#"[ "$(pwd -P)" = "$EXPECTED" ] && [ "$GREETING" = hi ]"#);
+        check.cwd = Some(dir.to_string_lossy().to_string());
+        check.env = HashMap::from([
+            ("EXPECTED".to_string(), dir.canonicalize().unwrap().to_string_lossy().to_string()),
+            ("GREETING".to_string(), "hi".to_string()),
+        ]);
+        let (outcomes, exit_code) = outcomes(&[check], false);
+        assert_eq!(outcomes, [outcome("check", Status::Succeeded, Some(0))]);
+        assert_eq!(exit_code, None);
+    }
+}
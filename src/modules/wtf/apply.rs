@@ -0,0 +1,146 @@
+use crate::app::AppContext;
+use crate::state::store::{WtfAction, WtfAppliedAction, WtfExplanation};
+use crate::utils::trace::TracedCommand;
+use anyhow::{Context as AnyhowContext, Result};
+use dialoguer::{theme::ColorfulTheme, Confirm};
+use std::io::{self, IsTerminal, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// One-line summary of an action
+pub fn describe(action: &WtfAction) -> String {
+    match action {
+        WtfAction::Command { command } => format!("Run `{}`", command),
+        WtfAction::Patch { diff } => {
+            let files: Vec<&str> = diff
+                .lines()
+                .filter_map(|line| line.strip_prefix("+++ "))
+                .map(|path| path.split('\t').next().unwrap_or(path).trim())
+                .map(|path| path.strip_prefix("b/").unwrap_or(path))
+                .filter(|path| *path != "/dev/null")
+                .collect();
+            if files.is_empty() {
+                "Apply a patch".to_string()
+            } else {
+                format!("Patch {}", files.join(", "))
+            }
+        }
+    }
+}
+
+/// Show each action, check patches with a dry run, and carry out the ones the
+/// user confirms. Every attempted action is recorded in the history.
+pub fn apply_actions(explanation: &WtfExplanation, dir: &Path, app_ctx: &mut AppContext) -> Result<()> {
+    if explanation.actions.is_empty() {
+        println!("No actions to apply.");
+        return Ok(());
+    }
+    if !io::stderr().is_terminal() {
+        return Err(anyhow::anyhow!("--apply asks before each action, so it needs a terminal"));
+    }
+
+    let total = explanation.actions.len();
+    for (index, action) in explanation.actions.iter().enumerate() {
+        println!("[{}/{}] {}", index + 1, total, describe(action));
+
+        let prompt = match action {
+            WtfAction::Command { .. } => format!("Run it in {}?", dir.display()),
+            WtfAction::Patch { diff } => {
+                println!("{}", diff.trim_end());
+                if let Err(e) = patch_strip_level(dir, diff) {
+                    println!("✗ Skipping: the patch does not apply cleanly: {:#}\n", e);
+                    continue;
+                }
+                "Apply this patch?".to_string()
+            }
+        };
+
+        let confirmed = Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(prompt)
+            .default(false)
+            .interact()?;
+        if !confirmed {
+            println!("Skipped.\n");
+            continue;
+        }
+
+        let result = match action {
+            WtfAction::Command { command } => run_command(dir, command),
+            WtfAction::Patch { diff } => patch_strip_level(dir, diff).and_then(|strip| git_apply(dir, diff, strip, false)),
+        };
+        match &result {
+            Ok(()) => println!("✓ Done\n"),
+            Err(e) => println!("✗ {:#}\n", e),
+        }
+
+        app_ctx.state.save_wtf_applied_action(WtfAppliedAction {
+            fingerprint: explanation.fingerprint.clone(),
+            action: action.clone(),
+            directory: dir.display().to_string(),
+            applied_at: chrono::Local::now().to_rfc3339(),
+            success: result.is_ok(),
+        })?;
+    }
+
+    Ok(())
+}
+
+fn run_command(dir: &Path, command: &str) -> Result<()> {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("powershell");
+        shell.arg("-Command");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+
+    let status = shell
+        .arg(command)
+        .current_dir(dir)
+        .traced_status()
+        .with_context(|| format!("Failed to run '{}'", command))?;
+    if !status.success() {
+        return Err(anyhow::anyhow!("`{}` exited with code {}", command, status.code().unwrap_or(1)));
+    }
+    Ok(())
+}
+
+/// Dry-run the patch with `git apply --check`, with and without `a/`/`b/` prefixes,
+/// and return the `-p` level that applies
+fn patch_strip_level(dir: &Path, diff: &str) -> Result<u8> {
+    match git_apply(dir, diff, 1, true) {
+        Ok(()) => Ok(1),
+        Err(e) => git_apply(dir, diff, 0, true).map(|()| 0).map_err(|_| e),
+    }
+}
+
+fn git_apply(dir: &Path, diff: &str, strip: u8, check: bool) -> Result<()> {
+    let mut command = Command::new("git");
+    command.arg("apply").arg(format!("-p{}", strip));
+    if check {
+        command.arg("--check");
+    }
+
+    let mut child = command
+        .current_dir(dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .traced_spawn()
+        .context("Failed to run `git apply`")?;
+
+    let mut diff = diff.to_string();
+    if !diff.ends_with('\n') {
+        diff.push('\n');
+    }
+    child.stdin.take().unwrap().write_all(diff.as_bytes())
+        .context("Failed to pass the patch to `git apply`")?;
+
+    let output = child.wait_with_output().context("Failed to run `git apply`")?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!("{}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(())
+}
@@ -1,53 +1,345 @@
-use crate::app::AppContext;
-use crate::cli::WtfArgs;
-use crate::state::store::WtfExplanation;
+use crate::app::{AppContext, ExitCode};
+use crate::cli::{ProviderArgs, Rating, WtfArgs, WtfCommand, WtfRulesCommand};
+use crate::modules::wtf::parsers::{self, Diagnostic};
+use crate::modules::wtf::normalize::{self, Normalizer};
+use crate::modules::wtf::apply;
+use crate::modules::wtf::project;
+use crate::modules::wtf::rules;
+use crate::state::store::{WtfAction, WtfExplanation};
+use crate::utils::trace::TracedCommand;
 use anyhow::{Context as AnyhowContext, Result};
-use regex;
 use sha2::{Digest, Sha256};
-use std::io::{self, Write};
+use std::fs;
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use tabled::{Table, Tabled};
+
+/// Errors are at the end of build output, so only the last lines are explained
+const MAX_INPUT_LINES: usize = 200;
 
 /// Run the error explanation (wtf) command
 pub fn run(args: WtfArgs, app_ctx: &mut AppContext) -> Result<()> {
-    let error_text = args.error_text.trim();
+    match args.action {
+        Some(WtfCommand::Rules(rules_args)) => {
+            return match rules_args.command {
+                WtfRulesCommand::List { scope } => list_rules(scope),
+                WtfRulesCommand::Test { text } => test_rules(text),
+                WtfRulesCommand::Add { id, pattern, cause, fix, scope, links, commands, confidence, pack } => {
+                    let actions = commands.into_iter().map(|command| WtfAction::Command { command }).collect();
+                    let rule = rules::Rule { id, pattern, scope, cause, fix, links, actions, confidence };
+                    add_rule(rule, &pack)
+                }
+            };
+        }
+        Some(WtfCommand::History { limit, applied: false }) => return show_history(limit, app_ctx),
+        Some(WtfCommand::History { limit, applied: true }) => return show_applied(limit, app_ctx),
+        Some(WtfCommand::Forget { fingerprint }) => return forget(&fingerprint, app_ctx),
+        Some(WtfCommand::Rate { rating, fingerprint }) => return rate(rating, fingerprint, app_ctx),
+        None => {}
+    }
     
-    if error_text.is_empty() {
-        return Err(anyhow::anyhow!("Error text cannot be empty"));
+    let cache = if args.no_cache {
+        CacheMode::Bypass
+    } else if args.refresh {
+        CacheMode::Refresh
+    } else {
+        CacheMode::Use
+    };
+    let options = ExplainOptions { cache, context_lines: args.context_lines, apply: args.apply, llm: args.llm };
+    
+    if !args.command.is_empty() {
+        return run_wrapped(&args.command, &options, app_ctx);
+    }
+    
+    let error_text = match args.error_text {
+        Some(text) => text,
+        None if !io::stdin().is_terminal() => {
+            let mut text = String::new();
+            io::stdin().read_to_string(&mut text)
+                .context("Failed to read error text from stdin")?;
+            if text.trim().is_empty() {
+                return explain_last_failure(&options, app_ctx);
+            }
+            text
+        }
+        None => return explain_last_failure(&options, app_ctx),
+    };
+    
+    let cwd = std::env::current_dir().context("Failed to get current directory")?;
+    explain(&error_text, None, &cwd, &options, app_ctx)
+}
+
+/// How `explain` uses the explanation cache
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CacheMode {
+    Use,
+    /// Regenerate and overwrite the cached entry
+    Refresh,
+    /// Neither read nor write the cache
+    Bypass,
+}
+
+#[derive(Debug, Clone)]
+struct ExplainOptions {
+    cache: CacheMode,
+    /// Source lines either side of the error to send to the LLM
+    context_lines: usize,
+    /// Offer to carry out the explanation's actions
+    apply: bool,
+    llm: ProviderArgs,
+}
+
+/// The last failed command, as recorded by the `dev init` shell hook
+struct LastFailure {
+    status: i32,
+    cwd: String,
+    command: String,
+    stderr: Option<String>,
+}
+
+fn load_last_failure() -> Result<Option<LastFailure>> {
+    let run_dir = crate::platform::dev_home()?.join("run");
+    let Ok(content) = fs::read_to_string(run_dir.join("last")) else {
+        return Ok(None);
+    };
+    
+    let mut failure = LastFailure { status: 1, cwd: String::new(), command: String::new(), stderr: None };
+    let mut lines = content.lines();
+    while let Some(line) = lines.next() {
+        if let Some(status) = line.strip_prefix("status=") {
+            failure.status = status.trim().parse().unwrap_or(1);
+        } else if let Some(cwd) = line.strip_prefix("cwd=") {
+            failure.cwd = cwd.to_string();
+        } else if let Some(command) = line.strip_prefix("cmd=") {
+            // Written last, and may span several lines
+            let rest: Vec<&str> = std::iter::once(command).chain(lines.by_ref()).collect();
+            failure.command = rest.join("\n");
+        }
     }
     
-    // Generate fingerprint
-    let fingerprint = generate_fingerprint(error_text);
+    failure.stderr = fs::read_to_string(run_dir.join("last.stderr"))
+        .ok()
+        .filter(|stderr| !stderr.trim().is_empty());
     
-    // Check cache
-    if let Some(explanation) = app_ctx.state.get_wtf_explanation(&fingerprint)
-        .context("Failed to check WTF cache")? {
-        print_explanation(&explanation);
+    Ok(Some(failure))
+}
+
+fn explain_last_failure(options: &ExplainOptions, app_ctx: &mut AppContext) -> Result<()> {
+    let failure = load_last_failure()?.ok_or_else(|| anyhow::anyhow!(
+        "No failed command recorded. Add `eval \"$(dev init)\"` to your shell rc, \
+         pass the error text, pipe it in, or run `dev wtf -- <command>`"
+    ))?;
+    
+    eprintln!("Explaining `{}` (exit code {}) in {}\n", failure.command, failure.status, failure.cwd);
+    
+    let command_info = format!(
+        "Command: {}\nWorking directory: {}\nExit code: {}",
+        failure.command, failure.cwd, failure.status
+    );
+    let error_text = match &failure.stderr {
+        Some(stderr) => stderr.clone(),
+        None => format!("`{}` failed with exit code {}", failure.command, failure.status),
+    };
+    
+    let project_dir = match failure.cwd.as_str() {
+        "" => std::env::current_dir().context("Failed to get current directory")?,
+        cwd => PathBuf::from(cwd),
+    };
+    explain(&error_text, Some(&command_info), &project_dir, options, app_ctx)
+}
+
+/// Run a command, echoing its output, and explain the output if it fails.
+/// The command's exit code is passed through.
+fn run_wrapped(command: &[String], options: &ExplainOptions, app_ctx: &mut AppContext) -> Result<()> {
+    let mut child = Command::new(&command[0])
+        .args(&command[1..])
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .traced_spawn()
+        .with_context(|| format!("Failed to run '{}'", command[0]))?;
+    
+    // Both streams go to one buffer so the captured output keeps its order
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let stdout = child.stdout.take().map(|pipe| tee(pipe, io::stdout(), Arc::clone(&output)));
+    let stderr = child.stderr.take().map(|pipe| tee(pipe, io::stderr(), Arc::clone(&output)));
+    
+    let status = child.wait()
+        .with_context(|| format!("Failed to wait for '{}'", command[0]))?;
+    for handle in stdout.into_iter().chain(stderr) {
+        let _ = handle.join();
+    }
+    
+    if status.success() {
         return Ok(());
     }
     
+    let code = exit_code(status);
+    let command_line = command.join(" ");
+    eprintln!("\n✗ `{}` exited with code {}\n", command_line, code);
+    
+    let output = String::from_utf8_lossy(&output.lock().unwrap()).to_string();
+    if output.trim().is_empty() {
+        eprintln!("The command printed no output to explain.");
+    } else {
+        // The wrapped command's exit code is what callers see, even if explaining fails
+        let explained = std::env::current_dir()
+            .context("Failed to get current directory")
+            .and_then(|cwd| {
+                let command_info = format!("Command: {}\nWorking directory: {}\nExit code: {}", command_line, cwd.display(), code);
+                explain(&output, Some(&command_info), &cwd, options, app_ctx)
+            });
+        if let Err(e) = explained {
+            eprintln!("⚠️  Could not explain the error: {:#}", e);
+        }
+    }
+    
+    Err(ExitCode(code).into())
+}
+
+fn tee<R, W>(mut pipe: R, mut out: W, buffer: Arc<Mutex<Vec<u8>>>) -> thread::JoinHandle<()>
+where
+    R: Read + Send + 'static,
+    W: Write + Send + 'static,
+{
+    thread::spawn(move || {
+        let mut chunk = [0u8; 8192];
+        while let Ok(n) = pipe.read(&mut chunk) {
+            if n == 0 {
+                break;
+            }
+            let _ = out.write_all(&chunk[..n]);
+            let _ = out.flush();
+            buffer.lock().unwrap().extend_from_slice(&chunk[..n]);
+        }
+    })
+}
+
+/// Exit code of a process, using the shell convention of 128 + signal when it was killed
+fn exit_code(status: ExitStatus) -> i32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+    status.code().unwrap_or(1)
+}
+
+fn tail_lines(text: &str, max: usize) -> String {
+    let lines: Vec<&str> = text.lines().collect();
+    lines[lines.len().saturating_sub(max)..].join("\n")
+}
+
+/// Explain error output, using the fingerprint cache. `command_info` and the
+/// project in `project_dir` give the LLM context but are not part of the fingerprint.
+fn explain(
+    error_text: &str,
+    command_info: Option<&str>,
+    project_dir: &Path,
+    options: &ExplainOptions,
+    app_ctx: &mut AppContext,
+) -> Result<()> {
+    let mut cache = options.cache;
+    if error_text.trim().is_empty() {
+        return Err(anyhow::anyhow!("Error text cannot be empty"));
+    }
+    
+    // Work on the primary diagnostic when the output can be parsed, otherwise on the tail of the raw text
+    let diagnostics = parsers::parse(error_text);
+    let primary = parsers::primary(&diagnostics);
+    let error_text = match primary {
+        Some(diagnostic) => {
+            println!("Primary error: {}", diagnostic);
+            if diagnostics.len() > 1 {
+                println!("({} more diagnostic(s) in the output)", diagnostics.len() - 1);
+            }
+            println!();
+            diagnostic.rendered.clone()
+        }
+        None => tail_lines(error_text.trim(), MAX_INPUT_LINES),
+    };
+    let error_text = error_text.as_str();
+    
+    // Generate fingerprint from the normalized error
+    let normalizer = Normalizer::new(&app_ctx.config.wtf.normalize)?;
+    let normalized = match primary {
+        Some(diagnostic) => normalizer.normalize(&diagnostic_key(diagnostic)),
+        None => normalizer.normalize(error_text),
+    };
+    let fingerprint = generate_fingerprint(&normalized);
+    log::debug!("Fingerprint {} for normalized error: {}", short_fingerprint(&fingerprint), normalized);
+    
+    // Check cache, then fall back to the most similar cached error
+    if cache == CacheMode::Use {
+        match cached_explanation(&fingerprint, app_ctx)? {
+            Some(explanation) if fits(&explanation, error_text, &normalizer) => {
+                log::debug!("Cache hit for {}", short_fingerprint(&fingerprint));
+                app_ctx.state.record_wtf_hit(&fingerprint)?;
+                return show_explanation(&explanation, true, project_dir, options, app_ctx);
+            }
+            Some(_) => log::debug!("Cached {} is about details of another error", short_fingerprint(&fingerprint)),
+            // An expired low-confidence entry is regenerated, as with --refresh
+            None if app_ctx.state.get_wtf_explanation(&fingerprint)?.is_some() => cache = CacheMode::Refresh,
+            None => {}
+        }
+        if let Some((explanation, score)) = similar_explanation(&normalized, error_text, &normalizer, app_ctx)? {
+            log::debug!("Reusing {} ({:.2} similar)", short_fingerprint(&explanation.fingerprint), score);
+            app_ctx.state.record_wtf_hit(&explanation.fingerprint)?;
+            println!("Similar to a cached error ({:.0}% similar)\n", score * 100.0);
+            return show_explanation(&explanation, true, project_dir, options, app_ctx);
+        }
+    }
+    
     // Generate deterministic explanation stub or use API
-    let explanation = match call_llm_api(error_text, &fingerprint) {
+    let project_info = project::project_context(project_dir, primary, options.context_lines);
+    let llm_options = ExplainOptions { cache, ..options.clone() };
+    let explanation = match call_llm_api(error_text, command_info, &project_info, project_dir, &fingerprint, &llm_options, app_ctx) {
         Ok(api_explanation) => api_explanation,
         Err(e) => {
-            eprintln!("⚠️  API Call Failed: {}", e);
-            eprintln!("Falling back to pattern matching...\n");
-            generate_explanation(error_text, &fingerprint)
+            eprintln!("⚠️  API Call Failed: {:#}", e);
+            eprintln!("Falling back to offline rules...\n");
+            generate_explanation(error_text, primary, &fingerprint)?
         }
     };
     
-    // Save to cache
-    app_ctx.state.save_wtf_explanation(explanation.clone())
-        .context("Failed to save WTF explanation to cache")?;
+    let mut explanation = explanation;
+    explanation.summary = error_text.lines().find(|l| !l.trim().is_empty()).unwrap_or("").trim().to_string();
+    explanation.details = Some(mentioned_details(&explanation, error_text, &normalizer));
+    explanation.normalized = normalized;
     
-    print_explanation(&explanation);
+    // Save to cache
+    if cache != CacheMode::Bypass {
+        app_ctx.state.save_wtf_explanation(explanation.clone())
+            .context("Failed to save WTF explanation to cache")?;
+    }
     
+    show_explanation(&explanation, false, project_dir, options, app_ctx)
+}
+
+/// Print an explanation and, with `--apply`, offer its actions
+fn show_explanation(
+    explanation: &WtfExplanation,
+    cached: bool,
+    project_dir: &Path,
+    options: &ExplainOptions,
+    app_ctx: &mut AppContext,
+) -> Result<()> {
+    print_explanation(explanation, cached);
+    if options.apply {
+        println!();
+        apply::apply_actions(explanation, project_dir, app_ctx)?;
+    } else if !explanation.actions.is_empty() {
+        println!("\nRun again with --apply to carry out these actions.");
+    }
     Ok(())
 }
 
-fn generate_fingerprint(error_text: &str) -> String {
-    // Normalize the error text for fingerprinting
-    let normalized = normalize_error_text(error_text);
-    
-    // Hash the normalized text
+fn generate_fingerprint(normalized: &str) -> String {
     let mut hasher = Sha256::new();
     hasher.update(normalized.as_bytes());
     let hash = hasher.finalize();
@@ -56,115 +348,308 @@ fn generate_fingerprint(error_text: &str) -> String {
     format!("{:x}", hash)
 }
 
-fn normalize_error_text(text: &str) -> String {
-    // Normalize: lowercase, remove extra whitespace, remove file paths, remove line numbers
-    let mut normalized = text.to_lowercase();
-    
-    // Remove common file paths (simplified)
-    if let Ok(re) = regex::Regex::new(r"/[^\s]+\.(rs|py|js|ts|go|java|cpp|c|h)") {
-        normalized = re.replace_all(&normalized, "<file>").to_string();
+/// Text that identifies a parsed diagnostic, so the same error in another file or line matches
+fn diagnostic_key(diagnostic: &Diagnostic) -> String {
+    format!(
+        "{}|{}|{}",
+        diagnostic.tool,
+        diagnostic.code.as_deref().unwrap_or(""),
+        diagnostic.message
+    )
+}
+
+/// The most similar cached explanation at or above the configured threshold.
+/// Low-confidence entries are only reused for exact matches.
+fn similar_explanation(
+    normalized: &str,
+    error_text: &str,
+    normalizer: &Normalizer,
+    app_ctx: &mut AppContext,
+) -> Result<Option<(WtfExplanation, f64)>> {
+    let threshold = app_ctx.config.wtf.similarity_threshold;
+    let low_confidence = app_ctx.config.wtf.low_confidence_threshold;
+    if threshold >= 1.0 || normalized.is_empty() {
+        return Ok(None);
     }
     
-    // Remove line numbers
-    if let Ok(re) = regex::Regex::new(r":\d+:\d+") {
-        normalized = re.replace_all(&normalized, ":<line>").to_string();
-    }
+    let similar = normalizer.similar_form(normalized);
+    let best = app_ctx.state.list_wtf_explanations()
+        .context("Failed to check WTF cache")?
+        .into_iter()
+        .filter(|e| !e.normalized.is_empty() && e.confidence > low_confidence)
+        .filter(|e| fits(e, error_text, normalizer))
+        .map(|e| {
+            let score = normalize::similarity(&similar, &normalizer.similar_form(&e.normalized));
+            (e, score)
+        })
+        .filter(|(_, score)| *score >= threshold)
+        .max_by(|(_, a), (_, b)| a.total_cmp(b));
     
-    // Remove extra whitespace
-    if let Ok(re) = regex::Regex::new(r"\s+") {
-        normalized = re.replace_all(&normalized, " ").to_string();
+    let Some((candidate, score)) = best else {
+        return Ok(None);
+    };
+    // Go through the cache lookup so expiry rules still apply
+    Ok(cached_explanation(&candidate.fingerprint, app_ctx)?.map(|e| (e, score)))
+}
+
+/// Cause, fix and actions of an explanation as one text
+fn explanation_text(explanation: &WtfExplanation) -> String {
+    let mut text = format!("{}\n{}", explanation.cause, explanation.suggested_fix);
+    for action in &explanation.actions {
+        text.push('\n');
+        text.push_str(match action {
+            WtfAction::Command { command } => command,
+            WtfAction::Patch { diff } => diff,
+        });
     }
-    
-    normalized.trim().to_string()
+    text
 }
 
-use crate::utils::llm::call_llm_json;
+/// Details of the error that the explanation repeats, like the name of a missing module
+fn mentioned_details(explanation: &WtfExplanation, error_text: &str, normalizer: &Normalizer) -> Vec<String> {
+    let text = explanation_text(explanation);
+    normalizer.details(error_text).into_iter().filter(|detail| text.contains(detail.as_str())).collect()
+}
+
+/// Whether a cached explanation can be shown for this error: it must not name
+/// details (a module, path or address) that this error doesn't contain
+fn fits(explanation: &WtfExplanation, error_text: &str, normalizer: &Normalizer) -> bool {
+    let details = match &explanation.details {
+        Some(details) => details.clone(),
+        // Older entries didn't record them; check everything the explanation names
+        None => normalizer.details(&explanation_text(explanation)),
+    };
+    details.iter().all(|detail| error_text.contains(detail.as_str()))
+}
+
+use crate::utils::llm::{call_llm, LlmResponse, Prompt};
 
 // ... (imports)
 
 // remove calling_llm_api and OpenAIChoice/Response structs
 
-fn call_llm_api(error_text: &str, fingerprint: &str) -> Result<WtfExplanation> {
-    let system_prompt = "You are a helpful coding assistant. You explain errors and suggest fixes. Output valid JSON only with 'cause' and 'suggested_fix' fields.";
-    let user_prompt = format!(
-        "Explain this error concisely and provide a suggested fix.\\n\\nError: {}",
-        error_text
-    );
-    
-    let parsed = call_llm_json(system_prompt, &user_prompt)?;
+/// What the model returns for an error
+#[derive(serde::Deserialize)]
+struct WtfResponse {
+    cause: String,
+    suggested_fix: String,
+    #[serde(default)]
+    actions: Vec<WtfAction>,
+}
+
+impl LlmResponse for WtfResponse {
+    const SCHEMA: &'static str = r#"{"cause": string, "suggested_fix": string, "actions": [{"kind": "command", "command": string} | {"kind": "patch", "diff": string}]}"#;
+}
+
+fn call_llm_api(
+    error_text: &str,
+    command_info: Option<&str>,
+    project_info: &str,
+    project_dir: &Path,
+    fingerprint: &str,
+    options: &ExplainOptions,
+    app_ctx: &mut AppContext,
+) -> Result<WtfExplanation> {
+    let system_prompt = "You are a helpful coding assistant. You explain errors and suggest fixes. \
+        'actions' lists fixes that can be applied automatically: shell commands run in the \
+        project directory, or unified diffs with paths relative to it. \
+        Only include actions you are confident fix the error; use an empty list otherwise.";
+    let mut user_prompt = String::from("Explain this error concisely and provide a suggested fix.\n\n");
+    if let Some(info) = command_info {
+        user_prompt.push_str(info);
+        user_prompt.push_str("\n\n");
+    }
+    if !project_info.is_empty() {
+        user_prompt.push_str(project_info);
+        user_prompt.push_str("\n\n");
+    }
+    user_prompt.push_str(&format!("Error: {}", error_text));
     
-    let cause = parsed["cause"].as_str().unwrap_or("Unknown cause").to_string();
-    let suggested_fix = parsed["suggested_fix"].as_str().unwrap_or("No fix suggested").to_string();
+    let prompt = Prompt {
+        command: "wtf",
+        system: system_prompt,
+        user: &user_prompt,
+        project_dir: Some(project_dir),
+        // A replaced explanation must not come back from the LLM reply cache
+        no_cache: options.cache != CacheMode::Use,
+    };
+    let response: WtfResponse = call_llm(app_ctx, &options.llm, &prompt)?;
     
-    Ok(WtfExplanation {
-        fingerprint: fingerprint.to_string(),
-        cause,
-        suggested_fix,
-        confidence: 0.9,
-    })
+    let mut explanation = new_explanation(fingerprint, response.cause, response.suggested_fix, 0.9, "llm");
+    explanation.actions = response.actions;
+    Ok(explanation)
 }
 
 
-fn generate_explanation(error_text: &str, fingerprint: &str) -> WtfExplanation {
-    // Deterministic explanation stub based on error patterns
-    let (cause, suggested_fix) = analyze_error_pattern(error_text);
-    
+fn new_explanation(fingerprint: &str, cause: String, suggested_fix: String, confidence: f64, source: &str) -> WtfExplanation {
+    let now = chrono::Local::now().to_rfc3339();
     WtfExplanation {
         fingerprint: fingerprint.to_string(),
         cause,
         suggested_fix,
-        confidence: 0.5, // Static confidence for now
-    }
-}
-
-fn analyze_error_pattern(error_text: &str) -> (String, String) {
-    let lower = error_text.to_lowercase();
-    
-    // Pattern matching for common errors
-    if lower.contains("cannot find module") || lower.contains("module not found") {
-        (
-            "Module or file not found".to_string(),
-            "Check the file path and ensure the module exists. Verify imports and file structure.".to_string(),
-        )
-    } else if lower.contains("undefined") || lower.contains("is not defined") {
-        (
-            "Variable or function is undefined".to_string(),
-            "Ensure the variable/function is declared before use. Check for typos and scope issues.".to_string(),
-        )
-    } else if lower.contains("permission denied") || lower.contains("access denied") {
-        (
-            "Permission denied".to_string(),
-            "Check file permissions. Try running with appropriate permissions or fix file ownership.".to_string(),
-        )
-    } else if lower.contains("syntax error") || lower.contains("parse error") {
-        (
-            "Syntax or parse error".to_string(),
-            "Check for missing brackets, quotes, or semicolons. Verify the syntax matches the language requirements.".to_string(),
-        )
-    } else if lower.contains("type error") || lower.contains("type mismatch") {
-        (
-            "Type mismatch or type error".to_string(),
-            "Check that variable types match expected types. Verify function signatures and return types.".to_string(),
-        )
-    } else if lower.contains("connection refused") || lower.contains("connection failed") {
-        (
-            "Connection failed".to_string(),
-            "Check if the service is running. Verify network connectivity and firewall settings.".to_string(),
-        )
-    } else if lower.contains("out of memory") || lower.contains("memory") {
-        (
-            "Memory issue".to_string(),
-            "Check for memory leaks or excessive memory usage. Consider optimizing data structures or increasing available memory.".to_string(),
-        )
-    } else {
-        (
-            "Unknown error pattern".to_string(),
+        confidence,
+        source: source.to_string(),
+        summary: String::new(),
+        normalized: String::new(),
+        created_at: now.clone(),
+        last_used: now,
+        hits: 0,
+        actions: Vec::new(),
+        details: None,
+    }
+}
+
+/// A cached explanation, unless it is low-confidence and older than the configured TTL
+fn cached_explanation(fingerprint: &str, app_ctx: &mut AppContext) -> Result<Option<WtfExplanation>> {
+    let Some(explanation) = app_ctx.state.get_wtf_explanation(fingerprint)
+        .context("Failed to check WTF cache")? else {
+        return Ok(None);
+    };
+    
+    let config = &app_ctx.config.wtf;
+    if explanation.confidence <= config.low_confidence_threshold {
+        // Entries from before timestamps were recorded count as expired
+        let expired = chrono::DateTime::parse_from_rfc3339(&explanation.created_at)
+            .map(|created| chrono::Local::now().signed_duration_since(created).num_days() >= config.low_confidence_ttl_days)
+            .unwrap_or(true);
+        if expired {
+            log::debug!("Cached explanation {} expired", short_fingerprint(fingerprint));
+            return Ok(None);
+        }
+    }
+    
+    Ok(Some(explanation))
+}
+
+/// Offline explanation from the best matching rule in the rule packs
+fn generate_explanation(error_text: &str, diagnostic: Option<&Diagnostic>, fingerprint: &str) -> Result<WtfExplanation> {
+    let rules = rules::load_rules()?;
+    let best = rules::matching(&rules, error_text, diagnostic).into_iter().next();
+    match &best {
+        Some(matched) => log::debug!("Best rule: {} ({})", matched.rule.rule.id, matched.rule.source),
+        None => log::debug!("No rule matches"),
+    }
+    
+    let (cause, mut suggested_fix, confidence, actions) = match best {
+        Some(matched) => {
+            let mut fix = matched.fix;
+            if !matched.links.is_empty() {
+                fix.push_str(&format!(" See: {}", matched.links.join(", ")));
+            }
+            (matched.cause, fix, matched.rule.rule.confidence, matched.actions)
+        }
+        None => (
+            diagnostic.map_or_else(|| "Unknown error pattern".to_string(), |d| d.message.clone()),
             "Review the error message carefully. Check documentation, logs, and related code for clues.".to_string(),
-        )
+            0.1,
+            Vec::new(),
+        ),
+    };
+    
+    if let Some(location) = diagnostic.and_then(Diagnostic::location) {
+        suggested_fix = format!("Look at {}. {}", location, suggested_fix);
     }
+    
+    let mut explanation = new_explanation(fingerprint, cause, suggested_fix, confidence, "rules");
+    explanation.actions = actions;
+    Ok(explanation)
 }
 
-fn print_explanation(explanation: &WtfExplanation) {
+#[derive(Tabled)]
+struct RuleRow {
+    #[tabled(rename = "ID")]
+    id: String,
+    #[tabled(rename = "Scope")]
+    scope: String,
+    #[tabled(rename = "Confidence")]
+    confidence: String,
+    #[tabled(rename = "Source")]
+    source: String,
+}
+
+fn list_rules(scope: Option<String>) -> Result<()> {
+    let rules = rules::load_rules()?;
+    let rows: Vec<RuleRow> = rules
+        .iter()
+        .filter(|loaded| match &scope {
+            Some(scope) => loaded.rule.scope.as_deref().is_some_and(|s| s.eq_ignore_ascii_case(scope)),
+            None => true,
+        })
+        .map(|loaded| RuleRow {
+            id: loaded.rule.id.clone(),
+            scope: loaded.rule.scope.clone().unwrap_or_else(|| "any".to_string()),
+            confidence: format!("{:.0}%", loaded.rule.confidence * 100.0),
+            source: loaded.source.clone(),
+        })
+        .collect();
+    
+    if rows.is_empty() {
+        println!("No rules found.");
+        return Ok(());
+    }
+    println!("{}", Table::new(rows));
+    Ok(())
+}
+
+fn test_rules(text: Option<String>) -> Result<()> {
+    let text = match text {
+        Some(text) => text,
+        None if !io::stdin().is_terminal() => {
+            let mut text = String::new();
+            io::stdin().read_to_string(&mut text)
+                .context("Failed to read error text from stdin")?;
+            text
+        }
+        None => return Err(anyhow::anyhow!("Pass the error text or pipe it in")),
+    };
+    
+    let diagnostics = parsers::parse(&text);
+    let primary = parsers::primary(&diagnostics);
+    let subject = match primary {
+        Some(diagnostic) => {
+            println!("Primary diagnostic: {}", diagnostic);
+            diagnostic.rendered.clone()
+        }
+        None => {
+            println!("No diagnostic parsed; matching the raw text.");
+            tail_lines(text.trim(), MAX_INPUT_LINES)
+        }
+    };
+    
+    let rules = rules::load_rules()?;
+    let matches = rules::matching(&rules, &subject, primary);
+    if matches.is_empty() {
+        println!("No rule matches.");
+        return Ok(());
+    }
+    
+    for (index, matched) in matches.iter().enumerate() {
+        let marker = if index == 0 { "→" } else { " " };
+        println!(
+            "\n{} {} ({:.0}%, {})",
+            marker, matched.rule.rule.id, matched.rule.rule.confidence * 100.0, matched.rule.source
+        );
+        println!("    Cause: {}", matched.cause);
+        println!("    Fix:   {}", matched.fix);
+        for link in &matched.links {
+            println!("    Link:  {}", link);
+        }
+        for action in &matched.actions {
+            println!("    Apply: {}", apply::describe(action));
+        }
+    }
+    Ok(())
+}
+
+fn add_rule(rule: rules::Rule, pack: &str) -> Result<()> {
+    rules::validate(&rule)?;
+    let id = rule.id.clone();
+    let path = rules::add_user_rule(rule, pack)?;
+    println!("✓ Saved rule '{}' to {}", id, path.display());
+    Ok(())
+}
+
+fn print_explanation(explanation: &WtfExplanation, cached: bool) {
     let stdout = io::stdout();
     let mut handle = stdout.lock();
     
@@ -173,4 +658,157 @@ fn print_explanation(explanation: &WtfExplanation) {
     writeln!(handle, "Cause: {}", explanation.cause).unwrap();
     writeln!(handle, "Suggested Fix: {}", explanation.suggested_fix).unwrap();
     writeln!(handle, "Confidence: {:.0}%", explanation.confidence * 100.0).unwrap();
+    let cached = if cached { ", cached" } else { "" };
+    writeln!(handle, "Source: {}{} ({})", explanation.source, cached, short_fingerprint(&explanation.fingerprint)).unwrap();
+    if !explanation.actions.is_empty() {
+        writeln!(handle, "Actions:").unwrap();
+        for (index, action) in explanation.actions.iter().enumerate() {
+            writeln!(handle, "  {}. {}", index + 1, apply::describe(action)).unwrap();
+        }
+    }
+}
+
+fn short_fingerprint(fingerprint: &str) -> &str {
+    &fingerprint[..fingerprint.len().min(12)]
+}
+
+#[derive(Tabled)]
+struct HistoryRow {
+    #[tabled(rename = "Fingerprint")]
+    fingerprint: String,
+    #[tabled(rename = "Error")]
+    summary: String,
+    #[tabled(rename = "Source")]
+    source: String,
+    #[tabled(rename = "Confidence")]
+    confidence: String,
+    #[tabled(rename = "Hits")]
+    hits: u32,
+    #[tabled(rename = "Last used")]
+    last_used: String,
+}
+
+fn show_history(limit: usize, app_ctx: &mut AppContext) -> Result<()> {
+    let explanations = app_ctx.state.list_wtf_explanations()
+        .context("Failed to list cached explanations")?;
+    
+    if explanations.is_empty() {
+        println!("No cached explanations.");
+        return Ok(());
+    }
+    
+    let rows: Vec<HistoryRow> = explanations
+        .into_iter()
+        .take(limit)
+        .map(|e| HistoryRow {
+            fingerprint: short_fingerprint(&e.fingerprint).to_string(),
+            summary: truncate(&e.summary, 60),
+            source: e.source,
+            confidence: format!("{:.0}%", e.confidence * 100.0),
+            hits: e.hits,
+            last_used: e.last_used.get(..16).unwrap_or(&e.last_used).replace('T', " "),
+        })
+        .collect();
+    println!("{}", Table::new(rows));
+    Ok(())
+}
+
+#[derive(Tabled)]
+struct AppliedRow {
+    #[tabled(rename = "Fingerprint")]
+    fingerprint: String,
+    #[tabled(rename = "Action")]
+    action: String,
+    #[tabled(rename = "Directory")]
+    directory: String,
+    #[tabled(rename = "Result")]
+    result: String,
+    #[tabled(rename = "Applied")]
+    applied_at: String,
+}
+
+fn show_applied(limit: usize, app_ctx: &mut AppContext) -> Result<()> {
+    let applied = app_ctx.state.list_wtf_applied_actions()
+        .context("Failed to list applied actions")?;
+    
+    if applied.is_empty() {
+        println!("No actions applied yet.");
+        return Ok(());
+    }
+    
+    let rows: Vec<AppliedRow> = applied
+        .into_iter()
+        .take(limit)
+        .map(|a| AppliedRow {
+            fingerprint: short_fingerprint(&a.fingerprint).to_string(),
+            action: truncate(&apply::describe(&a.action), 50),
+            directory: a.directory,
+            result: if a.success { "✓".to_string() } else { "✗ failed".to_string() },
+            applied_at: a.applied_at.get(..16).unwrap_or(&a.applied_at).replace('T', " "),
+        })
+        .collect();
+    println!("{}", Table::new(rows));
+    Ok(())
+}
+
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        text.to_string()
+    } else {
+        format!("{}…", text.chars().take(max - 1).collect::<String>())
+    }
+}
+
+/// Find a cached explanation by fingerprint or unique prefix
+fn find_explanation(prefix: &str, app_ctx: &AppContext) -> Result<WtfExplanation> {
+    let mut matches: Vec<WtfExplanation> = app_ctx.state.list_wtf_explanations()?
+        .into_iter()
+        .filter(|e| e.fingerprint.starts_with(prefix))
+        .collect();
+    
+    match matches.len() {
+        0 => Err(anyhow::anyhow!("No cached explanation matches '{}'", prefix)),
+        1 => Ok(matches.remove(0)),
+        n => Err(anyhow::anyhow!("Fingerprint prefix '{}' is ambiguous ({} matches)", prefix, n)),
+    }
+}
+
+fn forget(prefix: &str, app_ctx: &mut AppContext) -> Result<()> {
+    let explanation = find_explanation(prefix, app_ctx)?;
+    app_ctx.state.delete_wtf_explanation(&explanation.fingerprint)?;
+    println!("✓ Forgot {} ({})", short_fingerprint(&explanation.fingerprint), explanation.summary);
+    Ok(())
+}
+
+/// Confidence change per rating; bad answers drop faster so they expire sooner
+const RATE_UP: f64 = 0.1;
+const RATE_DOWN: f64 = 0.25;
+
+fn rate(rating: Rating, prefix: Option<String>, app_ctx: &mut AppContext) -> Result<()> {
+    let mut explanation = match prefix {
+        Some(prefix) => find_explanation(&prefix, app_ctx)?,
+        None => app_ctx.state.list_wtf_explanations()?
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("No cached explanations to rate"))?,
+    };
+    
+    let before = explanation.confidence;
+    explanation.confidence = match rating {
+        Rating::Up => (before + RATE_UP).min(1.0),
+        Rating::Down => (before - RATE_DOWN).max(0.0),
+    };
+    let fingerprint = short_fingerprint(&explanation.fingerprint).to_string();
+    let confidence = explanation.confidence;
+    app_ctx.state.save_wtf_explanation(explanation)?;
+    
+    println!("✓ Confidence of {}: {:.0}% → {:.0}%", fingerprint, before * 100.0, confidence * 100.0);
+    let config = &app_ctx.config.wtf;
+    if confidence <= config.low_confidence_threshold {
+        println!(
+            "  At or below {:.0}%, so it is regenerated after {} day(s). Use `dev wtf --refresh` to do it now.",
+            config.low_confidence_threshold * 100.0, config.low_confidence_ttl_days
+        );
+    }
+    Ok(())
 }
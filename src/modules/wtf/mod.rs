@@ -1,4 +1,9 @@
+pub mod apply;
 pub mod command;
+pub mod normalize;
+pub mod parsers;
+pub mod project;
+pub mod rules;
 
 pub use command::run;
 
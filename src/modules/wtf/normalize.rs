@@ -0,0 +1,183 @@
+use crate::config::model::NormalizeConfig;
+use anyhow::{Context as AnyhowContext, Result};
+use regex::Regex;
+use std::collections::HashSet;
+
+/// Built-in normalization layers, applied in this order after any user layers.
+/// Each replaces run-specific details with a placeholder so the same error
+/// from another run, machine or directory gets the same fingerprint.
+const LAYERS: &[(&str, &str, &str)] = &[
+    ("ansi", r"\x1b\[[0-9;]*[A-Za-z]", ""),
+    ("uuid", r"(?i)\b[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}\b", "<uuid>"),
+    (
+        "timestamp",
+        r"(?i)\b\d{4}-\d{2}-\d{2}(?:[t ]\d{2}:\d{2}(?::\d{2})?(?:[.,]\d+)?(?:z|[+-]\d{2}:?\d{2})?)?\b|\b\d{2}:\d{2}:\d{2}(?:[.,]\d+)?\b",
+        "<time>",
+    ),
+    ("url", r#"(?i)\bhttps?://[^\s'"<>]+"#, "<url>"),
+    ("windows-path", r#"(?i)\b[a-z]:\\[^\s'"<>|:*?]+"#, "<path>"),
+    ("unix-path", r#"(?:~|\.{1,2})?(?:/[^\s/'"`():,;]+){2,}/?"#, "<path>"),
+    ("file", r#"\b[\w.-]+\.(?:rs|py|js|jsx|ts|tsx|go|java|kt|c|cc|cpp|h|hpp|rb|php|cs|swift)\b"#, "<file>"),
+    ("ip", r"\b\d{1,3}(?:\.\d{1,3}){3}\b|\[[0-9a-fA-F:]*:[0-9a-fA-F:]*\]", "<ip>"),
+    ("port", r"(?i)((?:port|<ip>:|localhost:)\s*)\d{2,5}\b", "${1}<port>"),
+    ("hex", r"(?i)\b0x[0-9a-f]+\b|\b[0-9a-f]{12,}\b", "<hex>"),
+    ("pid", r"(?i)\b(pid|process|thread)([\s:=#]*)\d+", "${1}${2}<n>"),
+    ("line", r":\d+(?::\d+)?\b|(?i)\b(line|column|col)\s+\d+", "${1}<line>"),
+    ("quoted", r#"'[^'\n]{1,120}'|"[^"\n]{1,120}"|`[^`\n]{1,120}`"#, "<quoted>"),
+    ("number", r"\b\d+\b", "<n>"),
+];
+
+/// Layers left out of the fingerprint and only used to find similar errors:
+/// a quoted value is often what the error is about, like a module or symbol name
+const SIMILARITY_ONLY: &[&str] = &["quoted"];
+
+struct Layer {
+    regex: Regex,
+    replacement: String,
+    similarity_only: bool,
+    /// Whether what it replaces is a detail of the error, rather than formatting
+    masks_detail: bool,
+}
+
+/// Turns error text into a stable form for fingerprinting and similarity
+pub struct Normalizer {
+    layers: Vec<Layer>,
+}
+
+impl Normalizer {
+    /// Built-in layers minus `config.disable`, after the user's `config.extra` layers
+    pub fn new(config: &NormalizeConfig) -> Result<Self> {
+        for name in &config.disable {
+            if !LAYERS.iter().any(|(layer, _, _)| layer == name) {
+                let known: Vec<&str> = LAYERS.iter().map(|(layer, _, _)| *layer).collect();
+                return Err(anyhow::anyhow!(
+                    "Unknown normalizer layer '{}' in [wtf.normalize]. Known layers: {}",
+                    name, known.join(", ")
+                ));
+            }
+        }
+
+        let mut layers = Vec::new();
+        for extra in &config.extra {
+            layers.push(Layer {
+                regex: Regex::new(&extra.pattern)
+                    .with_context(|| format!("Invalid [wtf.normalize] pattern '{}'", extra.pattern))?,
+                replacement: extra.replace.clone(),
+                similarity_only: false,
+                masks_detail: true,
+            });
+        }
+        for (name, pattern, replacement) in LAYERS {
+            if config.disable.iter().any(|disabled| disabled == name) {
+                continue;
+            }
+            layers.push(Layer {
+                regex: Regex::new(pattern).expect("built-in normalizer pattern"),
+                replacement: replacement.to_string(),
+                similarity_only: SIMILARITY_ONLY.contains(name),
+                masks_detail: *name != "ansi",
+            });
+        }
+
+        Ok(Normalizer { layers })
+    }
+
+    /// The form that is hashed into the fingerprint
+    pub fn normalize(&self, text: &str) -> String {
+        let mut normalized = text.to_string();
+        for layer in self.layers.iter().filter(|layer| !layer.similarity_only) {
+            normalized = layer.regex.replace_all(&normalized, layer.replacement.as_str()).to_string();
+        }
+        normalized
+            .to_lowercase()
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// The looser form of a `normalize` result that similar errors are compared in
+    pub fn similar_form(&self, normalized: &str) -> String {
+        let mut similar = normalized.to_string();
+        for layer in self.layers.iter().filter(|layer| layer.similarity_only) {
+            similar = layer.regex.replace_all(&similar, layer.replacement.as_str()).to_string();
+        }
+        similar
+    }
+
+    /// The run-specific values the layers mask in `text`, such as quoted names,
+    /// paths, addresses and numbers. Quotes are left off quoted values.
+    pub fn details(&self, text: &str) -> Vec<String> {
+        let mut details: Vec<String> = Vec::new();
+        for layer in self.layers.iter().filter(|layer| layer.masks_detail) {
+            for found in layer.regex.find_iter(text) {
+                let detail = found.as_str().trim_matches(|c| matches!(c, '\'' | '"' | '`')).trim();
+                if detail.len() >= 2 && !details.iter().any(|d| d == detail) {
+                    details.push(detail.to_string());
+                }
+            }
+        }
+        details
+    }
+}
+
+fn tokens(normalized: &str) -> HashSet<&str> {
+    normalized
+        .split(|c: char| !(c.is_alphanumeric() || c == '<' || c == '>' || c == '_'))
+        .filter(|token| !token.is_empty())
+        .collect()
+}
+
+/// Token-set (Jaccard) similarity of two normalized texts, from 0 to 1
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (tokens(a), tokens(b));
+    let union = a.union(&b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(&b).count() as f64 / union as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalizer() -> Normalizer {
+        Normalizer::new(&NormalizeConfig::default()).unwrap()
+    }
+
+    #[test]
+    fn quoted_values_stay_in_the_fingerprint() {
+        let normalizer = normalizer();
+        let requests = normalizer.normalize("ModuleNotFoundError: No module named 'requests'");
+        let numpy = normalizer.normalize("ModuleNotFoundError: No module named 'numpy'");
+        assert_eq!(requests, "modulenotfounderror: no module named 'requests'");
+        assert_ne!(requests, numpy);
+        assert_eq!(normalizer.similar_form(&requests), normalizer.similar_form(&numpy));
+        assert_eq!(normalizer.similar_form(&requests), "modulenotfounderror: no module named <quoted>");
+    }
+
+    #[test]
+    fn run_specific_details_are_masked() {
+        let normalizer = normalizer();
+        assert_eq!(
+            normalizer.normalize("connect ECONNREFUSED 127.0.0.1:5432 from /tmp/tmp.x1/app.js"),
+            normalizer.normalize("connect ECONNREFUSED 10.0.0.7:6543 from /home/dev/src/app.js"),
+        );
+    }
+
+    #[test]
+    fn details_are_the_masked_values() {
+        let details = normalizer().details("No module named 'requests' in /srv/app/main.py at 10.0.0.7");
+        for expected in ["requests", "/srv/app/main.py", "main.py", "10.0.0.7"] {
+            assert!(details.iter().any(|d| d == expected), "{} missing from {:?}", expected, details);
+        }
+    }
+
+    #[test]
+    fn disabled_layers_are_skipped() {
+        let config = NormalizeConfig { disable: vec!["number".to_string()], extra: Vec::new() };
+        let normalizer = Normalizer::new(&config).unwrap();
+        assert_eq!(normalizer.normalize("exit 42"), "exit 42");
+        assert!(Normalizer::new(&NormalizeConfig { disable: vec!["nope".to_string()], extra: Vec::new() }).is_err());
+    }
+}
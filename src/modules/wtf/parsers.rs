@@ -0,0 +1,865 @@
+use regex::Regex;
+use serde_json::Value;
+use std::fmt;
+use std::sync::LazyLock;
+
+/// Tool that produced a diagnostic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tool {
+    Rustc,
+    Tsc,
+    Eslint,
+    Python,
+    Go,
+    Javac,
+    Maven,
+    Gcc,
+    Npm,
+}
+
+impl fmt::Display for Tool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Tool::Rustc => "rustc",
+            Tool::Tsc => "tsc",
+            Tool::Eslint => "eslint",
+            Tool::Python => "python",
+            Tool::Go => "go",
+            Tool::Javac => "javac",
+            Tool::Maven => "maven",
+            Tool::Gcc => "gcc/clang",
+            Tool::Npm => "npm",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Tool {
+    /// Names a rule's `scope` can use to target this tool
+    pub fn scopes(&self) -> &'static [&'static str] {
+        match self {
+            Tool::Rustc => &["rustc", "cargo", "rust"],
+            Tool::Tsc => &["tsc", "typescript"],
+            Tool::Eslint => &["eslint", "javascript", "typescript"],
+            Tool::Python => &["python", "pytest"],
+            Tool::Go => &["go"],
+            Tool::Javac => &["javac", "java"],
+            Tool::Maven => &["maven", "java"],
+            Tool::Gcc => &["gcc", "clang", "c", "c++"],
+            Tool::Npm => &["npm", "node", "javascript"],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// Position of a diagnostic in its file (1-based)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    pub line: u32,
+    pub column: Option<u32>,
+    pub end_line: Option<u32>,
+    pub end_column: Option<u32>,
+}
+
+/// One error or warning extracted from tool output
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub tool: Tool,
+    pub severity: Severity,
+    /// Tool-specific code, e.g. `E0308`, `TS2322`, `ModuleNotFoundError` or `ERESOLVE`
+    pub code: Option<String>,
+    pub message: String,
+    pub file: Option<String>,
+    pub span: Option<Span>,
+    /// The lines of output that belong to this diagnostic
+    pub rendered: String,
+}
+
+impl Diagnostic {
+    fn new(tool: Tool, severity: Severity, message: &str) -> Self {
+        Diagnostic {
+            tool,
+            severity,
+            code: None,
+            message: message.trim().to_string(),
+            file: None,
+            span: None,
+            rendered: String::new(),
+        }
+    }
+
+    fn at(mut self, file: &str, line: Option<u32>, column: Option<u32>) -> Self {
+        self.file = Some(file.to_string());
+        self.span = line.map(|line| Span { line, column, end_line: None, end_column: None });
+        self
+    }
+
+    fn code(mut self, code: Option<&str>) -> Self {
+        self.code = code.map(str::to_string);
+        self
+    }
+
+    fn rendered(mut self, lines: &[&str]) -> Self {
+        self.rendered = lines.join("\n").trim_end().to_string();
+        self
+    }
+
+    /// `file:line:column`, as far as it is known
+    pub fn location(&self) -> Option<String> {
+        let file = self.file.as_ref()?;
+        Some(match &self.span {
+            Some(Span { line, column: Some(column), .. }) => format!("{}:{}:{}", file, line, column),
+            Some(Span { line, .. }) => format!("{}:{}", file, line),
+            None => file.clone(),
+        })
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.tool)?;
+        if let Some(code) = &self.code {
+            write!(f, " {}", code)?;
+        }
+        write!(f, ": {}", self.message)?;
+        if let Some(location) = self.location() {
+            write!(f, " ({})", location)?;
+        }
+        Ok(())
+    }
+}
+
+/// A parser returns its diagnostics with the index of the line each starts on
+type Parser = fn(&[&str]) -> Vec<(usize, Diagnostic)>;
+
+/// Parse tool output into diagnostics, in the order they appear
+pub fn parse(output: &str) -> Vec<Diagnostic> {
+    let output = strip_ansi(output);
+    let lines: Vec<&str> = output.lines().collect();
+
+    let parsers: &[Parser] = &[
+        parse_rustc_json,
+        parse_rustc,
+        parse_tsc,
+        parse_eslint,
+        parse_python,
+        parse_pytest,
+        parse_go,
+        parse_javac,
+        parse_maven,
+        parse_gcc,
+        parse_npm,
+    ];
+
+    let mut found: Vec<(usize, Diagnostic)> = parsers.iter().flat_map(|parse| parse(&lines)).collect();
+    found.sort_by_key(|(index, _)| *index);
+    found.into_iter().map(|(_, diagnostic)| diagnostic).collect()
+}
+
+/// The diagnostic to explain: the first error, or the first warning if there are no errors
+pub fn primary(diagnostics: &[Diagnostic]) -> Option<&Diagnostic> {
+    diagnostics
+        .iter()
+        .find(|d| d.severity == Severity::Error)
+        .or_else(|| diagnostics.first())
+}
+
+fn strip_ansi(text: &str) -> String {
+    static ANSI: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\x1b\[[0-9;]*[A-Za-z]").unwrap());
+    ANSI.replace_all(text, "").to_string()
+}
+
+fn severity(level: &str) -> Severity {
+    if level.to_lowercase().contains("warn") {
+        Severity::Warning
+    } else {
+        Severity::Error
+    }
+}
+
+fn num(s: &str) -> Option<u32> {
+    s.parse().ok()
+}
+
+/// Lines from `start` up to the next blank line (or `max` lines)
+fn block<'a>(lines: &[&'a str], start: usize, max: usize) -> Vec<&'a str> {
+    lines[start..]
+        .iter()
+        .take(max)
+        .enumerate()
+        .take_while(|(i, line)| *i == 0 || !line.trim().is_empty())
+        .map(|(_, line)| *line)
+        .collect()
+}
+
+/// `cargo --message-format=json` and `rustc --error-format=json`
+fn parse_rustc_json(lines: &[&str]) -> Vec<(usize, Diagnostic)> {
+    let mut found = Vec::new();
+
+    for (index, line) in lines.iter().enumerate() {
+        let line = line.trim();
+        if !line.starts_with('{') {
+            continue;
+        }
+        let Ok(value) = serde_json::from_str::<Value>(line) else {
+            continue;
+        };
+        let message = if value["reason"] == "compiler-message" { &value["message"] } else { &value };
+        let (Some(level), Some(text)) = (message["level"].as_str(), message["message"].as_str()) else {
+            continue;
+        };
+        if !matches!(level, "error" | "warning") || text.starts_with("aborting due to") {
+            continue;
+        }
+
+        let mut diagnostic = Diagnostic::new(Tool::Rustc, severity(level), text)
+            .code(message["code"]["code"].as_str());
+        let primary_span = message["spans"]
+            .as_array()
+            .and_then(|spans| spans.iter().find(|span| span["is_primary"] == true));
+        if let Some(span) = primary_span {
+            diagnostic.file = span["file_name"].as_str().map(str::to_string);
+            diagnostic.span = span["line_start"].as_u64().map(|line| Span {
+                line: line as u32,
+                column: span["column_start"].as_u64().map(|c| c as u32),
+                end_line: span["line_end"].as_u64().map(|l| l as u32),
+                end_column: span["column_end"].as_u64().map(|c| c as u32),
+            });
+        }
+        diagnostic.rendered = message["rendered"].as_str().unwrap_or(text).trim_end().to_string();
+        found.push((index, diagnostic));
+    }
+
+    found
+}
+
+/// Human-readable rustc output: `error[E0308]: mismatched types` followed by ` --> file:line:col`
+fn parse_rustc(lines: &[&str]) -> Vec<(usize, Diagnostic)> {
+    static HEADER: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"^(error|warning)(?:\[(E\d{4})\])?: (.+)$").unwrap());
+    static LOCATION: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\s*--> (.+?):(\d+):(\d+)$").unwrap());
+
+    let mut found = Vec::new();
+
+    for (index, line) in lines.iter().enumerate() {
+        let Some(caps) = HEADER.captures(line) else {
+            continue;
+        };
+        let message = &caps[3];
+        // Summary lines printed after the real diagnostics
+        if message.starts_with("aborting due to")
+            || message.starts_with("could not compile")
+            || (message.contains("generated") && message.contains("warning"))
+        {
+            continue;
+        }
+
+        let rendered = block(lines, index, 40);
+        let mut diagnostic = Diagnostic::new(Tool::Rustc, severity(&caps[1]), message)
+            .code(caps.get(2).map(|m| m.as_str()))
+            .rendered(&rendered);
+        if let Some(loc) = rendered.iter().skip(1).find_map(|line| LOCATION.captures(line)) {
+            diagnostic = diagnostic.at(&loc[1], num(&loc[2]), num(&loc[3]));
+        }
+        // Plenty of tools print "error: ..."; only keep it with a rustc code or location
+        if diagnostic.code.is_none() && diagnostic.file.is_none() {
+            continue;
+        }
+        found.push((index, diagnostic));
+    }
+
+    found
+}
+
+/// `file.ts(3,5): error TS2322: ...` and the pretty `file.ts:3:5 - error TS2322: ...`
+fn parse_tsc(lines: &[&str]) -> Vec<(usize, Diagnostic)> {
+    static PLAIN: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"^(\S+\.[cm]?[jt]sx?)\((\d+),(\d+)\): (error|warning) (TS\d+): (.+)$").unwrap()
+    });
+    static PRETTY: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"^(\S+\.[cm]?[jt]sx?):(\d+):(\d+) - (error|warning) (TS\d+): (.+)$").unwrap()
+    });
+
+    lines
+        .iter()
+        .enumerate()
+        .filter_map(|(index, line)| {
+            let caps = PLAIN.captures(line).or_else(|| PRETTY.captures(line))?;
+            let diagnostic = Diagnostic::new(Tool::Tsc, severity(&caps[4]), &caps[6])
+                .code(Some(&caps[5]))
+                .at(&caps[1], num(&caps[2]), num(&caps[3]))
+                .rendered(&block(lines, index, 10));
+            Some((index, diagnostic))
+        })
+        .collect()
+}
+
+/// eslint's default "stylish" format: a file name line followed by indented problems
+fn parse_eslint(lines: &[&str]) -> Vec<(usize, Diagnostic)> {
+    static FILE: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"^(\S.*\.(?:[cm]?[jt]sx?|vue|svelte))$").unwrap());
+    static PROBLEM: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"^\s+(\d+):(\d+)\s+(error|warning)\s+(.+?)(?:\s{2,}(\S+))?$").unwrap());
+
+    let mut found = Vec::new();
+    let mut file: Option<&str> = None;
+
+    for (index, line) in lines.iter().enumerate() {
+        if let Some(caps) = FILE.captures(line) {
+            file = caps.get(1).map(|m| m.as_str());
+            continue;
+        }
+        let Some(file) = file else {
+            continue;
+        };
+        if let Some(caps) = PROBLEM.captures(line) {
+            let diagnostic = Diagnostic::new(Tool::Eslint, severity(&caps[3]), &caps[4])
+                .code(caps.get(5).map(|m| m.as_str()))
+                .at(file, num(&caps[1]), num(&caps[2]))
+                .rendered(&[file, line]);
+            found.push((index, diagnostic));
+        }
+    }
+
+    found
+}
+
+/// Python tracebacks; the location is the innermost frame
+fn parse_python(lines: &[&str]) -> Vec<(usize, Diagnostic)> {
+    static FRAME: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"^\s*File "(.+)", line (\d+)"#).unwrap());
+    static EXCEPTION: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^([A-Za-z_][\w.]*)(?::\s*(.*))?$").unwrap());
+
+    let mut found = Vec::new();
+    let mut index = 0;
+
+    while index < lines.len() {
+        // Syntax errors are reported as a bare frame without a "Traceback" header
+        if !lines[index].starts_with("Traceback (most recent call last)") && !FRAME.is_match(lines[index]) {
+            index += 1;
+            continue;
+        }
+
+        // Frames are indented; the exception is the first line that is not
+        let start = index;
+        let mut location = None;
+        let mut end = None;
+        for (offset, line) in lines[start..].iter().enumerate() {
+            if let Some(caps) = FRAME.captures(line) {
+                location = Some((caps[1].to_string(), num(&caps[2])));
+            } else if offset > 0 && !line.starts_with(' ') {
+                end = EXCEPTION.captures(line).map(|caps| {
+                    (start + offset, caps[1].to_string(), caps.get(2).map_or("", |m| m.as_str()).to_string())
+                });
+                break;
+            }
+        }
+
+        let Some((end_index, exception, message)) = end else {
+            index += 1;
+            continue;
+        };
+
+        let message = if message.is_empty() { exception.clone() } else { message };
+        let mut diagnostic = Diagnostic::new(Tool::Python, Severity::Error, &message)
+            .code(Some(exception.rsplit('.').next().unwrap_or(&exception)))
+            .rendered(&lines[start..=end_index]);
+        if let Some((file, line)) = location {
+            diagnostic = diagnostic.at(&file, line, None);
+        }
+        found.push((start, diagnostic));
+        index = end_index + 1;
+    }
+
+    found
+}
+
+/// pytest failure sections: `E` lines with the message, then `tests/test_x.py:5: AssertionError`
+fn parse_pytest(lines: &[&str]) -> Vec<(usize, Diagnostic)> {
+    static LOCATION: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(\S+\.py):(\d+): ([A-Za-z_][\w.]*)$").unwrap());
+
+    let mut found = Vec::new();
+    let mut section = 0;
+
+    for (index, line) in lines.iter().enumerate() {
+        // Each failure starts with a `____ test_name ____` header
+        if line.starts_with("____") || line.starts_with("====") {
+            section = index;
+            continue;
+        }
+        let Some(caps) = LOCATION.captures(line) else {
+            continue;
+        };
+
+        let exception = &caps[3];
+        let rendered = &lines[section..=index];
+        let message = rendered
+            .iter()
+            .find_map(|line| line.strip_prefix('E').filter(|rest| rest.starts_with(' ')))
+            .map(|message| message.trim())
+            .map(|message| message.strip_prefix(&format!("{}: ", exception)).unwrap_or(message))
+            .unwrap_or(exception);
+        let diagnostic = Diagnostic::new(Tool::Python, Severity::Error, message)
+            .code(Some(exception.rsplit('.').next().unwrap_or(exception)))
+            .at(&caps[1], num(&caps[2]), None)
+            .rendered(rendered);
+        found.push((section, diagnostic));
+    }
+
+    found
+}
+
+/// `./main.go:5:2: undefined: foo`
+fn parse_go(lines: &[&str]) -> Vec<(usize, Diagnostic)> {
+    static LINE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(\S+\.go):(\d+):(?:(\d+):)? (.+)$").unwrap());
+
+    lines
+        .iter()
+        .enumerate()
+        .filter_map(|(index, line)| {
+            let caps = LINE.captures(line)?;
+            let column = caps.get(3).and_then(|m| num(m.as_str()));
+            let diagnostic = Diagnostic::new(Tool::Go, Severity::Error, &caps[4])
+                .at(&caps[1], num(&caps[2]), column)
+                .rendered(&[line]);
+            Some((index, diagnostic))
+        })
+        .collect()
+}
+
+/// `Foo.java:3: error: cannot find symbol`, followed by the source line and details
+fn parse_javac(lines: &[&str]) -> Vec<(usize, Diagnostic)> {
+    static LINE: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"^(\S+\.java):(\d+): (error|warning): (.+)$").unwrap());
+
+    lines
+        .iter()
+        .enumerate()
+        .filter_map(|(index, line)| {
+            let caps = LINE.captures(line)?;
+            let rendered: Vec<&str> = std::iter::once(*line)
+                .chain(lines[index + 1..].iter().take_while(|l| l.starts_with(' ')).copied())
+                .collect();
+            let diagnostic = Diagnostic::new(Tool::Javac, severity(&caps[3]), &caps[4])
+                .at(&caps[1], num(&caps[2]), None)
+                .rendered(&rendered);
+            Some((index, diagnostic))
+        })
+        .collect()
+}
+
+/// `[ERROR] /path/Foo.java:[3,5] cannot find symbol`
+fn parse_maven(lines: &[&str]) -> Vec<(usize, Diagnostic)> {
+    static LINE: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"^\[(ERROR|WARNING)\] (\S+\.(?:java|kt|scala)):\[(\d+),(\d+)\] (.+)$").unwrap()
+    });
+
+    lines
+        .iter()
+        .enumerate()
+        .filter_map(|(index, line)| {
+            let caps = LINE.captures(line)?;
+            let diagnostic = Diagnostic::new(Tool::Maven, severity(&caps[1]), &caps[5])
+                .at(&caps[2], num(&caps[3]), num(&caps[4]))
+                .rendered(&[line]);
+            Some((index, diagnostic))
+        })
+        .collect()
+}
+
+/// `main.c:3:5: error: 'x' undeclared [-Werror=...]`
+fn parse_gcc(lines: &[&str]) -> Vec<(usize, Diagnostic)> {
+    static LINE: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"^(\S+\.(?:c|cc|cpp|cxx|c\+\+|h|hh|hpp|hxx|m|mm)):(\d+):(?:(\d+):)? (fatal error|error|warning): (.+?)(?: \[(-W[^\]]+)\])?$")
+            .unwrap()
+    });
+
+    lines
+        .iter()
+        .enumerate()
+        .filter_map(|(index, line)| {
+            let caps = LINE.captures(line)?;
+            let column = caps.get(3).and_then(|m| num(m.as_str()));
+            let diagnostic = Diagnostic::new(Tool::Gcc, severity(&caps[4]), &caps[5])
+                .code(caps.get(6).map(|m| m.as_str()))
+                .at(&caps[1], num(&caps[2]), column)
+                .rendered(&block(lines, index, 6));
+            Some((index, diagnostic))
+        })
+        .collect()
+}
+
+/// `npm ERR!` (npm < 10) and `npm error` blocks; one diagnostic per block
+fn parse_npm(lines: &[&str]) -> Vec<(usize, Diagnostic)> {
+    fn strip(line: &str) -> Option<&str> {
+        line.strip_prefix("npm ERR!").or_else(|| line.strip_prefix("npm error")).map(str::trim)
+    }
+
+    let Some(start) = lines.iter().position(|line| strip(line).is_some()) else {
+        return Vec::new();
+    };
+    let block: Vec<&str> = lines[start..].iter().filter(|line| strip(line).is_some()).copied().collect();
+
+    let mut code = None;
+    let mut message = None;
+    for content in block.iter().filter_map(|line| strip(line)) {
+        if let Some(value) = content.strip_prefix("code ") {
+            code.get_or_insert(value.trim());
+        } else if content.is_empty()
+            || content.starts_with("errno")
+            || content.starts_with("path ")
+            || content.starts_with("syscall ")
+            || content.starts_with("A complete log")
+            || content.contains("/_logs/")
+        {
+            continue;
+        } else if message.is_none() {
+            message = Some(content);
+        }
+    }
+
+    let diagnostic = Diagnostic::new(Tool::Npm, Severity::Error, message.or(code).unwrap_or("npm failed"))
+        .code(code)
+        .rendered(&block[..block.len().min(30)]);
+    vec![(start, diagnostic)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn single(output: &str) -> Diagnostic {
+        let diagnostics = parse(output);
+        assert_eq!(diagnostics.len(), 1, "expected one diagnostic, got {:#?}", diagnostics);
+        diagnostics.into_iter().next().unwrap()
+    }
+
+    #[test]
+    fn rustc_human() {
+        let output = r#"   Compiling demo v0.1.0 (/home/dev/demo)
+warning: unused variable: `y`
+ --> src/main.rs:3:9
+  |
+3 |     let y = 1;
+  |         ^ help: if this is intentional, prefix it with an underscore: `_y`
+  |
+  = note: `#[warn(unused_variables)]` on by default
+
+error[E0308]: mismatched types
+ --> src/main.rs:2:18
+  |
+2 |     let x: i32 = "hello";
+  |            ---   ^^^^^^^ expected `i32`, found `&str`
+  |            |
+  |            expected due to this
+
+For more information about this error, try `rustc --explain E0308`.
+warning: `demo` (bin "demo") generated 1 warning
+error: could not compile `demo` (bin "demo") due to 1 previous error; 1 warning emitted
+"#;
+        let diagnostics = parse(output);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert_eq!(diagnostics[0].location().as_deref(), Some("src/main.rs:3:9"));
+
+        let error = primary(&diagnostics).unwrap();
+        assert_eq!(error.tool, Tool::Rustc);
+        assert_eq!(error.code.as_deref(), Some("E0308"));
+        assert_eq!(error.message, "mismatched types");
+        assert_eq!(error.location().as_deref(), Some("src/main.rs:2:18"));
+        assert!(error.rendered.ends_with("expected due to this"));
+    }
+
+    #[test]
+    fn rustc_json() {
+        let output = concat!(
+            r#"{"reason":"compiler-artifact","package_id":"libc 0.2.150","target":{"kind":["lib"],"name":"libc"},"fresh":true}"#,
+            "\n",
+            r#"{"reason":"compiler-message","package_id":"demo 0.1.0 (path+file:///home/dev/demo)","manifest_path":"/home/dev/demo/Cargo.toml","target":{"kind":["bin"],"name":"demo"},"message":{"rendered":"error[E0425]: cannot find value `y` in this scope\n --> src/main.rs:3:13\n  |\n3 |     let z = y + 1;\n  |             ^ not found in this scope\n\n","$message_type":"diagnostic","children":[],"code":{"code":"E0425","explanation":null},"level":"error","message":"cannot find value `y` in this scope","spans":[{"byte_end":40,"byte_start":39,"column_end":14,"column_start":13,"expansion":null,"file_name":"src/main.rs","is_primary":true,"label":"not found in this scope","line_end":3,"line_start":3,"suggested_replacement":null,"suggestion_applicability":null,"text":[]}]}}"#,
+            "\n",
+            r#"{"reason":"compiler-message","package_id":"demo 0.1.0 (path+file:///home/dev/demo)","manifest_path":"/home/dev/demo/Cargo.toml","target":{"kind":["bin"],"name":"demo"},"message":{"rendered":"error: aborting due to 1 previous error\n\n","$message_type":"diagnostic","children":[],"code":null,"level":"error","message":"aborting due to 1 previous error","spans":[]}}"#,
+            "\n",
+            r#"{"reason":"build-finished","success":false}"#,
+            "\n",
+        );
+        let diagnostic = single(output);
+        assert_eq!(diagnostic.tool, Tool::Rustc);
+        assert_eq!(diagnostic.code.as_deref(), Some("E0425"));
+        assert_eq!(diagnostic.message, "cannot find value `y` in this scope");
+        assert_eq!(
+            diagnostic.span,
+            Some(Span { line: 3, column: Some(13), end_line: Some(3), end_column: Some(14) })
+        );
+        assert!(diagnostic.rendered.starts_with("error[E0425]"));
+    }
+
+    #[test]
+    fn plain_error_lines_are_not_rustc() {
+        assert!(parse("error: failed to push some refs to 'origin'\n").is_empty());
+    }
+
+    #[test]
+    fn tsc_plain() {
+        let output = "src/index.ts(3,7): error TS2322: Type 'string' is not assignable to type 'number'.\n\
+                      src/util.ts(10,1): error TS2304: Cannot find name 'foo'.\n";
+        let diagnostics = parse(output);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].tool, Tool::Tsc);
+        assert_eq!(diagnostics[0].code.as_deref(), Some("TS2322"));
+        assert_eq!(diagnostics[0].message, "Type 'string' is not assignable to type 'number'.");
+        assert_eq!(diagnostics[0].location().as_deref(), Some("src/index.ts:3:7"));
+        assert_eq!(diagnostics[1].location().as_deref(), Some("src/util.ts:10:1"));
+    }
+
+    #[test]
+    fn tsc_pretty_with_colours() {
+        let output = "\x1b[96msrc/index.ts\x1b[0m:\x1b[93m3\x1b[0m:\x1b[93m7\x1b[0m - \x1b[91merror\x1b[0m\x1b[90m TS2322: \x1b[0mType 'string' is not assignable to type 'number'.\n\
+                      \n\
+                      \x1b[7m3\x1b[0m const n: number = \"x\";\n\
+                      \n\
+                      Found 1 error in src/index.ts\x1b[90m:3\x1b[0m\n";
+        let diagnostic = single(output);
+        assert_eq!(diagnostic.code.as_deref(), Some("TS2322"));
+        assert_eq!(diagnostic.location().as_deref(), Some("src/index.ts:3:7"));
+        assert!(!diagnostic.rendered.contains('\x1b'));
+    }
+
+    #[test]
+    fn eslint_stylish() {
+        let output = r#"
+/home/dev/app/src/App.jsx
+   3:10  error    'useState' is defined but never used  no-unused-vars
+  12:5   warning  Unexpected console statement          no-console
+
+✖ 2 problems (1 error, 1 warning)
+"#;
+        let diagnostics = parse(output);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].tool, Tool::Eslint);
+        assert_eq!(diagnostics[0].message, "'useState' is defined but never used");
+        assert_eq!(diagnostics[0].code.as_deref(), Some("no-unused-vars"));
+        assert_eq!(diagnostics[0].location().as_deref(), Some("/home/dev/app/src/App.jsx:3:10"));
+        assert_eq!(diagnostics[1].severity, Severity::Warning);
+        assert_eq!(diagnostics[1].code.as_deref(), Some("no-console"));
+    }
+
+    #[test]
+    fn python_traceback() {
+        let output = r#"Traceback (most recent call last):
+  File "/home/dev/app/main.py", line 10, in <module>
+    main()
+  File "/home/dev/app/main.py", line 7, in main
+    import requests
+ModuleNotFoundError: No module named 'requests'
+"#;
+        let diagnostic = single(output);
+        assert_eq!(diagnostic.tool, Tool::Python);
+        assert_eq!(diagnostic.code.as_deref(), Some("ModuleNotFoundError"));
+        assert_eq!(diagnostic.message, "No module named 'requests'");
+        assert_eq!(diagnostic.location().as_deref(), Some("/home/dev/app/main.py:7"));
+    }
+
+    #[test]
+    fn python_syntax_error() {
+        let output = r#"  File "/home/dev/app/main.py", line 3
+    print("hi"
+         ^
+SyntaxError: '(' was never closed
+"#;
+        let diagnostic = single(output);
+        assert_eq!(diagnostic.code.as_deref(), Some("SyntaxError"));
+        assert_eq!(diagnostic.message, "'(' was never closed");
+        assert_eq!(diagnostic.location().as_deref(), Some("/home/dev/app/main.py:3"));
+    }
+
+    #[test]
+    fn pytest_failures() {
+        let output = r#"============================= test session starts ==============================
+platform linux -- Python 3.11.4, pytest-7.4.0, pluggy-1.2.0
+rootdir: /home/dev/app
+collected 2 items
+
+tests/test_math.py FF                                                    [100%]
+
+=================================== FAILURES ===================================
+___________________________________ test_add ___________________________________
+
+    def test_add():
+>       assert add(1, 2) == 4
+E       assert 3 == 4
+E        +  where 3 = add(1, 2)
+
+tests/test_math.py:5: AssertionError
+__________________________________ test_load ___________________________________
+
+    def test_load():
+>       load("missing.json")
+
+tests/test_math.py:9: 
+_ _ _ _ _ _ _ _ _ _ _ _ _ _ _ _ _ _ _ _ _ _ _ _ _ _ _ _ _ _ _ _ _ _ _ _ _ _ _ _ 
+
+path = 'missing.json'
+
+    def load(path):
+>       with open(path) as f:
+E       FileNotFoundError: [Errno 2] No such file or directory: 'missing.json'
+
+app/io.py:3: FileNotFoundError
+=========================== short test summary info ============================
+FAILED tests/test_math.py::test_add - assert 3 == 4
+FAILED tests/test_math.py::test_load - FileNotFoundError: [Errno 2] No such file or directory: 'missing.json'
+============================== 2 failed in 0.03s ===============================
+"#;
+        let diagnostics = parse(output);
+        assert_eq!(diagnostics.len(), 2);
+
+        assert_eq!(diagnostics[0].tool, Tool::Python);
+        assert_eq!(diagnostics[0].code.as_deref(), Some("AssertionError"));
+        assert_eq!(diagnostics[0].message, "assert 3 == 4");
+        assert_eq!(diagnostics[0].location().as_deref(), Some("tests/test_math.py:5"));
+        assert!(diagnostics[0].rendered.starts_with("____"));
+
+        assert_eq!(diagnostics[1].code.as_deref(), Some("FileNotFoundError"));
+        assert_eq!(diagnostics[1].message, "[Errno 2] No such file or directory: 'missing.json'");
+        assert_eq!(diagnostics[1].location().as_deref(), Some("app/io.py:3"));
+    }
+
+    #[test]
+    fn go_build() {
+        let output = r#"# example.com/demo
+./main.go:5:2: undefined: foo
+./main.go:8:9: cannot use "x" (untyped string constant) as int value in return statement
+"#;
+        let diagnostics = parse(output);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].tool, Tool::Go);
+        assert_eq!(diagnostics[0].message, "undefined: foo");
+        assert_eq!(diagnostics[0].location().as_deref(), Some("./main.go:5:2"));
+        assert_eq!(diagnostics[1].location().as_deref(), Some("./main.go:8:9"));
+    }
+
+    #[test]
+    fn go_vet_without_column() {
+        let diagnostic = single("./handler.go:14: unreachable code\n");
+        assert_eq!(diagnostic.location().as_deref(), Some("./handler.go:14"));
+    }
+
+    #[test]
+    fn javac() {
+        let output = r#"Main.java:5: error: cannot find symbol
+        System.out.println(x);
+                           ^
+  symbol:   variable x
+  location: class Main
+1 error
+"#;
+        let diagnostic = single(output);
+        assert_eq!(diagnostic.tool, Tool::Javac);
+        assert_eq!(diagnostic.message, "cannot find symbol");
+        assert_eq!(diagnostic.location().as_deref(), Some("Main.java:5"));
+        assert!(diagnostic.rendered.ends_with("location: class Main"));
+    }
+
+    #[test]
+    fn maven() {
+        let output = r#"[INFO] -------------------------------------------------------------
+[ERROR] COMPILATION ERROR : 
+[INFO] -------------------------------------------------------------
+[ERROR] /home/dev/app/src/main/java/com/example/App.java:[5,28] cannot find symbol
+  symbol:   class Strin
+  location: class com.example.App
+[INFO] 1 error
+[INFO] BUILD FAILURE
+"#;
+        let diagnostic = single(output);
+        assert_eq!(diagnostic.tool, Tool::Maven);
+        assert_eq!(diagnostic.message, "cannot find symbol");
+        assert_eq!(
+            diagnostic.location().as_deref(),
+            Some("/home/dev/app/src/main/java/com/example/App.java:5:28")
+        );
+    }
+
+    #[test]
+    fn gcc() {
+        let output = r#"main.c: In function 'main':
+main.c:4:5: error: 'x' undeclared (first use in this function)
+    4 |     x = 1;
+      |     ^
+main.c:4:5: note: each undeclared identifier is reported only once for each function it appears in
+main.c:6:12: warning: unused variable 'y' [-Wunused-variable]
+    6 |     int y;
+      |         ^
+"#;
+        let diagnostics = parse(output);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].tool, Tool::Gcc);
+        assert_eq!(diagnostics[0].message, "'x' undeclared (first use in this function)");
+        assert_eq!(diagnostics[0].location().as_deref(), Some("main.c:4:5"));
+        assert_eq!(diagnostics[1].severity, Severity::Warning);
+        assert_eq!(diagnostics[1].code.as_deref(), Some("-Wunused-variable"));
+        assert_eq!(diagnostics[1].message, "unused variable 'y'");
+    }
+
+    #[test]
+    fn clang_fatal_error() {
+        let output = "src/main.cpp:3:10: fatal error: 'foo.h' file not found\n\
+                      #include \"foo.h\"\n\
+                      \x20        ^~~~~~~\n\
+                      1 error generated.\n";
+        let diagnostic = single(output);
+        assert_eq!(diagnostic.severity, Severity::Error);
+        assert_eq!(diagnostic.message, "'foo.h' file not found");
+        assert_eq!(diagnostic.location().as_deref(), Some("src/main.cpp:3:10"));
+    }
+
+    #[test]
+    fn npm_error() {
+        let output = r#"npm error code ERESOLVE
+npm error ERESOLVE unable to resolve dependency tree
+npm error
+npm error While resolving: app@1.0.0
+npm error Found: react@18.2.0
+npm error A complete log of this run can be found in: /home/dev/.npm/_logs/2024-05-01T10_00_00_000Z-debug-0.log
+"#;
+        let diagnostic = single(output);
+        assert_eq!(diagnostic.tool, Tool::Npm);
+        assert_eq!(diagnostic.code.as_deref(), Some("ERESOLVE"));
+        assert_eq!(diagnostic.message, "ERESOLVE unable to resolve dependency tree");
+    }
+
+    #[test]
+    fn npm_err_legacy() {
+        let output = r#"npm ERR! code ENOENT
+npm ERR! syscall open
+npm ERR! path /home/dev/app/package.json
+npm ERR! errno -2
+npm ERR! enoent ENOENT: no such file or directory, open '/home/dev/app/package.json'
+npm ERR! enoent This is related to npm not being able to find a file.
+
+npm ERR! A complete log of this run can be found in:
+npm ERR!     /home/dev/.npm/_logs/2024-05-01T10_00_00_000Z-debug-0.log
+"#;
+        let diagnostic = single(output);
+        assert_eq!(diagnostic.code.as_deref(), Some("ENOENT"));
+        assert_eq!(
+            diagnostic.message,
+            "enoent ENOENT: no such file or directory, open '/home/dev/app/package.json'"
+        );
+        assert!(!diagnostic.rendered.is_empty());
+    }
+
+    #[test]
+    fn diagnostics_keep_output_order() {
+        let output = "main.c:6:12: warning: unused variable 'y' [-Wunused-variable]\n\
+                      \n\
+                      ./main.go:5:2: undefined: foo\n";
+        let diagnostics = parse(output);
+        assert_eq!(diagnostics.iter().map(|d| d.tool).collect::<Vec<_>>(), vec![Tool::Gcc, Tool::Go]);
+        assert_eq!(primary(&diagnostics).unwrap().tool, Tool::Go);
+    }
+}
@@ -0,0 +1,82 @@
+use crate::modules::ctx::command::detect_project;
+use crate::modules::wtf::parsers::Diagnostic;
+use crate::utils::redact::is_env_file;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Lines of each manifest included in the prompt
+const MANIFEST_LINES: usize = 40;
+
+/// Lock files are long and rarely explain an error
+const LOCK_FILES: &[&str] = &["Cargo.lock", "yarn.lock", "pnpm-lock.yaml", "package-lock.json", "go.sum"];
+
+/// Project type, package manager, manifest excerpts and the source around the
+/// error, as a block for the LLM prompt. Empty when nothing was found.
+pub fn project_context(dir: &Path, diagnostic: Option<&Diagnostic>, context_lines: usize) -> String {
+    let mut sections = Vec::new();
+
+    if let Ok((project_type, dependencies, package_manager)) = detect_project(dir) {
+        if let Some(project_type) = project_type {
+            sections.push(format!("Project type: {:?}", project_type));
+        }
+        if let Some(package_manager) = package_manager {
+            sections.push(format!("Package manager: {}", package_manager));
+        }
+
+        let mut manifests: Vec<&String> = dependencies
+            .values()
+            .filter(|file| !LOCK_FILES.contains(&file.as_str()))
+            .collect();
+        manifests.sort();
+        for manifest in manifests {
+            if let Ok(content) = fs::read_to_string(dir.join(manifest)) {
+                let excerpt: Vec<&str> = content.lines().take(MANIFEST_LINES).collect();
+                sections.push(format!("{}:\n```\n{}\n```", manifest, excerpt.join("\n")));
+            }
+        }
+    }
+
+    if context_lines > 0 {
+        if let Some(window) = diagnostic.and_then(|d| source_window(dir, d, context_lines)) {
+            sections.push(window);
+        }
+    }
+
+    sections.join("\n\n")
+}
+
+/// `context_lines` lines either side of the diagnostic's line, if its file is inside `dir`
+fn source_window(dir: &Path, diagnostic: &Diagnostic, context_lines: usize) -> Option<String> {
+    let file = diagnostic.file.as_deref()?;
+    let line = diagnostic.span.as_ref()?.line as usize;
+    let path = resolve_in(dir, file)?;
+    if is_env_file(&path) {
+        return None;
+    }
+
+    let content = fs::read_to_string(&path).ok()?;
+    let lines: Vec<&str> = content.lines().collect();
+    if line == 0 || line > lines.len() {
+        return None;
+    }
+
+    let start = line.saturating_sub(context_lines).max(1);
+    let end = (line + context_lines).min(lines.len());
+    let width = end.to_string().len();
+    let window: Vec<String> = (start..=end)
+        .map(|n| {
+            let marker = if n == line { ">" } else { " " };
+            format!("{} {:>width$} | {}", marker, n, lines[n - 1], width = width)
+        })
+        .collect();
+
+    Some(format!("Source around {}:{}:\n```\n{}\n```", file, line, window.join("\n")))
+}
+
+/// The file's path if it exists inside `dir`; errors elsewhere (dependencies, the
+/// standard library) are left out
+fn resolve_in(dir: &Path, file: &str) -> Option<PathBuf> {
+    let dir = dir.canonicalize().ok()?;
+    let path = dir.join(file).canonicalize().ok()?;
+    (path.starts_with(&dir) && path.is_file()).then_some(path)
+}
@@ -0,0 +1,365 @@
+use crate::modules::snip::template::Template;
+use crate::modules::wtf::parsers::Diagnostic;
+use crate::platform::Shell;
+use crate::state::store::WtfAction;
+use anyhow::{Context as AnyhowContext, Result};
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+/// Rule packs shipped with dev
+const BUNDLED: &[(&str, &str)] = &[
+    ("general.toml", include_str!("rules/general.toml")),
+    ("rust.toml", include_str!("rules/rust.toml")),
+    ("python.toml", include_str!("rules/python.toml")),
+    ("javascript.toml", include_str!("rules/javascript.toml")),
+    ("go.toml", include_str!("rules/go.toml")),
+    ("c.toml", include_str!("rules/c.toml")),
+    ("java.toml", include_str!("rules/java.toml")),
+];
+
+/// A file of rules, in TOML (`[[rule]]` tables) or YAML (a `rule:` list)
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RulePack {
+    #[serde(default, rename = "rule")]
+    pub rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rule {
+    pub id: String,
+    /// Regex matched against the primary diagnostic, or the raw output if nothing was parsed
+    pub pattern: String,
+    /// Tool or language the rule applies to, e.g. "rust", "tsc" or "python"; any if unset.
+    /// Only checked against parsed diagnostics, so it never hides a match on raw output
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    /// Cause and fix may use named captures of the pattern as `{name}`
+    pub cause: String,
+    pub fix: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub links: Vec<String>,
+    /// Commands or patches `dev wtf --apply` can run. Commands are templates like
+    /// cause and fix; patches are used as written
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub actions: Vec<WtfAction>,
+    #[serde(default = "default_confidence")]
+    pub confidence: f64,
+}
+
+fn default_confidence() -> f64 {
+    0.5
+}
+
+/// `{name}` in cause, fix and link templates
+static PLACEHOLDER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\{(\w+)\}").unwrap());
+
+/// A rule ready to match, with the pack it came from
+pub struct LoadedRule {
+    pub rule: Rule,
+    pub regex: Regex,
+    pub source: String,
+}
+
+/// Outcome of applying the best matching rule
+pub struct RuleMatch<'a> {
+    pub rule: &'a LoadedRule,
+    pub cause: String,
+    pub fix: String,
+    pub links: Vec<String>,
+    pub actions: Vec<WtfAction>,
+}
+
+/// Directory for user rule packs (`~/.dev/wtf-rules`)
+pub fn user_rules_dir() -> Result<PathBuf> {
+    Ok(crate::platform::dev_home()?.join("wtf-rules"))
+}
+
+/// Load the bundled packs, then the user's. A user rule replaces a bundled rule with the same id.
+pub fn load_rules() -> Result<Vec<LoadedRule>> {
+    let mut rules: Vec<LoadedRule> = Vec::new();
+
+    for (name, content) in BUNDLED {
+        let pack: RulePack = toml::from_str(content)
+            .with_context(|| format!("Failed to parse bundled rule pack {}", name))?;
+        add_pack(&mut rules, pack, &format!("bundled/{}", name));
+    }
+
+    let dir = user_rules_dir()?;
+    if let Ok(entries) = fs::read_dir(&dir) {
+        let mut paths: Vec<PathBuf> = entries.filter_map(|e| e.ok().map(|e| e.path())).collect();
+        paths.sort();
+        for path in paths {
+            match load_pack(&path) {
+                Ok(Some(pack)) => add_pack(&mut rules, pack, &path.to_string_lossy()),
+                Ok(None) => {}
+                Err(e) => eprintln!("⚠️  Skipping rule pack {:?}: {:#}", path, e),
+            }
+        }
+    }
+
+    Ok(rules)
+}
+
+/// Parse a rule pack file; files that are not TOML or YAML are ignored
+pub fn load_pack(path: &Path) -> Result<Option<RulePack>> {
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    if !matches!(extension, "toml" | "yaml" | "yml") {
+        return Ok(None);
+    }
+
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {:?}", path))?;
+    let pack = if extension == "toml" {
+        toml::from_str(&content).with_context(|| format!("Failed to parse {:?}", path))?
+    } else {
+        serde_norway::from_str(&content).with_context(|| format!("Failed to parse {:?}", path))?
+    };
+    Ok(Some(pack))
+}
+
+fn add_pack(rules: &mut Vec<LoadedRule>, pack: RulePack, source: &str) {
+    for rule in pack.rules {
+        let regex = match Regex::new(&rule.pattern) {
+            Ok(regex) => regex,
+            Err(e) => {
+                eprintln!("⚠️  Skipping rule '{}' in {}: invalid pattern: {}", rule.id, source, e);
+                continue;
+            }
+        };
+        rules.retain(|loaded| loaded.rule.id != rule.id);
+        rules.push(LoadedRule { rule, regex, source: source.to_string() });
+    }
+}
+
+/// Check a rule before it is saved: the pattern compiles and templates only use its captures
+pub fn validate(rule: &Rule) -> Result<()> {
+    let regex = Regex::new(&rule.pattern)
+        .with_context(|| format!("Invalid pattern for rule '{}'", rule.id))?;
+    if !(0.0..=1.0).contains(&rule.confidence) {
+        return Err(anyhow::anyhow!("Confidence must be between 0 and 1"));
+    }
+
+    let names: Vec<&str> = regex.capture_names().flatten().collect();
+    let templates = [rule.cause.as_str(), rule.fix.as_str()]
+        .into_iter()
+        .chain(rule.links.iter().map(String::as_str))
+        .chain(rule.actions.iter().filter_map(|action| match action {
+            WtfAction::Command { command } => Some(command.as_str()),
+            WtfAction::Patch { .. } => None,
+        }));
+    for template in templates {
+        for caps in PLACEHOLDER.captures_iter(template) {
+            if !names.contains(&&caps[1]) {
+                return Err(anyhow::anyhow!(
+                    "Template uses {{{}}}, but the pattern has no capture named '{}'",
+                    &caps[1], &caps[1]
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+impl LoadedRule {
+    pub fn is_bundled(&self) -> bool {
+        self.source.starts_with("bundled/")
+    }
+
+    /// Whether the rule's scope allows this diagnostic. Unscoped rules apply to
+    /// everything, and scoped ones to the raw output when no parser recognised it.
+    pub fn applies_to(&self, diagnostic: Option<&Diagnostic>) -> bool {
+        match (&self.rule.scope, diagnostic) {
+            (None, _) | (Some(_), None) => true,
+            (Some(scope), Some(diagnostic)) => diagnostic.tool.scopes().contains(&scope.to_lowercase().as_str()),
+        }
+    }
+}
+
+/// All rules that match, best first: highest confidence, then user rules before
+/// bundled ones, then the order they were loaded in
+pub fn matching<'a>(rules: &'a [LoadedRule], text: &str, diagnostic: Option<&Diagnostic>) -> Vec<RuleMatch<'a>> {
+    let mut matches: Vec<(usize, RuleMatch)> = rules
+        .iter()
+        .enumerate()
+        .filter(|(_, rule)| rule.applies_to(diagnostic))
+        .filter_map(|(index, rule)| {
+            let caps = rule.regex.captures(text)?;
+            Some((index, RuleMatch {
+                rule,
+                cause: render(&rule.rule.cause, &caps),
+                fix: render(&rule.rule.fix, &caps),
+                links: rule.rule.links.iter().map(|link| render(link, &caps)).collect(),
+                actions: rule.rule.actions.iter().filter_map(|action| match action {
+                    WtfAction::Command { command } => {
+                        render_command(command, &caps).map(|command| WtfAction::Command { command })
+                    }
+                    patch => Some(patch.clone()),
+                }).collect(),
+            }))
+        })
+        .collect();
+
+    matches.sort_by(|(a_index, a), (b_index, b)| {
+        b.rule.rule.confidence
+            .total_cmp(&a.rule.rule.confidence)
+            .then(a.rule.is_bundled().cmp(&b.rule.is_bundled()))
+            .then(a_index.cmp(b_index))
+    });
+    matches.into_iter().map(|(_, m)| m).collect()
+}
+
+/// Fill `{name}` placeholders from named captures, as they are, for cause, fix and links
+fn render(template: &str, caps: &Captures) -> String {
+    PLACEHOLDER
+        .replace_all(template, |p: &Captures| {
+            caps.name(&p[1]).map_or("", |m| m.as_str()).to_string()
+        })
+        .to_string()
+}
+
+/// Fill `{name}` placeholders in an action command, quoting each capture so the
+/// shell `dev wtf --apply` runs it in reads it as one word, however it is written
+fn render_command(command: &str, caps: &Captures) -> Option<String> {
+    let shell = if cfg!(windows) { Shell::PowerShell } else { Shell::Bash };
+    let literal = |text: &str| text.replace('{', "{{").replace('}', "}}");
+
+    // Only `{name}` is a placeholder in rules; other braces are literal
+    let mut text = String::new();
+    let mut values = HashMap::new();
+    let mut last = 0;
+    for placeholder in PLACEHOLDER.captures_iter(command) {
+        let whole = placeholder.get(0)?;
+        text.push_str(&literal(&command[last..whole.start()]));
+        text.push_str(whole.as_str());
+        let value = caps.name(&placeholder[1]).map_or("", |m| m.as_str());
+        values.insert(placeholder[1].to_string(), value.to_string());
+        last = whole.end();
+    }
+    text.push_str(&literal(&command[last..]));
+
+    match Template::parse(&text, shell) {
+        Ok(template) => Some(template.fill(&values, shell)),
+        Err(e) => {
+            log::debug!("Skipping action `{}`: {:#}", command, e);
+            None
+        }
+    }
+}
+
+/// Append a rule to a user pack (`~/.dev/wtf-rules/<pack>.toml`), replacing one with the same id
+pub fn add_user_rule(rule: Rule, pack: &str) -> Result<PathBuf> {
+    let dir = user_rules_dir()?;
+    fs::create_dir_all(&dir)
+        .with_context(|| format!("Failed to create {:?}", dir))?;
+    let path = dir.join(format!("{}.toml", pack));
+
+    let mut rules = if path.exists() { load_pack(&path)?.unwrap_or_default() } else { RulePack::default() };
+    rules.rules.retain(|r| r.id != rule.id);
+    rules.rules.push(rule);
+
+    fs::write(&path, toml::to_string_pretty(&rules)?)
+        .with_context(|| format!("Failed to write {:?}", path))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::wtf::parsers;
+
+    fn loaded(pack: RulePack) -> Vec<LoadedRule> {
+        let mut rules = Vec::new();
+        add_pack(&mut rules, pack, "test.yaml");
+        rules
+    }
+
+    fn rule(pattern: &str, command: &str) -> Vec<LoadedRule> {
+        loaded(RulePack {
+            rules: vec![Rule {
+                id: "test".to_string(),
+                pattern: pattern.to_string(),
+                scope: None,
+                cause: "Missing {name}".to_string(),
+                fix: "Install {name}".to_string(),
+                links: Vec::new(),
+                actions: vec![WtfAction::Command { command: command.to_string() }],
+                confidence: 0.5,
+            }],
+        })
+    }
+
+    fn command(matched: &RuleMatch) -> String {
+        match &matched.actions[0] {
+            WtfAction::Command { command } => command.clone(),
+            WtfAction::Patch { .. } => unreachable!(),
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn captures_are_quoted_in_commands_only() {
+        let rules = rule(r"No module named '(?P<name>[^']+)'", "pip install {name}");
+
+        let matches = matching(&rules, "No module named 'requests'", None);
+        assert_eq!(command(&matches[0]), "pip install requests");
+
+        let matches = matching(&rules, "No module named 'x; rm -rf ~'", None);
+        assert_eq!(command(&matches[0]), "pip install 'x; rm -rf ~'");
+        assert_eq!(matches[0].cause, "Missing x; rm -rf ~");
+
+        let rules = rule(r"No module named '(?P<name>[^']+)'", r#"echo "{name}" && awk '{print $1}' log"#);
+        let matches = matching(&rules, "No module named '$(id)`id`'", None);
+        assert_eq!(command(&matches[0]), r#"echo "\$(id)\`id\`" && awk '{print $1}' log"#);
+    }
+
+    #[test]
+    fn yaml_packs_load() {
+        let pack: RulePack = serde_norway::from_str(
+            r#"
+rule:
+  - id: vpn-down
+    scope: go
+    pattern: 'dial tcp (?P<host>\S+): i/o timeout'
+    cause: Can't reach {host}
+    fix: Connect to the VPN
+    confidence: 0.8
+"#,
+        )
+        .unwrap();
+        assert_eq!(pack.rules.len(), 1);
+        assert_eq!(pack.rules[0].scope.as_deref(), Some("go"));
+        assert_eq!(pack.rules[0].confidence, 0.8);
+        validate(&pack.rules[0]).unwrap();
+    }
+
+    #[test]
+    fn scoped_rules_match_raw_output_when_nothing_was_parsed() {
+        let rules = loaded(RulePack {
+            rules: vec![Rule {
+                id: "vpn-down".to_string(),
+                pattern: r"dial tcp (?P<host>\S+): i/o timeout".to_string(),
+                scope: Some("go".to_string()),
+                cause: "Can't reach {host}".to_string(),
+                fix: "Connect to the VPN".to_string(),
+                links: Vec::new(),
+                actions: Vec::new(),
+                confidence: 0.8,
+            }],
+        });
+
+        let raw = "Get \"https://proxy.corp/mod\": dial tcp 10.0.0.1:443: i/o timeout";
+        let matches = matching(&rules, raw, None);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].cause, "Can't reach 10.0.0.1:443");
+
+        // A diagnostic from another tool still rules it out
+        let diagnostics = parsers::parse("main.c:1:1: error: dial tcp 10.0.0.1:443: i/o timeout\n");
+        let diagnostic = parsers::primary(&diagnostics);
+        assert!(diagnostic.is_some());
+        assert!(matching(&rules, &diagnostic.unwrap().message, diagnostic).is_empty());
+    }
+}
@@ -1,4 +1,6 @@
+pub mod paths;
+pub mod process;
 pub mod shell;
 
+pub use paths::{context_run_dir, dev_home, safe_file_name};
 pub use shell::{detect_shell, Shell};
-
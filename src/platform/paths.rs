@@ -0,0 +1,34 @@
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+
+/// Root directory for dev's state, config and runtime files (`~/.dev`)
+pub fn dev_home() -> Result<PathBuf> {
+    let home = dirs::home_dir()
+        .context("Could not determine home directory")?;
+    Ok(home.join(".dev"))
+}
+
+/// Runtime directory for a context (`~/.dev/run/<ctx>`), created on demand
+pub fn context_run_dir(name: &str) -> Result<PathBuf> {
+    let dir = dev_home()?.join("run").join(safe_file_name(name));
+    std::fs::create_dir_all(&dir)
+        .with_context(|| format!("Failed to create runtime directory {:?}", dir))?;
+    Ok(dir)
+}
+
+/// A single path component for a user-chosen name: names of letters, digits,
+/// `_`, `-` and `.` are kept as they are; anything else (`/`, `..`, spaces) is
+/// replaced and a short hash of the original keeps distinct names apart
+pub fn safe_file_name(name: &str) -> String {
+    let safe: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.') { c } else { '_' })
+        .collect();
+    if safe == name && !name.is_empty() && !name.starts_with('.') {
+        return safe;
+    }
+    let hash = Sha256::digest(name.as_bytes());
+    let hash: String = hash.iter().take(4).map(|byte| format!("{:02x}", byte)).collect();
+    format!("{}-{}", safe.trim_start_matches('.'), hash)
+}
@@ -0,0 +1,77 @@
+use crate::utils::trace::TracedCommand;
+use anyhow::{Context, Result};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Check whether a process with the given PID is still running
+pub fn is_running(pid: u32) -> bool {
+    if cfg!(windows) {
+        Command::new("tasklist")
+            .args(["/FI", &format!("PID eq {}", pid), "/NH"])
+            .traced_output()
+            .map(|output| String::from_utf8_lossy(&output.stdout).contains(&pid.to_string()))
+            .unwrap_or(false)
+    } else {
+        Command::new("kill")
+            .args(["-0", &pid.to_string()])
+            .stderr(Stdio::null())
+            .traced_status()
+            .map(|status| status.success())
+            .unwrap_or(false)
+    }
+}
+
+/// When the process started, to tell it apart from a later process that was
+/// given the same PID. `None` if it is not running or this can't be found out
+/// (Windows).
+pub fn start_time(pid: u32) -> Option<String> {
+    if cfg!(windows) {
+        return None;
+    }
+    // Clock ticks since boot, field 22 of /proc/<pid>/stat (after the parenthesised name)
+    if let Ok(stat) = std::fs::read_to_string(format!("/proc/{}/stat", pid)) {
+        return stat.rsplit_once(')')?.1.split_whitespace().nth(19).map(str::to_string);
+    }
+    let output = Command::new("ps")
+        .args(["-o", "lstart=", "-p", &pid.to_string()])
+        .stderr(Stdio::null())
+        .traced_output()
+        .ok()?;
+    let started = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !started.is_empty()).then_some(started)
+}
+
+/// Stop a process and its children, escalating to a forced kill after `grace`
+pub fn terminate(pid: u32, grace: Duration) -> Result<()> {
+    if cfg!(windows) {
+        Command::new("taskkill")
+            .args(["/PID", &pid.to_string(), "/T", "/F"])
+            .traced_output()
+            .context("Failed to run taskkill")?;
+        return Ok(());
+    }
+
+    // Services are spawned as process group leaders, so signal the whole group
+    let group = format!("-{}", pid);
+    Command::new("kill")
+        .args(["-TERM", "--", &group])
+        .stderr(Stdio::null())
+        .traced_status()
+        .context("Failed to run kill")?;
+
+    let deadline = Instant::now() + grace;
+    while Instant::now() < deadline {
+        if !is_running(pid) {
+            return Ok(());
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+
+    Command::new("kill")
+        .args(["-KILL", "--", &group])
+        .stderr(Stdio::null())
+        .traced_status()
+        .context("Failed to run kill")?;
+    Ok(())
+}
@@ -2,6 +2,7 @@ use std::env;
 
 /// Supported shell types
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(clippy::enum_variant_names)]
 pub enum Shell {
     Bash,
     Zsh,
@@ -1,40 +1,47 @@
-use crate::state::store::{Context, Snippet, StateStore, WtfExplanation};
+use crate::state::store::{
+    Context, LlmCacheEntry, LlmUsage, Snippet, SnippetRun, StateStore, TrustedCommand, WtfAppliedAction,
+    WtfExplanation,
+};
 use anyhow::{Context as AnyhowContext, Result};
 use rusqlite::{params, Connection};
 use std::cell::RefCell;
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 /// SQLite-backed implementation of StateStore
 /// Uses RefCell since this is a single-threaded CLI application
 pub struct SqliteStore {
     conn: RefCell<Connection>,
+    /// Where the database lives, for error messages
+    path: PathBuf,
 }
 
 impl SqliteStore {
     /// Create a new SqliteStore instance
     pub fn new() -> Result<Self> {
-        let db_path = get_db_path()?;
-        
+        Self::open(&get_db_path()?)
+    }
+    
+    /// Open (or create) the database at `db_path`
+    pub fn open(db_path: &Path) -> Result<Self> {
         // Create parent directory if it doesn't exist
         if let Some(parent) = db_path.parent() {
             std::fs::create_dir_all(parent)
                 .with_context(|| format!("Failed to create directory for database: {:?}", parent))?;
         }
         
-        let conn = Connection::open(&db_path)
+        let conn = Connection::open(db_path)
             .with_context(|| format!("Failed to open database at {:?}", db_path))?;
         
         Ok(SqliteStore {
             conn: RefCell::new(conn),
+            path: db_path.to_path_buf(),
         })
     }
 }
 
 fn get_db_path() -> Result<PathBuf> {
-    let home = dirs::home_dir()
-        .context("Could not determine home directory")?;
-    Ok(home.join(".dev").join("state.db"))
+    Ok(crate::platform::dev_home()?.join("state.db"))
 }
 
 impl StateStore for SqliteStore {
@@ -54,7 +61,9 @@ impl StateStore for SqliteStore {
                 virtual_env TEXT,
                 important_files TEXT,
                 important_dirs TEXT,
-                package_manager TEXT
+                package_manager TEXT,
+                layout TEXT,
+                services TEXT
             )",
             [],
         )?;
@@ -64,7 +73,7 @@ impl StateStore for SqliteStore {
         let table_info_result: Result<Vec<String>, _> = conn
             .prepare("PRAGMA table_info(contexts)")
             .and_then(|mut stmt| {
-                stmt.query_map([], |row| Ok(row.get::<_, String>(1)?))
+                stmt.query_map([], |row| row.get::<_, String>(1))
                     .and_then(|iter| iter.collect::<Result<Vec<_>, _>>())
             });
         
@@ -88,6 +97,12 @@ impl StateStore for SqliteStore {
             if !table_info.contains(&"package_manager".to_string()) {
                 let _ = conn.execute("ALTER TABLE contexts ADD COLUMN package_manager TEXT", []);
             }
+            if !table_info.contains(&"layout".to_string()) {
+                let _ = conn.execute("ALTER TABLE contexts ADD COLUMN layout TEXT", []);
+            }
+            if !table_info.contains(&"services".to_string()) {
+                let _ = conn.execute("ALTER TABLE contexts ADD COLUMN services TEXT", []);
+            }
         }
         
         // Create wtf_explanations table
@@ -101,6 +116,66 @@ impl StateStore for SqliteStore {
             [],
         )?;
         
+        let wtf_columns: Vec<String> = conn
+            .prepare("PRAGMA table_info(wtf_explanations)")?
+            .query_map([], |row| row.get::<_, String>(1))?
+            .collect::<Result<Vec<_>, _>>()?;
+        for (column, definition) in [
+            ("source", "TEXT"),
+            ("summary", "TEXT"),
+            ("normalized", "TEXT"),
+            ("created_at", "TEXT"),
+            ("last_used", "TEXT"),
+            ("hits", "INTEGER"),
+            ("actions", "TEXT"),
+            ("details", "TEXT"),
+        ] {
+            if !wtf_columns.iter().any(|c| c == column) {
+                conn.execute(&format!("ALTER TABLE wtf_explanations ADD COLUMN {} {}", column, definition), [])?;
+            }
+        }
+        
+        // Create wtf_applied_actions table
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS wtf_applied_actions (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                fingerprint TEXT NOT NULL,
+                action TEXT NOT NULL,
+                directory TEXT NOT NULL,
+                applied_at TEXT NOT NULL,
+                success INTEGER NOT NULL
+            )",
+            [],
+        )?;
+        
+        // Create llm_cache table
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS llm_cache (
+                key TEXT PRIMARY KEY,
+                provider TEXT NOT NULL,
+                model TEXT NOT NULL,
+                reply TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            )",
+            [],
+        )?;
+        
+        // Create llm_usage table
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS llm_usage (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                command TEXT NOT NULL,
+                provider TEXT NOT NULL,
+                model TEXT NOT NULL,
+                input_tokens INTEGER NOT NULL,
+                output_tokens INTEGER NOT NULL,
+                cost REAL,
+                cached INTEGER NOT NULL,
+                created_at TEXT NOT NULL
+            )",
+            [],
+        )?;
+        
         // Create snippets table
         conn.execute(
             "CREATE TABLE IF NOT EXISTS snippets (
@@ -111,6 +186,53 @@ impl StateStore for SqliteStore {
             [],
         )?;
         
+        let snippet_columns: Vec<String> = conn
+            .prepare("PRAGMA table_info(snippets)")?
+            .query_map([], |row| row.get::<_, String>(1))?
+            .collect::<Result<Vec<_>, _>>()?;
+        for column in ["steps", "description"] {
+            if !snippet_columns.iter().any(|c| c == column) {
+                conn.execute(&format!("ALTER TABLE snippets ADD COLUMN {} TEXT", column), [])?;
+            }
+        }
+        
+        // Create snippet_runs table
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS snippet_runs (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL,
+                file TEXT,
+                command TEXT NOT NULL,
+                vars TEXT NOT NULL,
+                cwd TEXT NOT NULL,
+                context TEXT,
+                started_at TEXT NOT NULL,
+                duration_ms INTEGER NOT NULL,
+                exit_code INTEGER
+            )",
+            [],
+        )?;
+        
+        // Create secret_names table
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS secret_names (
+                name TEXT PRIMARY KEY,
+                added_at TEXT NOT NULL
+            )",
+            [],
+        )?;
+        
+        // Create trusted_commands table
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS trusted_commands (
+                hash TEXT PRIMARY KEY,
+                path TEXT NOT NULL,
+                command TEXT NOT NULL,
+                trusted_at TEXT NOT NULL
+            )",
+            [],
+        )?;
+        
         Ok(())
     }
     
@@ -126,14 +248,19 @@ impl StateStore for SqliteStore {
         let important_dirs_json = serde_json::to_string(&context.important_dirs)?;
         let project_type_json = context.project_type.as_ref()
             .map(|pt| serde_json::to_string(pt).unwrap_or_default());
+        let layout_json = context.layout.as_ref()
+            .map(serde_json::to_string)
+            .transpose()?;
+        let services_json = serde_json::to_string(&context.services)?;
         
         let conn = self.conn.borrow_mut();
         
         conn.execute(
             "INSERT OR REPLACE INTO contexts (
                 name, working_dir, git_branch, env_vars, on_switch,
-                project_type, dependencies, virtual_env, important_files, important_dirs, package_manager
-            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                project_type, dependencies, virtual_env, important_files, important_dirs, package_manager,
+                layout, services
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
             params![
                 context.name,
                 context.working_dir,
@@ -145,7 +272,9 @@ impl StateStore for SqliteStore {
                 context.virtual_env,
                 important_files_json,
                 important_dirs_json,
-                context.package_manager
+                context.package_manager,
+                layout_json,
+                services_json
             ],
         )?;
         
@@ -156,7 +285,8 @@ impl StateStore for SqliteStore {
         let conn = self.conn.borrow();
         let mut stmt = conn.prepare(
             "SELECT name, working_dir, git_branch, env_vars, on_switch,
-                    project_type, dependencies, virtual_env, important_files, important_dirs, package_manager
+                    project_type, dependencies, virtual_env, important_files, important_dirs, package_manager,
+                    layout, services
              FROM contexts WHERE name = ?1"
         )?;
         
@@ -181,7 +311,14 @@ impl StateStore for SqliteStore {
             let important_dirs: Vec<String> = serde_json::from_str(&important_dirs_json)
                 .unwrap_or_default();
             
-            Ok(Context {
+            let layout_json: Option<String> = row.get(11)?;
+            let layout = layout_json
+                .and_then(|json| serde_json::from_str(&json).ok());
+            
+            // Parsed below, so that a corrupt list is reported rather than dropped
+            let services_json: Option<String> = row.get(12)?;
+            
+            let context = Context {
                 name: row.get(0)?,
                 working_dir: row.get(1)?,
                 git_branch: row.get(2)?,
@@ -193,21 +330,29 @@ impl StateStore for SqliteStore {
                 important_files,
                 important_dirs,
                 package_manager: row.get(10)?,
-            })
+                layout,
+                services: Vec::new(),
+            };
+            Ok((context, services_json))
         })?;
         
-        match rows.next() {
-            Some(row) => Ok(Some(row?)),
-            None => Ok(None),
+        let Some(row) = rows.next() else {
+            return Ok(None);
+        };
+        let (mut context, services_json) = row?;
+        if let Some(json) = services_json {
+            // Forgetting the services would leave running ones that `dev ctx down` can't stop
+            context.services = serde_json::from_str(&json).with_context(|| {
+                format!("Failed to parse the services of context '{}' in {:?}", name, self.path)
+            })?;
         }
+        Ok(Some(context))
     }
     
     fn list_contexts(&self) -> Result<Vec<String>> {
         let conn = self.conn.borrow();
         let mut stmt = conn.prepare("SELECT name FROM contexts ORDER BY name")?;
-        let rows = stmt.query_map([], |row| {
-            Ok(row.get::<_, String>(0)?)
-        })?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
         
         let mut names = Vec::new();
         for row in rows {
@@ -225,19 +370,9 @@ impl StateStore for SqliteStore {
     
     fn get_wtf_explanation(&self, fingerprint: &str) -> Result<Option<WtfExplanation>> {
         let conn = self.conn.borrow();
-        let mut stmt = conn.prepare(
-            "SELECT fingerprint, cause, suggested_fix, confidence
-             FROM wtf_explanations WHERE fingerprint = ?1"
-        )?;
+        let mut stmt = conn.prepare(&format!("{} WHERE fingerprint = ?1", WTF_SELECT))?;
         
-        let mut rows = stmt.query_map(params![fingerprint], |row| {
-            Ok(WtfExplanation {
-                fingerprint: row.get(0)?,
-                cause: row.get(1)?,
-                suggested_fix: row.get(2)?,
-                confidence: row.get(3)?,
-            })
-        })?;
+        let mut rows = stmt.query_map(params![fingerprint], wtf_from_row)?;
         
         match rows.next() {
             Some(row) => Ok(Some(row?)),
@@ -246,52 +381,210 @@ impl StateStore for SqliteStore {
     }
     
     fn save_wtf_explanation(&mut self, explanation: WtfExplanation) -> Result<()> {
+        let actions_json = serde_json::to_string(&explanation.actions)?;
+        let details_json = serde_json::to_string(&explanation.details)?;
         let conn = self.conn.borrow_mut();
         conn.execute(
-            "INSERT OR REPLACE INTO wtf_explanations (fingerprint, cause, suggested_fix, confidence)
-             VALUES (?1, ?2, ?3, ?4)",
+            "INSERT OR REPLACE INTO wtf_explanations (
+                fingerprint, cause, suggested_fix, confidence, source, summary, normalized,
+                created_at, last_used, hits, actions, details
+             ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
             params![
                 explanation.fingerprint,
                 explanation.cause,
                 explanation.suggested_fix,
-                explanation.confidence
+                explanation.confidence,
+                explanation.source,
+                explanation.summary,
+                explanation.normalized,
+                explanation.created_at,
+                explanation.last_used,
+                explanation.hits,
+                actions_json,
+                details_json
             ],
         )?;
         
         Ok(())
     }
     
-    fn save_snippet(&mut self, snippet: Snippet) -> Result<()> {
-        let tags_json = serde_json::to_string(&snippet.tags)?;
-        let conn = self.conn.borrow_mut();
+    fn list_wtf_explanations(&self) -> Result<Vec<WtfExplanation>> {
+        let conn = self.conn.borrow();
+        let mut stmt = conn.prepare(&format!("{} ORDER BY last_used DESC", WTF_SELECT))?;
+        
+        let rows = stmt.query_map([], wtf_from_row)?;
         
+        let mut explanations = Vec::new();
+        for row in rows {
+            explanations.push(row?);
+        }
+        
+        Ok(explanations)
+    }
+    
+    fn delete_wtf_explanation(&mut self, fingerprint: &str) -> Result<()> {
+        let conn = self.conn.borrow_mut();
+        conn.execute("DELETE FROM wtf_explanations WHERE fingerprint = ?1", params![fingerprint])?;
+        Ok(())
+    }
+    
+    fn record_wtf_hit(&mut self, fingerprint: &str) -> Result<()> {
+        let conn = self.conn.borrow_mut();
+        conn.execute(
+            "UPDATE wtf_explanations SET hits = COALESCE(hits, 0) + 1, last_used = ?2 WHERE fingerprint = ?1",
+            params![fingerprint, chrono::Local::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+    
+    fn save_wtf_applied_action(&mut self, applied: WtfAppliedAction) -> Result<()> {
+        let action_json = serde_json::to_string(&applied.action)?;
+        let conn = self.conn.borrow_mut();
         conn.execute(
-            "INSERT OR REPLACE INTO snippets (name, command, tags)
-             VALUES (?1, ?2, ?3)",
-            params![snippet.name, snippet.command, tags_json],
+            "INSERT INTO wtf_applied_actions (fingerprint, action, directory, applied_at, success)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![applied.fingerprint, action_json, applied.directory, applied.applied_at, applied.success],
+        )?;
+        Ok(())
+    }
+    
+    fn list_wtf_applied_actions(&self) -> Result<Vec<WtfAppliedAction>> {
+        let conn = self.conn.borrow();
+        let mut stmt = conn.prepare(
+            "SELECT fingerprint, action, directory, applied_at, success
+             FROM wtf_applied_actions ORDER BY id DESC",
         )?;
         
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, bool>(4)?,
+            ))
+        })?;
+        
+        let mut applied = Vec::new();
+        for row in rows {
+            let (fingerprint, action_json, directory, applied_at, success) = row?;
+            applied.push(WtfAppliedAction {
+                fingerprint,
+                action: serde_json::from_str(&action_json)?,
+                directory,
+                applied_at,
+                success,
+            });
+        }
+        
+        Ok(applied)
+    }
+    
+    fn get_llm_cache(&self, key: &str) -> Result<Option<LlmCacheEntry>> {
+        let conn = self.conn.borrow();
+        let mut stmt = conn.prepare(
+            "SELECT key, provider, model, reply, created_at FROM llm_cache WHERE key = ?1"
+        )?;
+        
+        let mut rows = stmt.query_map(params![key], |row| {
+            Ok(LlmCacheEntry {
+                key: row.get(0)?,
+                provider: row.get(1)?,
+                model: row.get(2)?,
+                reply: row.get(3)?,
+                created_at: row.get(4)?,
+            })
+        })?;
+        
+        match rows.next() {
+            Some(row) => Ok(Some(row?)),
+            None => Ok(None),
+        }
+    }
+    
+    fn save_llm_cache(&mut self, entry: LlmCacheEntry) -> Result<()> {
+        let conn = self.conn.borrow_mut();
+        conn.execute(
+            "INSERT OR REPLACE INTO llm_cache (key, provider, model, reply, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![entry.key, entry.provider, entry.model, entry.reply, entry.created_at],
+        )?;
         Ok(())
     }
     
-    fn get_snippet(&self, name: &str) -> Result<Option<Snippet>> {
+    fn clear_llm_cache(&mut self) -> Result<usize> {
+        let conn = self.conn.borrow_mut();
+        let deleted = conn.execute("DELETE FROM llm_cache", [])?;
+        Ok(deleted)
+    }
+    
+    fn save_llm_usage(&mut self, usage: LlmUsage) -> Result<()> {
+        let conn = self.conn.borrow_mut();
+        conn.execute(
+            "INSERT INTO llm_usage (command, provider, model, input_tokens, output_tokens, cost, cached, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                usage.command,
+                usage.provider,
+                usage.model,
+                usage.input_tokens as i64,
+                usage.output_tokens as i64,
+                usage.cost,
+                usage.cached,
+                usage.created_at
+            ],
+        )?;
+        Ok(())
+    }
+    
+    fn list_llm_usage(&self, since: &str) -> Result<Vec<LlmUsage>> {
         let conn = self.conn.borrow();
         let mut stmt = conn.prepare(
-            "SELECT name, command, tags FROM snippets WHERE name = ?1"
+            "SELECT command, provider, model, input_tokens, output_tokens, cost, cached, created_at
+             FROM llm_usage WHERE created_at >= ?1 ORDER BY id",
         )?;
         
-        let mut rows = stmt.query_map(params![name], |row| {
-            let tags_json: String = row.get(2)?;
-            let tags: Vec<String> = serde_json::from_str(&tags_json)
-                .unwrap_or_default();
-            
-            Ok(Snippet {
-                name: row.get(0)?,
-                command: row.get(1)?,
-                tags,
+        let rows = stmt.query_map(params![since], |row| {
+            Ok(LlmUsage {
+                command: row.get(0)?,
+                provider: row.get(1)?,
+                model: row.get(2)?,
+                input_tokens: row.get::<_, i64>(3)? as u64,
+                output_tokens: row.get::<_, i64>(4)? as u64,
+                cost: row.get(5)?,
+                cached: row.get(6)?,
+                created_at: row.get(7)?,
             })
         })?;
         
+        let mut usage = Vec::new();
+        for row in rows {
+            usage.push(row?);
+        }
+        
+        Ok(usage)
+    }
+    
+    fn save_snippet(&mut self, snippet: Snippet) -> Result<()> {
+        let tags_json = serde_json::to_string(&snippet.tags)?;
+        let steps_json = if snippet.steps.is_empty() { None } else { Some(serde_json::to_string(&snippet.steps)?) };
+        let conn = self.conn.borrow_mut();
+        
+        conn.execute(
+            "INSERT OR REPLACE INTO snippets (name, command, tags, steps, description)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![snippet.name, snippet.command, tags_json, steps_json, snippet.description],
+        )?;
+        
+        Ok(())
+    }
+    
+    fn get_snippet(&self, name: &str) -> Result<Option<Snippet>> {
+        let conn = self.conn.borrow();
+        let mut stmt = conn.prepare(&format!("{} WHERE name = ?1", SNIPPET_SELECT))?;
+        
+        let mut rows = stmt.query_map(params![name], snippet_from_row)?;
+        
         match rows.next() {
             Some(row) => Ok(Some(row?)),
             None => Ok(None),
@@ -305,41 +598,17 @@ impl StateStore for SqliteStore {
         
         if let Some(tag) = tag_filter {
             let tag_pattern = format!("%\"{}\"%", tag);
-            let mut stmt = conn.prepare(
-                "SELECT name, command, tags FROM snippets WHERE tags LIKE ?1 ORDER BY name"
-            )?;
+            let mut stmt = conn.prepare(&format!("{} WHERE tags LIKE ?1 ORDER BY name", SNIPPET_SELECT))?;
             
-            let rows = stmt.query_map(params![tag_pattern], |row| {
-                let tags_json: String = row.get(2)?;
-                let tags: Vec<String> = serde_json::from_str(&tags_json)
-                    .unwrap_or_default();
-                
-                Ok(Snippet {
-                    name: row.get(0)?,
-                    command: row.get(1)?,
-                    tags,
-                })
-            })?;
+            let rows = stmt.query_map(params![tag_pattern], snippet_from_row)?;
             
             for row in rows {
                 snippets.push(row?);
             }
         } else {
-            let mut stmt = conn.prepare(
-                "SELECT name, command, tags FROM snippets ORDER BY name"
-            )?;
+            let mut stmt = conn.prepare(&format!("{} ORDER BY name", SNIPPET_SELECT))?;
             
-            let rows = stmt.query_map([], |row| {
-                let tags_json: String = row.get(2)?;
-                let tags: Vec<String> = serde_json::from_str(&tags_json)
-                    .unwrap_or_default();
-                
-                Ok(Snippet {
-                    name: row.get(0)?,
-                    command: row.get(1)?,
-                    tags,
-                })
-            })?;
+            let rows = stmt.query_map([], snippet_from_row)?;
             
             for row in rows {
                 snippets.push(row?);
@@ -354,5 +623,238 @@ impl StateStore for SqliteStore {
         conn.execute("DELETE FROM snippets WHERE name = ?1", params![name])?;
         Ok(())
     }
+    
+    fn save_snippet_run(&mut self, run: SnippetRun) -> Result<i64> {
+        let vars_json = serde_json::to_string(&run.vars)?;
+        let conn = self.conn.borrow_mut();
+        conn.execute(
+            "INSERT INTO snippet_runs (name, file, command, vars, cwd, context, started_at, duration_ms, exit_code)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                run.name,
+                run.file,
+                run.command,
+                vars_json,
+                run.cwd,
+                run.context,
+                run.started_at,
+                run.duration_ms,
+                run.exit_code
+            ],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+    
+    fn get_snippet_run(&self, id: i64) -> Result<Option<SnippetRun>> {
+        let conn = self.conn.borrow();
+        let mut stmt = conn.prepare(&format!("{} WHERE id = ?1", SNIPPET_RUN_SELECT))?;
+        let mut rows = stmt.query_map(params![id], snippet_run_from_row)?;
+        match rows.next() {
+            Some(row) => Ok(Some(row?)),
+            None => Ok(None),
+        }
+    }
+    
+    fn list_snippet_runs(&self, name: Option<&str>, limit: usize) -> Result<Vec<SnippetRun>> {
+        let conn = self.conn.borrow();
+        let mut stmt = conn.prepare(&format!(
+            "{} WHERE ?1 IS NULL OR name = ?1 ORDER BY id DESC LIMIT ?2",
+            SNIPPET_RUN_SELECT
+        ))?;
+        let rows = stmt.query_map(params![name, limit as i64], snippet_run_from_row)?;
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+    
+    fn save_secret_name(&mut self, name: &str) -> Result<()> {
+        let conn = self.conn.borrow_mut();
+        conn.execute(
+            "INSERT OR IGNORE INTO secret_names (name, added_at) VALUES (?1, ?2)",
+            params![name, chrono::Local::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+    
+    fn list_secret_names(&self) -> Result<Vec<String>> {
+        let conn = self.conn.borrow();
+        let mut stmt = conn.prepare("SELECT name FROM secret_names ORDER BY name")?;
+        let rows = stmt.query_map([], |row| row.get(0))?;
+        
+        let mut names = Vec::new();
+        for row in rows {
+            names.push(row?);
+        }
+        
+        Ok(names)
+    }
+    
+    fn get_trusted_command(&self, hash: &str) -> Result<Option<TrustedCommand>> {
+        let conn = self.conn.borrow();
+        let mut stmt = conn.prepare(
+            "SELECT hash, path, command, trusted_at FROM trusted_commands WHERE hash = ?1"
+        )?;
+        
+        let mut rows = stmt.query_map(params![hash], |row| {
+            Ok(TrustedCommand {
+                hash: row.get(0)?,
+                path: row.get(1)?,
+                command: row.get(2)?,
+                trusted_at: row.get(3)?,
+            })
+        })?;
+        
+        match rows.next() {
+            Some(row) => Ok(Some(row?)),
+            None => Ok(None),
+        }
+    }
+    
+    fn save_trusted_command(&mut self, trusted: TrustedCommand) -> Result<()> {
+        let conn = self.conn.borrow_mut();
+        conn.execute(
+            "INSERT OR REPLACE INTO trusted_commands (hash, path, command, trusted_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![trusted.hash, trusted.path, trusted.command, trusted.trusted_at],
+        )?;
+        
+        Ok(())
+    }
+    
+    fn list_trusted_commands(&self) -> Result<Vec<TrustedCommand>> {
+        let conn = self.conn.borrow();
+        let mut stmt = conn.prepare(
+            "SELECT hash, path, command, trusted_at FROM trusted_commands ORDER BY path, trusted_at"
+        )?;
+        
+        let rows = stmt.query_map([], |row| {
+            Ok(TrustedCommand {
+                hash: row.get(0)?,
+                path: row.get(1)?,
+                command: row.get(2)?,
+                trusted_at: row.get(3)?,
+            })
+        })?;
+        
+        let mut trusted = Vec::new();
+        for row in rows {
+            trusted.push(row?);
+        }
+        
+        Ok(trusted)
+    }
+    
+    fn delete_trusted_command(&mut self, hash: &str) -> Result<()> {
+        let conn = self.conn.borrow_mut();
+        conn.execute("DELETE FROM trusted_commands WHERE hash = ?1", params![hash])?;
+        Ok(())
+    }
 }
 
+const SNIPPET_SELECT: &str = "SELECT name, command, tags, steps, description FROM snippets";
+
+/// Build a snippet from a `SNIPPET_SELECT` row
+fn snippet_from_row(row: &rusqlite::Row) -> rusqlite::Result<Snippet> {
+    let tags_json: String = row.get(2)?;
+    Ok(Snippet {
+        name: row.get(0)?,
+        command: row.get(1)?,
+        tags: serde_json::from_str(&tags_json).unwrap_or_default(),
+        steps: row
+            .get::<_, Option<String>>(3)?
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default(),
+        description: row.get(4)?,
+    })
+}
+
+const SNIPPET_RUN_SELECT: &str =
+    "SELECT id, name, file, command, vars, cwd, context, started_at, duration_ms, exit_code FROM snippet_runs";
+
+fn snippet_run_from_row(row: &rusqlite::Row) -> rusqlite::Result<SnippetRun> {
+    let vars_json: String = row.get(4)?;
+    Ok(SnippetRun {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        file: row.get(2)?,
+        command: row.get(3)?,
+        vars: serde_json::from_str(&vars_json).unwrap_or_default(),
+        cwd: row.get(5)?,
+        context: row.get(6)?,
+        started_at: row.get(7)?,
+        duration_ms: row.get(8)?,
+        exit_code: row.get(9)?,
+    })
+}
+
+const WTF_SELECT: &str = "SELECT fingerprint, cause, suggested_fix, confidence, source, summary, normalized,
+            created_at, last_used, hits, actions, details
+     FROM wtf_explanations";
+
+/// Build an explanation from a `WTF_SELECT` row; entries cached before these
+/// columns existed get empty values
+fn wtf_from_row(row: &rusqlite::Row) -> rusqlite::Result<WtfExplanation> {
+    Ok(WtfExplanation {
+        fingerprint: row.get(0)?,
+        cause: row.get(1)?,
+        suggested_fix: row.get(2)?,
+        confidence: row.get(3)?,
+        source: row.get::<_, Option<String>>(4)?.unwrap_or_else(|| "unknown".to_string()),
+        summary: row.get::<_, Option<String>>(5)?.unwrap_or_default(),
+        normalized: row.get::<_, Option<String>>(6)?.unwrap_or_default(),
+        created_at: row.get::<_, Option<String>>(7)?.unwrap_or_default(),
+        last_used: row.get::<_, Option<String>>(8)?.unwrap_or_default(),
+        hits: row.get::<_, Option<u32>>(9)?.unwrap_or(0),
+        actions: row
+            .get::<_, Option<String>>(10)?
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default(),
+        details: row
+            .get::<_, Option<String>>(11)?
+            .and_then(|json| serde_json::from_str(&json).ok()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::store::ContextService;
+
+    #[test]
+    fn corrupt_services_are_reported() {
+        let dir = std::env::temp_dir().join(format!("dev-sqlite-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut store = SqliteStore::open(&dir.join("state.db")).unwrap();
+        store.init().unwrap();
+
+        let service = ContextService {
+            name: "web".to_string(),
+            command: "npm start".to_string(),
+            working_dir: None,
+            env: HashMap::new(),
+            ready: None,
+        };
+        store
+            .save_context(Context {
+                name: "app".to_string(),
+                working_dir: "/tmp/app".to_string(),
+                git_branch: None,
+                env_vars: HashMap::new(),
+                on_switch: None,
+                project_type: None,
+                dependencies: HashMap::new(),
+                virtual_env: None,
+                important_files: Vec::new(),
+                important_dirs: Vec::new(),
+                package_manager: None,
+                layout: None,
+                services: vec![service.clone()],
+            })
+            .unwrap();
+        assert_eq!(store.get_context("app").unwrap().unwrap().services, [service]);
+
+        store.conn.borrow().execute("UPDATE contexts SET services = '[{\"name\": ' WHERE name = 'app'", []).unwrap();
+        let error = store.get_context("app").unwrap_err().to_string();
+        assert!(error.contains("services of context 'app'"), "{}", error);
+        assert!(error.contains("state.db"), "{}", error);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -14,7 +14,7 @@ pub enum ProjectType {
 }
 
 /// Context data structure - represents a complete project context
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Context {
     pub name: String,
     pub working_dir: String,
@@ -28,6 +28,67 @@ pub struct Context {
     pub important_files: Vec<String>, // relative paths to important files
     pub important_dirs: Vec<String>, // relative paths to important directories
     pub package_manager: Option<String>, // npm, cargo, pip, go, etc.
+    pub layout: Option<SessionLayout>, // terminal multiplexer layout
+    pub services: Vec<ContextService>, // background processes started by `dev ctx up`
+}
+
+/// A named background process declared by a context
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContextService {
+    pub name: String,
+    pub command: String,
+    /// Directory relative to the context's working directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub working_dir: Option<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub env: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ready: Option<ReadinessCheck>,
+}
+
+/// How to tell that a service has finished starting
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReadinessCheck {
+    /// TCP port on localhost that accepts connections once ready
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    /// Regex matched against the service's log output
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_regex: Option<String>,
+    #[serde(default = "default_ready_timeout")]
+    pub timeout_secs: u64,
+}
+
+fn default_ready_timeout() -> u64 {
+    30
+}
+
+/// Terminal multiplexer layout attached to a context
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionLayout {
+    #[serde(default)]
+    pub windows: Vec<LayoutWindow>,
+}
+
+/// A window (tmux) or tab (zellij) in a session layout
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LayoutWindow {
+    pub name: String,
+    /// tmux layout preset or captured layout string (e.g. "main-vertical")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layout: Option<String>,
+    #[serde(default)]
+    pub panes: Vec<LayoutPane>,
+}
+
+/// A single pane and the command it starts with
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LayoutPane {
+    /// Directory relative to the context's working directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
 }
 
 /// WTF explanation data
@@ -37,14 +98,140 @@ pub struct WtfExplanation {
     pub cause: String,
     pub suggested_fix: String,
     pub confidence: f64,
+    /// Where the explanation came from: "llm", "rules", or "unknown" for old entries
+    pub source: String,
+    /// First line of the explained error, for `dev wtf history`
+    pub summary: String,
+    /// Normalized error text, used to find similar errors
+    pub normalized: String,
+    pub created_at: String,
+    pub last_used: String,
+    /// Number of times the cached explanation was served
+    pub hits: u32,
+    /// Fixes `dev wtf --apply` can carry out
+    pub actions: Vec<WtfAction>,
+    /// Details of the explained error (names, paths, addresses) that the
+    /// explanation repeats; it is only reused for errors containing all of them.
+    /// `None` for entries cached before details were recorded.
+    pub details: Option<Vec<String>>,
 }
 
-/// Snippet data structure
+/// A structured fix suggested with an explanation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum WtfAction {
+    /// A shell command, run in the project directory
+    Command { command: String },
+    /// A unified diff against files in the project directory
+    Patch { diff: String },
+}
+
+/// An action the user applied with `dev wtf --apply`
+#[derive(Debug, Clone)]
+pub struct WtfAppliedAction {
+    pub fingerprint: String,
+    pub action: WtfAction,
+    pub directory: String,
+    pub applied_at: String,
+    pub success: bool,
+}
+
+/// A cached LLM reply
 #[derive(Debug, Clone)]
+pub struct LlmCacheEntry {
+    /// Hash of the provider, model, parameters and prompts
+    pub key: String,
+    pub provider: String,
+    pub model: String,
+    pub reply: String,
+    pub created_at: String,
+}
+
+/// One LLM call, for `dev llm usage` and budgets
+#[derive(Debug, Clone)]
+pub struct LlmUsage {
+    /// The dev command that made the call, e.g. "docs generate"
+    pub command: String,
+    pub provider: String,
+    pub model: String,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    /// US dollars; `None` when the model's price is unknown
+    pub cost: Option<f64>,
+    /// Served from the LLM cache, so free
+    pub cached: bool,
+    /// RFC 3339 in UTC, so times compare as strings
+    pub created_at: String,
+}
+
+/// Snippet data structure
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Snippet {
     pub name: String,
+    /// Command of a single-command snippet; empty for a runbook
     pub command: String,
     pub tags: Vec<String>,
+    /// What the snippet is for, shown by `show` and searched by `pick`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Ordered steps of a runbook snippet
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub steps: Vec<SnippetStep>,
+}
+
+/// One step of a runbook snippet
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SnippetStep {
+    /// Shown in progress output and referred to by later steps' conditions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Command template, filled in like a snippet's command
+    pub command: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub env: HashMap<String, String>,
+    /// Ask before running the step
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub confirm: bool,
+    /// Keep going if the step fails
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub continue_on_error: bool,
+    /// When to run: `success` (the default), `failure`, `always`, or an
+    /// earlier step's exit code such as `migrate != 0`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub when: Option<String>,
+}
+
+/// One run of a snippet, for `dev snip history` and `rerun`
+#[derive(Debug, Clone)]
+pub struct SnippetRun {
+    /// Assigned by the store when the run is saved
+    pub id: i64,
+    pub name: String,
+    /// Runbook file the snippet was run from, when it is not a saved snippet
+    pub file: Option<String>,
+    /// Command as run, with the variables filled in; a runbook's steps one per line
+    pub command: String,
+    /// Values of every variable, including built-ins and defaults
+    pub vars: HashMap<String, String>,
+    pub cwd: String,
+    /// Active context at the time
+    pub context: Option<String>,
+    pub started_at: String,
+    pub duration_ms: u64,
+    /// `None` when the command could not be started or was killed by a signal
+    pub exit_code: Option<i32>,
+}
+
+/// A hook or command the user has agreed to run for a project path
+#[derive(Debug, Clone)]
+pub struct TrustedCommand {
+    pub hash: String,
+    pub path: String,
+    pub command: String,
+    pub trusted_at: String,
 }
 
 /// Trait for state storage operations
@@ -66,11 +253,43 @@ pub trait StateStore: Send {
     // WTF operations
     fn get_wtf_explanation(&self, fingerprint: &str) -> Result<Option<WtfExplanation>>;
     fn save_wtf_explanation(&mut self, explanation: WtfExplanation) -> Result<()>;
+    fn list_wtf_explanations(&self) -> Result<Vec<WtfExplanation>>;
+    fn delete_wtf_explanation(&mut self, fingerprint: &str) -> Result<()>;
+    /// Count a cache hit and update `last_used`
+    fn record_wtf_hit(&mut self, fingerprint: &str) -> Result<()>;
+    fn save_wtf_applied_action(&mut self, applied: WtfAppliedAction) -> Result<()>;
+    /// Applied actions, newest first
+    fn list_wtf_applied_actions(&self) -> Result<Vec<WtfAppliedAction>>;
+    
+    // LLM operations
+    fn get_llm_cache(&self, key: &str) -> Result<Option<LlmCacheEntry>>;
+    fn save_llm_cache(&mut self, entry: LlmCacheEntry) -> Result<()>;
+    /// Delete every cached reply, returning how many there were
+    fn clear_llm_cache(&mut self) -> Result<usize>;
+    fn save_llm_usage(&mut self, usage: LlmUsage) -> Result<()>;
+    /// Calls made at or after `since` (RFC 3339 in UTC, like `created_at`), oldest first
+    fn list_llm_usage(&self, since: &str) -> Result<Vec<LlmUsage>>;
     
     // Snippet operations
     fn save_snippet(&mut self, snippet: Snippet) -> Result<()>;
     fn get_snippet(&self, name: &str) -> Result<Option<Snippet>>;
     fn list_snippets(&self, tag_filter: Option<&str>) -> Result<Vec<Snippet>>;
     fn delete_snippet(&mut self, name: &str) -> Result<()>;
+    /// Record a run, returning its id
+    fn save_snippet_run(&mut self, run: SnippetRun) -> Result<i64>;
+    fn get_snippet_run(&self, id: i64) -> Result<Option<SnippetRun>>;
+    /// Runs newest first, of every snippet or only of `name`
+    fn list_snippet_runs(&self, name: Option<&str>, limit: usize) -> Result<Vec<SnippetRun>>;
+    
+    // Secret operations
+    /// Record the name of a secret stored in the keyring, which cannot list its entries
+    fn save_secret_name(&mut self, name: &str) -> Result<()>;
+    fn list_secret_names(&self) -> Result<Vec<String>>;
+    
+    // Trust operations
+    fn get_trusted_command(&self, hash: &str) -> Result<Option<TrustedCommand>>;
+    fn save_trusted_command(&mut self, trusted: TrustedCommand) -> Result<()>;
+    fn list_trusted_commands(&self) -> Result<Vec<TrustedCommand>>;
+    fn delete_trusted_command(&mut self, hash: &str) -> Result<()>;
 }
 
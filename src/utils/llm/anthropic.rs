@@ -0,0 +1,148 @@
+use super::stream::{ended_early, send_lines, sse_data};
+use super::usage::Usage;
+use super::{send_json, Completion, LlmProvider, ProviderSettings};
+use anyhow::{Context, Result};
+use reqwest::blocking::RequestBuilder;
+use serde_json::json;
+
+const API_VERSION: &str = "2023-06-01";
+
+#[derive(serde::Deserialize)]
+struct AnthropicContent {
+    #[serde(default)]
+    text: String,
+}
+
+#[derive(serde::Deserialize)]
+struct AnthropicResponse {
+    content: Vec<AnthropicContent>,
+    usage: Option<AnthropicUsage>,
+}
+
+#[derive(serde::Deserialize, Default)]
+#[serde(default)]
+struct AnthropicUsage {
+    input_tokens: u64,
+    output_tokens: u64,
+}
+
+impl From<AnthropicUsage> for Usage {
+    fn from(usage: AnthropicUsage) -> Self {
+        Usage { input_tokens: usage.input_tokens, output_tokens: usage.output_tokens }
+    }
+}
+
+/// One server-sent event of a streamed response. Text comes in deltas; input
+/// tokens are counted in `message_start` and output tokens in `message_delta`.
+#[derive(serde::Deserialize)]
+struct AnthropicEvent {
+    #[serde(rename = "type")]
+    kind: String,
+    delta: Option<AnthropicContent>,
+    message: Option<AnthropicStart>,
+    usage: Option<AnthropicUsage>,
+    error: Option<AnthropicError>,
+}
+
+#[derive(serde::Deserialize)]
+struct AnthropicStart {
+    usage: Option<AnthropicUsage>,
+}
+
+#[derive(serde::Deserialize)]
+struct AnthropicError {
+    message: String,
+}
+
+/// The Anthropic messages API
+pub struct Anthropic {
+    settings: ProviderSettings,
+}
+
+impl Anthropic {
+    pub fn new(settings: ProviderSettings) -> Self {
+        Anthropic { settings }
+    }
+
+    fn request(&self, system_prompt: &str, user_prompt: &str) -> Result<(RequestBuilder, serde_json::Value)> {
+        let mut body = json!({
+            "model": self.settings.model,
+            "max_tokens": self.settings.max_tokens,
+            "system": system_prompt,
+            "messages": [{"role": "user", "content": user_prompt}],
+        });
+        if let Some(temperature) = self.settings.temperature {
+            body["temperature"] = json!(temperature);
+        }
+
+        let mut request = self.settings.client()?
+            .post(self.settings.url("messages"))
+            .header("anthropic-version", API_VERSION);
+        if let Some(api_key) = &self.settings.api_key {
+            request = request.header("x-api-key", api_key);
+        }
+        Ok((request, body))
+    }
+}
+
+impl LlmProvider for Anthropic {
+    fn settings(&self) -> &ProviderSettings {
+        &self.settings
+    }
+
+    fn complete(&self, system_prompt: &str, user_prompt: &str) -> Result<Completion> {
+        let (request, body) = self.request(system_prompt, user_prompt)?;
+        let response: AnthropicResponse = send_json(&self.settings, request, &body)?;
+
+        let text: String = response.content.iter().map(|c| c.text.as_str()).collect();
+        if text.trim().is_empty() {
+            return Err(anyhow::anyhow!("Empty content from {}", self.settings.name));
+        }
+        Ok(Completion { text, usage: response.usage.map(Usage::from) })
+    }
+
+    fn stream(&self, system_prompt: &str, user_prompt: &str, on_text: &mut dyn FnMut(&str)) -> Result<Completion> {
+        let (request, mut body) = self.request(system_prompt, user_prompt)?;
+        body["stream"] = json!(true);
+
+        let mut reply = Completion::default();
+        let mut done = false;
+        send_lines(&self.settings, request, &body, |line| {
+            let Some(data) = sse_data(line) else {
+                return Ok(());
+            };
+            let event: AnthropicEvent = serde_json::from_str(data)
+                .with_context(|| format!("Unexpected response from {}", self.settings.name))?;
+            match event.kind.as_str() {
+                "content_block_delta" => {
+                    if let Some(delta) = event.delta {
+                        on_text(&delta.text);
+                        reply.text.push_str(&delta.text);
+                    }
+                }
+                "message_start" => {
+                    if let Some(usage) = event.message.and_then(|message| message.usage) {
+                        reply.usage.get_or_insert_with(Usage::default).input_tokens = usage.input_tokens;
+                    }
+                }
+                "message_delta" => {
+                    if let Some(usage) = event.usage {
+                        reply.usage.get_or_insert_with(Usage::default).output_tokens = usage.output_tokens;
+                    }
+                }
+                "message_stop" => done = true,
+                "error" => {
+                    let message = event.error.map(|error| error.message).unwrap_or_default();
+                    return Err(anyhow::anyhow!("{} failed: {}", self.settings.name, message));
+                }
+                _ => {}
+            }
+            Ok(())
+        })?;
+
+        if !done {
+            return Err(ended_early(&self.settings));
+        }
+        Ok(reply)
+    }
+}
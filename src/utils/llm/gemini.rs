@@ -0,0 +1,89 @@
+use super::usage::Usage;
+use super::{send_json, Completion, LlmProvider, ProviderSettings};
+use anyhow::Result;
+use serde_json::json;
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiResponse {
+    candidates: Vec<GeminiCandidate>,
+    usage_metadata: Option<GeminiUsage>,
+}
+
+#[derive(serde::Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct GeminiUsage {
+    prompt_token_count: u64,
+    candidates_token_count: u64,
+}
+
+#[derive(serde::Deserialize)]
+struct GeminiCandidate {
+    content: GeminiContent,
+}
+
+#[derive(serde::Deserialize)]
+struct GeminiContent {
+    parts: Vec<GeminiPart>,
+}
+
+#[derive(serde::Deserialize)]
+struct GeminiPart {
+    text: String,
+}
+
+/// The Google Gemini generateContent API
+pub struct Gemini {
+    settings: ProviderSettings,
+}
+
+impl Gemini {
+    pub fn new(settings: ProviderSettings) -> Self {
+        Gemini { settings }
+    }
+}
+
+impl LlmProvider for Gemini {
+    fn settings(&self) -> &ProviderSettings {
+        &self.settings
+    }
+
+    fn complete(&self, system_prompt: &str, user_prompt: &str) -> Result<Completion> {
+        let mut generation_config = json!({
+            "response_mime_type": "application/json",
+            "maxOutputTokens": self.settings.max_tokens,
+        });
+        if let Some(temperature) = self.settings.temperature {
+            generation_config["temperature"] = json!(temperature);
+        }
+
+        let mut request = self.settings.client()?
+            .post(self.settings.url(&format!("models/{}:generateContent", self.settings.model)));
+        if let Some(api_key) = &self.settings.api_key {
+            request = request.header("x-goog-api-key", api_key);
+        }
+        let body = json!({
+            "system_instruction": { "parts": [{"text": system_prompt}] },
+            "contents": [{
+                "role": "user",
+                "parts": [{"text": user_prompt}]
+            }],
+            "generationConfig": generation_config,
+        });
+        let response: GeminiResponse = send_json(&self.settings, request, &body)?;
+
+        if let Some(candidate) = response.candidates.first() {
+            if let Some(part) = candidate.content.parts.first() {
+                let usage = response.usage_metadata.as_ref().map(|usage| Usage {
+                    input_tokens: usage.prompt_token_count,
+                    output_tokens: usage.candidates_token_count,
+                });
+                Ok(Completion { text: part.text.clone(), usage })
+            } else {
+                Err(anyhow::anyhow!("Empty content from {}", self.settings.name))
+            }
+        } else {
+            Err(anyhow::anyhow!("No suitable response from {}", self.settings.name))
+        }
+    }
+}
@@ -0,0 +1,187 @@
+//! A local HTTP server for provider tests. It answers each connection with the
+//! next scripted reply, closes it, and records the requests it received.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// A request as the server received it
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    /// Header names in lowercase
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+
+    pub fn json(&self) -> serde_json::Value {
+        serde_json::from_str(&self.body).unwrap()
+    }
+}
+
+/// One scripted response
+#[derive(Debug, Clone)]
+pub struct Reply {
+    status: u16,
+    headers: Vec<(String, String)>,
+    /// Written one at a time with a flush in between; more than one part is
+    /// sent with chunked encoding, as streaming APIs do
+    parts: Vec<String>,
+    delay: Duration,
+    /// Close the connection after the parts without ending the body
+    disconnect: bool,
+}
+
+impl Reply {
+    pub fn json(status: u16, body: &str) -> Self {
+        Reply {
+            status,
+            headers: vec![("Content-Type".to_string(), "application/json".to_string())],
+            parts: vec![body.to_string()],
+            delay: Duration::ZERO,
+            disconnect: false,
+        }
+    }
+
+    /// A `text/event-stream` body sent as the given pieces
+    pub fn stream(parts: &[&str]) -> Self {
+        Reply {
+            status: 200,
+            headers: vec![("Content-Type".to_string(), "text/event-stream".to_string())],
+            parts: parts.iter().map(|part| part.to_string()).collect(),
+            delay: Duration::ZERO,
+            disconnect: false,
+        }
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Wait this long before answering
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Drop the connection once the parts are sent, in the middle of the body
+    pub fn disconnect(mut self) -> Self {
+        self.disconnect = true;
+        self
+    }
+}
+
+pub struct MockServer {
+    pub url: String,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl MockServer {
+    /// Serve `replies` in order, one per connection
+    pub fn start(replies: Vec<Reply>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        let received = Arc::clone(&requests);
+        thread::spawn(move || {
+            for reply in replies {
+                let Ok((stream, _)) = listener.accept() else {
+                    return;
+                };
+                // Read before accepting the next connection, so requests are recorded in order
+                if let Some(request) = read_request(&stream) {
+                    received.lock().unwrap().push(request);
+                }
+                // A delayed reply must not hold up the next connection, e.g. a retry after a timeout
+                thread::spawn(move || {
+                    // The client may have given up already
+                    let _ = write_reply(stream, &reply);
+                });
+            }
+        });
+
+        MockServer { url, requests }
+    }
+
+    /// Requests received so far
+    pub fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+fn read_request(stream: &TcpStream) -> Option<Request> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line).ok()?;
+    let mut start = line.split_whitespace();
+    let method = start.next()?.to_string();
+    let path = start.next()?.to_string();
+
+    let mut headers = Vec::new();
+    loop {
+        line.clear();
+        reader.read_line(&mut line).ok()?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let (name, value) = header.split_once(':')?;
+        headers.push((name.trim().to_lowercase(), value.trim().to_string()));
+    }
+
+    let length = headers
+        .iter()
+        .find(|(name, _)| name == "content-length")
+        .and_then(|(_, value)| value.parse().ok())
+        .unwrap_or(0);
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).ok()?;
+
+    Some(Request { method, path, headers, body: String::from_utf8_lossy(&body).to_string() })
+}
+
+fn write_reply(mut stream: TcpStream, reply: &Reply) -> std::io::Result<()> {
+    thread::sleep(reply.delay);
+
+    let chunked = reply.parts.len() > 1 || reply.disconnect;
+    let mut head = format!("HTTP/1.1 {} Mock\r\nConnection: close\r\n", reply.status);
+    for (name, value) in &reply.headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    if chunked {
+        head.push_str("Transfer-Encoding: chunked\r\n\r\n");
+    } else {
+        head.push_str(&format!("Content-Length: {}\r\n\r\n", reply.parts.concat().len()));
+    }
+    stream.write_all(head.as_bytes())?;
+    stream.flush()?;
+
+    for part in &reply.parts {
+        if chunked {
+            stream.write_all(format!("{:x}\r\n{}\r\n", part.len(), part).as_bytes())?;
+        } else {
+            stream.write_all(part.as_bytes())?;
+        }
+        stream.flush()?;
+        // Lets the client read each part on its own
+        thread::sleep(Duration::from_millis(20));
+    }
+
+    if reply.disconnect {
+        return stream.shutdown(std::net::Shutdown::Both);
+    }
+    if chunked {
+        stream.write_all(b"0\r\n\r\n")?;
+    }
+    stream.flush()
+}
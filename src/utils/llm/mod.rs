@@ -0,0 +1,881 @@
+pub mod anthropic;
+pub mod gemini;
+pub mod ollama;
+pub mod openai;
+pub mod stream;
+pub mod usage;
+
+#[cfg(test)]
+mod mock;
+
+use crate::app::AppContext;
+use crate::cli::ProviderArgs;
+use crate::config::model::{LlmConfig, ProviderKind};
+use crate::state::store::{LlmCacheEntry, StateStore};
+use crate::utils::redact::{redact_secrets, Redactions, Redactor};
+use crate::utils::trace;
+use anyhow::{Context, Result};
+use dialoguer::{theme::ColorfulTheme, Confirm};
+use rand::Rng;
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use reqwest::header::RETRY_AFTER;
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::io::{self, IsTerminal};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use stream::FieldPrinter;
+use usage::Usage;
+
+/// Providers tried, in order, when none is configured or requested
+const AUTO_PROVIDERS: &[&str] = &["openai", "anthropic", "gemini"];
+
+/// A chat API that answers with JSON
+pub trait LlmProvider {
+    fn settings(&self) -> &ProviderSettings;
+
+    /// Send the prompts and return the model's reply
+    fn complete(&self, system_prompt: &str, user_prompt: &str) -> Result<Completion>;
+
+    /// Like `complete`, passing the reply to `on_text` piece by piece as it
+    /// arrives. Providers without streaming pass the whole reply at once.
+    fn stream(&self, system_prompt: &str, user_prompt: &str, on_text: &mut dyn FnMut(&str)) -> Result<Completion> {
+        let completion = self.complete(system_prompt, user_prompt)?;
+        on_text(&completion.text);
+        Ok(completion)
+    }
+}
+
+/// A model's reply text and the tokens it used, when the provider reports them
+#[derive(Debug, Clone, Default)]
+pub struct Completion {
+    pub text: String,
+    pub usage: Option<Usage>,
+}
+
+/// A provider's configuration with defaults filled in and the API key looked up
+#[derive(Debug, Clone)]
+pub struct ProviderSettings {
+    pub name: String,
+    pub kind: ProviderKind,
+    pub model: String,
+    pub base_url: String,
+    pub api_key: Option<String>,
+    pub timeout: Duration,
+    pub temperature: Option<f64>,
+    pub max_tokens: u32,
+    /// Retries for rate-limited, failed (5xx) or timed-out requests
+    pub max_retries: u32,
+    /// US dollars per million input tokens, when known
+    pub input_price: Option<f64>,
+    /// US dollars per million output tokens, when known
+    pub output_price: Option<f64>,
+}
+
+impl ProviderSettings {
+    pub fn client(&self) -> Result<Client> {
+        Client::builder()
+            .timeout(self.timeout)
+            .build()
+            .context("Failed to create HTTP client")
+    }
+
+    /// `base_url` joined with an API path
+    pub fn url(&self, path: &str) -> String {
+        format!("{}/{}", self.base_url.trim_end_matches('/'), path.trim_start_matches('/'))
+    }
+}
+
+/// Defaults for a kind of provider: model, base URL, API key variable and keyring entry
+fn kind_defaults(kind: ProviderKind) -> (&'static str, &'static str, Option<&'static str>, Option<&'static str>) {
+    match kind {
+        ProviderKind::OpenAi => ("gpt-4o", "https://api.openai.com/v1", Some("OPENAI_API_KEY"), Some("openai_api_key")),
+        ProviderKind::Anthropic => (
+            "claude-3-5-sonnet-latest",
+            "https://api.anthropic.com/v1",
+            Some("ANTHROPIC_API_KEY"),
+            Some("anthropic_api_key"),
+        ),
+        ProviderKind::Gemini => (
+            "gemini-2.0-flash",
+            "https://generativelanguage.googleapis.com/v1beta",
+            Some("GEMINI_API_KEY"),
+            Some("gemini_api_key"),
+        ),
+        ProviderKind::Ollama => ("llama3.1", "http://localhost:11434", None, None),
+    }
+}
+
+fn built_in_kind(name: &str) -> Option<ProviderKind> {
+    match name {
+        "openai" => Some(ProviderKind::OpenAi),
+        "anthropic" => Some(ProviderKind::Anthropic),
+        "gemini" => Some(ProviderKind::Gemini),
+        "ollama" => Some(ProviderKind::Ollama),
+        _ => None,
+    }
+}
+
+/// Settings for a named provider. Built-in providers need no configuration;
+/// other names must be declared in `[llm.providers.<name>]` with a `kind`.
+/// API keys default to the kind's variables only for the built-in names, so a
+/// custom OpenAI-compatible endpoint does not pick up the OpenAI key.
+pub fn resolve(config: &LlmConfig, name: &str, model: Option<&str>) -> Result<ProviderSettings> {
+    let entry = config.providers.get(name).cloned().unwrap_or_default();
+    let built_in = built_in_kind(name);
+    let kind = entry.kind.or(built_in).ok_or_else(|| {
+        if config.providers.contains_key(name) {
+            anyhow::anyhow!("LLM provider '{}' needs a `kind` (openai, anthropic, gemini or ollama)", name)
+        } else {
+            anyhow::anyhow!(
+                "Unknown LLM provider '{}'. Use openai, anthropic, gemini or ollama, or add [llm.providers.{}] to ~/.dev/config.toml",
+                name, name
+            )
+        }
+    })?;
+
+    let (default_model, default_url, default_env, default_secret) = kind_defaults(kind);
+    let (key_env, key_secret) = if entry.api_key_env.is_some() || entry.api_key_secret.is_some() || built_in.is_none() {
+        (entry.api_key_env.clone(), entry.api_key_secret.clone())
+    } else {
+        (default_env.map(str::to_string), default_secret.map(str::to_string))
+    };
+
+    let api_key = match (&key_env, &key_secret) {
+        (None, None) => None,
+        _ => Some(find_key(key_env.as_deref(), key_secret.as_deref()).ok_or_else(|| {
+            let mut sources = Vec::new();
+            if let Some(env) = &key_env {
+                sources.push(format!("set {}", env));
+            }
+            if let Some(secret) = &key_secret {
+                sources.push(format!("run `dev secrets add {}`", secret));
+            }
+            anyhow::anyhow!("No API key for LLM provider '{}': {}", name, sources.join(" or "))
+        })?),
+    };
+
+    let model = model.map(str::to_string).or(entry.model).unwrap_or_else(|| default_model.to_string());
+    // Like API keys, built-in prices only apply to the built-in names; local models are free
+    let built_in_prices = match (built_in, kind) {
+        (_, ProviderKind::Ollama) => Some((0.0, 0.0)),
+        (Some(_), _) => usage::built_in_prices(&model),
+        (None, _) => None,
+    };
+
+    Ok(ProviderSettings {
+        name: name.to_string(),
+        kind,
+        model,
+        base_url: entry.base_url.unwrap_or_else(|| default_url.to_string()),
+        api_key,
+        timeout: Duration::from_secs(entry.timeout_secs.unwrap_or(60)),
+        temperature: entry.temperature,
+        max_tokens: entry.max_tokens.unwrap_or(2048),
+        max_retries: entry.max_retries.unwrap_or(3),
+        input_price: entry.input_price.or(built_in_prices.map(|(input, _)| input)),
+        output_price: entry.output_price.or(built_in_prices.map(|(_, output)| output)),
+    })
+}
+
+fn find_key(env_var: Option<&str>, secret_key: Option<&str>) -> Option<String> {
+    if let Some(key) = env_var.and_then(|var| std::env::var(var).ok()).filter(|key| !key.is_empty()) {
+        return Some(key);
+    }
+    let entry = keyring::Entry::new("dev-cli", secret_key?).ok()?;
+    entry.get_password().ok()
+}
+
+/// Providers to try in order. With `--provider`, only that one; otherwise
+/// `[llm] provider` followed by `[llm] fallback`, or, when no provider is
+/// configured, every built-in provider with an API key.
+pub fn providers(config: &LlmConfig, args: &ProviderArgs) -> Result<Vec<Box<dyn LlmProvider>>> {
+    let model = args.model.as_deref();
+    let settings = if let Some(name) = args.provider.as_deref() {
+        vec![resolve(config, name, model)?]
+    } else if let Some(name) = config.provider.as_deref() {
+        let mut settings = vec![resolve(config, name, model)?];
+        for fallback in config.fallback.iter().filter(|fallback| fallback.as_str() != name) {
+            // A fallback without a key is skipped rather than failing the primary
+            match resolve(config, fallback, None) {
+                Ok(fallback) => settings.push(fallback),
+                Err(e) => log::debug!("Skipping fallback provider: {:#}", e),
+            }
+        }
+        settings
+    } else {
+        let settings: Vec<ProviderSettings> = AUTO_PROVIDERS
+            .iter()
+            .filter_map(|name| resolve(config, name, model).ok())
+            .collect();
+        if settings.is_empty() {
+            return Err(anyhow::anyhow!(
+                "No API key found. Set OPENAI_API_KEY, ANTHROPIC_API_KEY or GEMINI_API_KEY (or add them with `dev secrets add <key>`), \
+                 or choose a provider such as `--provider ollama`"
+            ));
+        }
+        settings
+    };
+
+    Ok(settings.into_iter().map(build).collect())
+}
+
+fn build(settings: ProviderSettings) -> Box<dyn LlmProvider> {
+    match settings.kind {
+        ProviderKind::OpenAi => Box::new(openai::OpenAi::new(settings)),
+        ProviderKind::Anthropic => Box::new(anthropic::Anthropic::new(settings)),
+        ProviderKind::Gemini => Box::new(gemini::Gemini::new(settings)),
+        ProviderKind::Ollama => Box::new(ollama::Ollama::new(settings)),
+    }
+}
+
+/// The JSON object a command expects back from the model
+pub trait LlmResponse: DeserializeOwned {
+    /// Shape of the object, added to the system prompt, e.g. `{"markdown": string}`
+    const SCHEMA: &'static str;
+}
+
+/// A request for `call_llm` or `stream_llm`
+pub struct Prompt<'a> {
+    /// The dev command making the call, e.g. "docs generate", for usage accounting
+    pub command: &'a str,
+    pub system: &'a str,
+    pub user: &'a str,
+    /// Project whose `.env` values are redacted; the current directory when unset
+    pub project_dir: Option<&'a Path>,
+    /// Neither read nor write the reply cache, for a fresh answer to a repeated prompt
+    pub no_cache: bool,
+}
+
+/// Where a call is made from: the configuration, the state DB holding the
+/// cache and usage, and the dev command to attribute usage to
+struct Session<'a> {
+    config: &'a LlmConfig,
+    state: &'a mut dyn StateStore,
+    command: &'a str,
+    /// An exceeded budget was already warned about
+    budget_warned: bool,
+}
+
+/// Ask the model and decode its reply as `T`. Secrets are redacted from the
+/// prompts first. A reply that does not match is sent back once for repair; if
+/// a provider still fails, the next one is tried. Replies are cached unless the
+/// prompt opts out, and usage is recorded under the prompt's command.
+pub fn call_llm<T: LlmResponse>(app_ctx: &mut AppContext, args: &ProviderArgs, prompt: &Prompt) -> Result<T> {
+    let mut session = Session { config: &app_ctx.config.llm, state: app_ctx.state.as_mut(), command: prompt.command, budget_warned: false };
+    call(&mut session, args, prompt, None).map(|(response, _)| response)
+}
+
+/// Like `call_llm`, but streams the reply and prints the string field `field`
+/// of the response to stdout as it arrives. By the time this returns, the
+/// field's final value has been printed in full.
+pub fn stream_llm<T: LlmResponse>(app_ctx: &mut AppContext, args: &ProviderArgs, prompt: &Prompt, field: &str) -> Result<T> {
+    let mut session = Session { config: &app_ctx.config.llm, state: app_ctx.state.as_mut(), command: prompt.command, budget_warned: false };
+    let mut printer = FieldPrinter::new(field);
+    let (response, value) = call(&mut session, args, prompt, Some(&mut printer))?;
+    printer.finish(value[field].as_str().unwrap_or_default())?;
+    Ok(response)
+}
+
+fn call<T: LlmResponse>(
+    session: &mut Session,
+    args: &ProviderArgs,
+    prompt: &Prompt,
+    mut printer: Option<&mut FieldPrinter>,
+) -> Result<(T, serde_json::Value)> {
+    let providers = providers(session.config, args)?;
+    let (system_prompt, user_prompt) = redact_prompt(session, &providers, args, prompt)?;
+    let system_prompt = format!("{}\n\nRespond with a single JSON object of this shape and nothing else:\n{}", system_prompt, T::SCHEMA);
+    let user_prompt = user_prompt.as_str();
+
+    let mut last_error = None;
+    for (index, provider) in providers.iter().enumerate() {
+        match ask::<T>(session, provider.as_ref(), &system_prompt, user_prompt, prompt.no_cache, printer.as_deref_mut()) {
+            Ok(response) => return Ok(response),
+            Err(e) if e.is::<usage::BudgetExceeded>() => return Err(e),
+            Err(e) => {
+                if let Some(next) = providers.get(index + 1) {
+                    log::warn!("LLM provider {} failed: {:#}. Trying {}", provider.settings().name, e, next.settings().name);
+                }
+                last_error = Some(e);
+            }
+        }
+    }
+    Err(last_error.unwrap())
+}
+
+fn ask<T: LlmResponse>(
+    session: &mut Session,
+    provider: &dyn LlmProvider,
+    system_prompt: &str,
+    user_prompt: &str,
+    no_cache: bool,
+    mut printer: Option<&mut FieldPrinter>,
+) -> Result<(T, serde_json::Value)> {
+    let settings = provider.settings();
+    let key = cache_key(settings, system_prompt, user_prompt);
+    let cached = if no_cache { None } else { cached_reply(session, &key)? };
+    if let Some(reply) = cached {
+        // A reply is only cached once it decoded, but the schema may have changed since
+        if let Ok(response) = decode::<T>(&reply) {
+            log::debug!("LLM cache hit for {} ({})", settings.name, settings.model);
+            if let Some(printer) = printer {
+                printer.restart();
+                printer.push(&reply);
+            }
+            usage::record(session.state, session.command, settings, Usage::default(), true)?;
+            return Ok(response);
+        }
+    }
+
+    let reply = complete(session, provider, system_prompt, user_prompt, printer.as_deref_mut())?;
+    let (response, reply) = match decode::<T>(&reply) {
+        Ok(response) => (response, reply),
+        Err(e) => {
+            log::info!("Invalid response from {} ({:#}); asking it to repair it", settings.name, e);
+            let repair_prompt = format!(
+                "{}\n\nYour previous reply could not be used: {:#}\nPrevious reply:\n{}\n\nReply again with only a JSON object of the requested shape.",
+                user_prompt, e, reply
+            );
+            let reply = complete(session, provider, system_prompt, &repair_prompt, printer)?;
+            let response = decode::<T>(&reply).context("The response was still invalid after asking for a repair")?;
+            (response, reply)
+        }
+    };
+
+    if !no_cache && session.config.cache_ttl_hours > 0 {
+        session.state.save_llm_cache(LlmCacheEntry {
+            key,
+            provider: settings.name.clone(),
+            model: settings.model.clone(),
+            reply,
+            created_at: chrono::Local::now().to_rfc3339(),
+        })?;
+    }
+    Ok(response)
+}
+
+/// The shared redaction pass run before anything is sent: values from the
+/// project's `.env` files, the keyring and the providers' API keys, and anything
+/// matching a secret pattern, are replaced with placeholders. With
+/// `--no-redact`, the user can confirm sending them as they are.
+fn redact_prompt(
+    session: &Session,
+    providers: &[Box<dyn LlmProvider>],
+    args: &ProviderArgs,
+    prompt: &Prompt,
+) -> Result<(String, String)> {
+    let dir = match prompt.project_dir {
+        Some(dir) => dir.to_path_buf(),
+        None => std::env::current_dir()?,
+    };
+    let api_keys = providers.iter().filter_map(|provider| provider.settings().api_key.clone());
+    let redactor = Redactor::for_dir(&dir)
+        .with_values("keyring-value", keyring_values(session)?)
+        .with_values("api-key", api_keys);
+
+    let mut redactions = Redactions::default();
+    let system_prompt = redactor.redact(prompt.system, &mut redactions);
+    let user_prompt = redactor.redact(prompt.user, &mut redactions);
+    if redactions.is_empty() {
+        return Ok((system_prompt, user_prompt));
+    }
+
+    if args.no_redact {
+        eprintln!("⚠️  The request contains {} secret(s): {}", redactions.total(), redactions);
+        if !io::stderr().is_terminal() {
+            return Err(anyhow::anyhow!("--no-redact asks for confirmation, so it needs a terminal"));
+        }
+        let confirmed = Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt("Send them to the LLM unredacted?")
+            .default(false)
+            .interact()?;
+        if confirmed {
+            return Ok((prompt.system.to_string(), prompt.user.to_string()));
+        }
+    }
+    eprintln!("🔒 Redacted {} secret(s) before sending: {}", redactions.total(), redactions);
+    Ok((system_prompt, user_prompt))
+}
+
+/// Values of the dev-cli keyring entries dev knows of: ones added with
+/// `dev secrets add` and the providers' key entries
+fn keyring_values(session: &Session) -> Result<Vec<String>> {
+    let mut names = session.state.list_secret_names()?;
+    names.extend(
+        AUTO_PROVIDERS
+            .iter()
+            .filter_map(|name| built_in_kind(name))
+            .filter_map(|kind| kind_defaults(kind).3)
+            .map(str::to_string),
+    );
+    names.extend(session.config.providers.values().filter_map(|provider| provider.api_key_secret.clone()));
+    names.sort();
+    names.dedup();
+
+    Ok(names
+        .iter()
+        .filter_map(|name| keyring::Entry::new("dev-cli", name).ok()?.get_password().ok())
+        .collect())
+}
+
+/// Identifies a request by everything that affects the reply
+fn cache_key(settings: &ProviderSettings, system_prompt: &str, user_prompt: &str) -> String {
+    let request = json!([
+        settings.name,
+        settings.base_url,
+        settings.model,
+        settings.temperature,
+        settings.max_tokens,
+        system_prompt,
+        user_prompt,
+    ]);
+    let mut hasher = Sha256::new();
+    hasher.update(request.to_string().as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+/// The cached reply for `key`, unless the cache is off or the entry is older than the TTL
+fn cached_reply(session: &Session, key: &str) -> Result<Option<String>> {
+    let ttl_hours = session.config.cache_ttl_hours;
+    if ttl_hours == 0 {
+        return Ok(None);
+    }
+    let Some(entry) = session.state.get_llm_cache(key).context("Failed to check the LLM cache")? else {
+        return Ok(None);
+    };
+
+    let fresh = chrono::DateTime::parse_from_rfc3339(&entry.created_at)
+        .map(|created| chrono::Local::now().signed_duration_since(created).num_hours() < ttl_hours as i64)
+        .unwrap_or(false);
+    Ok(fresh.then_some(entry.reply))
+}
+
+/// The reply as `T`, along with the JSON it was decoded from
+fn decode<T: LlmResponse>(reply: &str) -> Result<(T, serde_json::Value)> {
+    let value = parse_json_reply(reply)?;
+    let response = serde_json::from_value(value.clone()).context("The response does not have the expected fields")?;
+    Ok((response, value))
+}
+
+/// One model call, logged, traced and counted. With a printer, the reply is streamed into it.
+fn complete(
+    session: &mut Session,
+    provider: &dyn LlmProvider,
+    system_prompt: &str,
+    user_prompt: &str,
+    printer: Option<&mut FieldPrinter>,
+) -> Result<String> {
+    session.budget_warned |= usage::check_budget(session.state, &session.config.budget, session.budget_warned)?;
+
+    let settings = provider.settings();
+    log::info!("Calling LLM provider {} ({})", settings.name, settings.model);
+
+    let started = Instant::now();
+    let streamed = printer.is_some();
+    let result = match printer {
+        Some(printer) => {
+            printer.restart();
+            provider.stream(system_prompt, user_prompt, &mut |text| printer.push(text))
+        }
+        None => provider.complete(system_prompt, user_prompt),
+    };
+    let duration_ms = started.elapsed().as_millis() as u64;
+
+    let mut fields = json!({
+        "provider": settings.name,
+        "model": settings.model,
+        "url": settings.base_url,
+        "streamed": streamed,
+        "system_prompt": redact_secrets(system_prompt),
+        "user_prompt": redact_secrets(user_prompt),
+        "duration_ms": duration_ms,
+    });
+    match &result {
+        Ok(completion) => {
+            log::debug!("LLM call finished in {} ms", duration_ms);
+            fields["response"] = json!(redact_secrets(&completion.text));
+            if let Some(usage) = completion.usage {
+                fields["input_tokens"] = json!(usage.input_tokens);
+                fields["output_tokens"] = json!(usage.output_tokens);
+            }
+        }
+        Err(e) => {
+            log::debug!("LLM call failed after {} ms: {:#}", duration_ms, e);
+            fields["error"] = json!(format!("{:#}", e));
+        }
+    }
+    trace::record("llm", fields);
+
+    let completion = result?;
+    usage::record(session.state, session.command, settings, completion.usage.unwrap_or_default(), false)?;
+    Ok(completion.text)
+}
+
+/// Send a JSON request and decode the JSON response, logging both (redacted) at
+/// trace level
+pub fn send_json<T: DeserializeOwned>(settings: &ProviderSettings, request: RequestBuilder, body: &serde_json::Value) -> Result<T> {
+    let raw = send(settings, request, body)?.text()?;
+    log::trace!("{} response: {}", settings.name, redact_secrets(&raw));
+    serde_json::from_str(&raw).with_context(|| format!("Unexpected response from {}", settings.name))
+}
+
+/// Send a JSON request and return the successful response. 429 and 5xx
+/// responses and timeouts are retried with exponential backoff and jitter,
+/// waiting at least as long as `Retry-After` asks.
+fn send(settings: &ProviderSettings, request: RequestBuilder, body: &serde_json::Value) -> Result<Response> {
+    log::trace!("{} request: {}", settings.name, redact_secrets(&body.to_string()));
+    let request = request.json(body);
+
+    let mut attempt = 0;
+    loop {
+        let this_try = request.try_clone().context("Failed to build LLM request")?;
+        let retries_left = attempt < settings.max_retries;
+
+        let response = match this_try.send() {
+            Ok(response) => response,
+            Err(e) if e.is_timeout() && retries_left => {
+                let delay = backoff(attempt, None);
+                log::warn!("{} timed out; retrying in {:.1}s", settings.name, delay.as_secs_f64());
+                std::thread::sleep(delay);
+                attempt += 1;
+                continue;
+            }
+            Err(e) => return Err(e).with_context(|| format!("Request to {} failed", settings.name)),
+        };
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+
+        let retryable = status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error();
+        let retry_after = response.headers().get(RETRY_AFTER).and_then(|value| value.to_str().ok()).and_then(parse_retry_after);
+        let raw = response.text().unwrap_or_default();
+        log::trace!("{} response ({}): {}", settings.name, status, redact_secrets(&raw));
+
+        if !retryable || !retries_left || retry_after.is_some_and(|wait| wait > MAX_RETRY_AFTER) {
+            let detail: String = redact_secrets(raw.trim()).chars().take(300).collect();
+            return Err(anyhow::anyhow!("{} returned {}: {}", settings.name, status, detail));
+        }
+
+        let delay = backoff(attempt, retry_after);
+        log::warn!("{} returned {}; retrying in {:.1}s", settings.name, status, delay.as_secs_f64());
+        std::thread::sleep(delay);
+        attempt += 1;
+    }
+}
+
+/// Longest `Retry-After` worth waiting for; beyond it the call fails instead
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// 0.5s, 1s, 2s, ... (capped at 8s) scaled by a random factor, but never
+/// shorter than the server's `Retry-After`
+fn backoff(attempt: u32, retry_after: Option<Duration>) -> Duration {
+    let base = Duration::from_millis(500).saturating_mul(1 << attempt.min(4)).min(Duration::from_secs(8));
+    let jittered = base.mul_f64(rand::thread_rng().gen_range(0.5..=1.0));
+    retry_after.map_or(jittered, |wait| wait.max(jittered))
+}
+
+/// `Retry-After` as seconds or an HTTP date
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    if let Ok(seconds) = value.trim().parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value.trim()).ok()?;
+    let wait = date.signed_duration_since(chrono::Utc::now()).to_std().unwrap_or(Duration::ZERO);
+    Some(wait)
+}
+
+/// Parse a model reply as JSON, allowing for a surrounding Markdown code fence
+pub fn parse_json_reply(text: &str) -> Result<serde_json::Value> {
+    let text = text.trim();
+    let text = text
+        .strip_prefix("```json")
+        .or_else(|| text.strip_prefix("```"))
+        .and_then(|rest| rest.strip_suffix("```"))
+        .unwrap_or(text);
+    serde_json::from_str(text.trim()).context("The model did not return valid JSON")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::mock::{MockServer, Reply};
+    use super::*;
+    use crate::config::model::ProviderConfig;
+    use crate::state::SqliteStore;
+    use std::fs;
+    use std::path::PathBuf;
+
+    const OPENAI_REPLY: &str = r#"{"choices": [{"message": {"content": "{\"answer\": \"42\"}"}}], "usage": {"prompt_tokens": 10, "completion_tokens": 5}}"#;
+
+    #[derive(serde::Deserialize)]
+    struct Answer {
+        answer: String,
+    }
+
+    impl LlmResponse for Answer {
+        const SCHEMA: &'static str = r#"{"answer": string}"#;
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("dev-llm-test-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// A custom provider of `kind` pointed at the mock server, with short retries
+    fn provider_config(kind: ProviderKind, server: &MockServer) -> ProviderConfig {
+        ProviderConfig {
+            kind: Some(kind),
+            model: Some("test-model".to_string()),
+            base_url: Some(server.url.clone()),
+            timeout_secs: Some(5),
+            max_retries: Some(2),
+            ..Default::default()
+        }
+    }
+
+    fn settings(kind: ProviderKind, server: &MockServer) -> ProviderSettings {
+        let mut config = LlmConfig::default();
+        config.providers.insert("mock".to_string(), provider_config(kind, server));
+        let mut settings = resolve(&config, "mock", None).unwrap();
+        settings.api_key = Some("sk-test".to_string());
+        settings
+    }
+
+    fn complete_with(settings: ProviderSettings) -> Result<Completion> {
+        build(settings).complete("system", "user")
+    }
+
+    #[test]
+    fn base_url_override_is_used_for_each_kind() {
+        let server = MockServer::start(vec![Reply::json(200, OPENAI_REPLY)]);
+        let completion = complete_with(settings(ProviderKind::OpenAi, &server)).unwrap();
+        assert_eq!(completion.text, r#"{"answer": "42"}"#);
+        assert_eq!(completion.usage.map(|usage| (usage.input_tokens, usage.output_tokens)), Some((10, 5)));
+        let request = &server.requests()[0];
+        assert_eq!((request.method.as_str(), request.path.as_str()), ("POST", "/chat/completions"));
+        assert_eq!(request.header("authorization"), Some("Bearer sk-test"));
+        assert_eq!(request.json()["model"], "test-model");
+        assert_eq!(request.json()["messages"][1]["content"], "user");
+
+        let server = MockServer::start(vec![Reply::json(200, r#"{"content": [{"type": "text", "text": "hi"}]}"#)]);
+        assert_eq!(complete_with(settings(ProviderKind::Anthropic, &server)).unwrap().text, "hi");
+        let request = &server.requests()[0];
+        assert_eq!(request.path, "/messages");
+        assert_eq!(request.header("x-api-key"), Some("sk-test"));
+        assert_eq!(request.header("anthropic-version"), Some("2023-06-01"));
+
+        let server = MockServer::start(vec![Reply::json(200, r#"{"candidates": [{"content": {"parts": [{"text": "hi"}]}}]}"#)]);
+        assert_eq!(complete_with(settings(ProviderKind::Gemini, &server)).unwrap().text, "hi");
+        let request = &server.requests()[0];
+        assert_eq!(request.path, "/models/test-model:generateContent");
+        assert_eq!(request.header("x-goog-api-key"), Some("sk-test"));
+
+        let server = MockServer::start(vec![Reply::json(200, r#"{"message": {"content": "hi"}, "eval_count": 3}"#)]);
+        assert_eq!(complete_with(settings(ProviderKind::Ollama, &server)).unwrap().text, "hi");
+        assert_eq!(server.requests()[0].path, "/api/chat");
+    }
+
+    #[test]
+    fn rate_limits_and_server_errors_are_retried() {
+        let server = MockServer::start(vec![
+            Reply::json(503, "overloaded"),
+            Reply::json(429, "slow down").header("Retry-After", "0"),
+            Reply::json(200, OPENAI_REPLY),
+        ]);
+        complete_with(settings(ProviderKind::OpenAi, &server)).unwrap();
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[test]
+    fn retries_give_up_with_the_last_status() {
+        let server = MockServer::start(vec![
+            Reply::json(500, "boom"),
+            Reply::json(502, "bad gateway"),
+            Reply::json(500, "still broken"),
+            Reply::json(200, OPENAI_REPLY),
+        ]);
+        let error = complete_with(settings(ProviderKind::OpenAi, &server)).unwrap_err().to_string();
+        assert!(error.contains("mock returned 500"), "{}", error);
+        assert!(error.contains("still broken"), "{}", error);
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[test]
+    fn client_errors_fail_at_once_with_the_detail() {
+        let server = MockServer::start(vec![
+            Reply::json(401, r#"{"error": {"message": "Incorrect API key provided"}}"#),
+            Reply::json(200, OPENAI_REPLY),
+        ]);
+        let error = complete_with(settings(ProviderKind::OpenAi, &server)).unwrap_err().to_string();
+        assert!(error.contains("401"), "{}", error);
+        assert!(error.contains("Incorrect API key provided"), "{}", error);
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[test]
+    fn long_retry_after_fails_at_once() {
+        let server = MockServer::start(vec![
+            Reply::json(429, "quota").header("Retry-After", "3600"),
+            Reply::json(200, OPENAI_REPLY),
+        ]);
+        let error = complete_with(settings(ProviderKind::OpenAi, &server)).unwrap_err().to_string();
+        assert!(error.contains("429"), "{}", error);
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[test]
+    fn timeouts_are_retried() {
+        let server = MockServer::start(vec![
+            Reply::json(200, OPENAI_REPLY).delay(Duration::from_secs(2)),
+            Reply::json(200, OPENAI_REPLY),
+        ]);
+        let mut settings = settings(ProviderKind::OpenAi, &server);
+        settings.timeout = Duration::from_millis(300);
+        complete_with(settings).unwrap();
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[test]
+    fn unexpected_bodies_are_reported() {
+        let server = MockServer::start(vec![Reply::json(200, r#"{"object": "list"}"#)]);
+        let error = format!("{:#}", complete_with(settings(ProviderKind::OpenAi, &server)).unwrap_err());
+        assert!(error.starts_with("Unexpected response from mock"), "{}", error);
+    }
+
+    /// Stream from the provider, collecting the pieces passed to `on_text`
+    fn stream_with(settings: ProviderSettings) -> (Result<Completion>, Vec<String>) {
+        let mut pieces = Vec::new();
+        let result = build(settings).stream("system", "user", &mut |text| pieces.push(text.to_string()));
+        (result, pieces)
+    }
+
+    #[test]
+    fn openai_stream_joins_events_split_across_chunks() {
+        let server = MockServer::start(vec![Reply::stream(&[
+            "data: {\"choices\": [{\"delta\": {\"content\": \"Hel",
+            "lo\"}}]}\n",
+            "\ndata: {\"choices\": [{\"delta\": {\"content\": \" world\"}}]}\n\ndata: {\"choices\": [], ",
+            "\"usage\": {\"prompt_tokens\": 7, \"completion_tokens\": 2}}\n\n",
+            "data: [DONE]\n\n",
+        ])]);
+        let (result, pieces) = stream_with(settings(ProviderKind::OpenAi, &server));
+        let completion = result.unwrap();
+        assert_eq!(pieces, ["Hello", " world"]);
+        assert_eq!(completion.text, "Hello world");
+        assert_eq!(completion.usage.map(|usage| (usage.input_tokens, usage.output_tokens)), Some((7, 2)));
+        assert_eq!(server.requests()[0].json()["stream"], true);
+    }
+
+    #[test]
+    fn stream_without_final_event_ended_early() {
+        let server = MockServer::start(vec![Reply::stream(&[
+            "data: {\"choices\": [{\"delta\": {\"content\": \"Hel\"}}]}\n\n",
+            "data: {\"choices\": [{\"delta\": {\"content\": \"lo\"}}]}\n\n",
+        ])]);
+        let (result, pieces) = stream_with(settings(ProviderKind::OpenAi, &server));
+        assert_eq!(result.unwrap_err().to_string(), "The response from mock ended early");
+        assert_eq!(pieces, ["Hel", "lo"]);
+
+        let server = MockServer::start(vec![Reply::stream(&["{\"message\": {\"content\": \"Hi\"}}\n", "{\"message\": {\"content\": \"!\"}}\n"])]);
+        let (result, _) = stream_with(settings(ProviderKind::Ollama, &server));
+        assert_eq!(result.unwrap_err().to_string(), "The response from mock ended early");
+    }
+
+    #[test]
+    fn stream_disconnect_is_an_error() {
+        let server = MockServer::start(vec![Reply::stream(&[
+            "data: {\"choices\": [{\"delta\": {\"content\": \"Hel\"}}]}\n\n",
+            "data: {\"choices\": [{\"delta\": {\"con",
+        ])
+        .disconnect()]);
+        let (result, pieces) = stream_with(settings(ProviderKind::OpenAi, &server));
+        let error = format!("{:#}", result.unwrap_err());
+        assert!(error.starts_with("Failed to read the response from mock"), "{}", error);
+        assert_eq!(pieces, ["Hel"]);
+        // A stream that broke off is not retried, since part of it was already shown
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[test]
+    fn anthropic_stream_events() {
+        let server = MockServer::start(vec![Reply::stream(&[
+            "event: message_start\ndata: {\"type\": \"message_start\", \"message\": {\"usage\": {\"input_tokens\": 9}}}\n\n",
+            "event: content_block_delta\ndata: {\"type\": \"content_block_delta\", \"delta\": {\"text\": \"Hi\"}}\n",
+            "\nevent: ping\ndata: {\"type\": \"ping\"}\n\nevent: message_delta\ndata: {\"type\": \"message_delta\", \"usage\": {\"output_tokens\": 1}}\n\n",
+            "event: message_stop\ndata: {\"type\": \"message_stop\"}\n\n",
+        ])]);
+        let (result, pieces) = stream_with(settings(ProviderKind::Anthropic, &server));
+        let completion = result.unwrap();
+        assert_eq!(pieces, ["Hi"]);
+        assert_eq!(completion.usage.map(|usage| (usage.input_tokens, usage.output_tokens)), Some((9, 1)));
+
+        let server = MockServer::start(vec![Reply::stream(&[
+            "event: content_block_delta\ndata: {\"type\": \"content_block_delta\", \"delta\": {\"text\": \"Hi\"}}\n\n",
+            "event: error\ndata: {\"type\": \"error\", \"error\": {\"message\": \"Overloaded\"}}\n\n",
+        ])]);
+        let (result, _) = stream_with(settings(ProviderKind::Anthropic, &server));
+        assert_eq!(result.unwrap_err().to_string(), "mock failed: Overloaded");
+    }
+
+    #[test]
+    fn failing_provider_falls_back_and_invalid_replies_are_repaired() {
+        let dir = temp_dir("fallback");
+        let mut store = SqliteStore::open(&dir.join("state.db")).unwrap();
+        store.init().unwrap();
+
+        let primary = MockServer::start(vec![Reply::json(400, "bad request")]);
+        let secondary = MockServer::start(vec![
+            Reply::json(200, r#"{"choices": [{"message": {"content": "not json"}}]}"#),
+            Reply::json(200, OPENAI_REPLY),
+        ]);
+        let mut config = LlmConfig { provider: Some("primary".to_string()), fallback: vec!["secondary".to_string()], ..Default::default() };
+        config.providers.insert("primary".to_string(), provider_config(ProviderKind::OpenAi, &primary));
+        config.providers.insert("secondary".to_string(), provider_config(ProviderKind::OpenAi, &secondary));
+
+        let prompt = Prompt { command: "test", system: "system", user: "question", project_dir: Some(&dir), no_cache: false };
+        let mut session = Session { config: &config, state: &mut store, command: "test", budget_warned: false };
+        let (answer, _) = call::<Answer>(&mut session, &ProviderArgs::default(), &prompt, None).unwrap();
+        assert_eq!(answer.answer, "42");
+        assert_eq!(primary.requests().len(), 1);
+        let requests = secondary.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[1].json()["messages"][1]["content"].as_str().unwrap().contains("could not be used"));
+
+        // The same request is answered from the cache; both servers are out of replies
+        let (answer, _) = call::<Answer>(&mut session, &ProviderArgs::default(), &prompt, None).unwrap();
+        assert_eq!(answer.answer, "42");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn no_cache_neither_reads_nor_writes_the_cache() {
+        let dir = temp_dir("no-cache");
+        let mut store = SqliteStore::open(&dir.join("state.db")).unwrap();
+        store.init().unwrap();
+
+        let server = MockServer::start(vec![Reply::json(200, OPENAI_REPLY); 3]);
+        let mut config = LlmConfig { provider: Some("mock".to_string()), ..Default::default() };
+        config.providers.insert("mock".to_string(), provider_config(ProviderKind::OpenAi, &server));
+        let mut session = Session { config: &config, state: &mut store, command: "test", budget_warned: false };
+        let cached = Prompt { command: "test", system: "system", user: "question", project_dir: Some(&dir), no_cache: false };
+        let fresh = Prompt { no_cache: true, ..cached };
+
+        let mut ask = |prompt: &Prompt| call::<Answer>(&mut session, &ProviderArgs::default(), prompt, None).unwrap();
+        ask(&fresh);
+        // Nothing was written, so this asks again and fills the cache
+        ask(&cached);
+        assert_eq!(server.requests().len(), 2);
+        ask(&cached);
+        assert_eq!(server.requests().len(), 2);
+        // The cached reply is not read either
+        ask(&fresh);
+        assert_eq!(server.requests().len(), 3);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
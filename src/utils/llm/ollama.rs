@@ -0,0 +1,123 @@
+use super::stream::{ended_early, send_lines};
+use super::usage::Usage;
+use super::{send_json, Completion, LlmProvider, ProviderSettings};
+use anyhow::{Context, Result};
+use reqwest::blocking::RequestBuilder;
+use serde_json::json;
+
+#[derive(serde::Deserialize)]
+struct OllamaMessage {
+    content: String,
+}
+
+#[derive(serde::Deserialize)]
+struct OllamaResponse {
+    message: OllamaMessage,
+    #[serde(flatten)]
+    counts: OllamaCounts,
+}
+
+/// One line of a streamed response; the last one has the token counts
+#[derive(serde::Deserialize)]
+struct OllamaChunk {
+    message: Option<OllamaMessage>,
+    error: Option<String>,
+    /// Set on the last line
+    #[serde(default)]
+    done: bool,
+    #[serde(flatten)]
+    counts: OllamaCounts,
+}
+
+#[derive(serde::Deserialize)]
+struct OllamaCounts {
+    prompt_eval_count: Option<u64>,
+    eval_count: Option<u64>,
+}
+
+impl OllamaCounts {
+    fn usage(&self) -> Option<Usage> {
+        if self.prompt_eval_count.is_none() && self.eval_count.is_none() {
+            return None;
+        }
+        Some(Usage {
+            input_tokens: self.prompt_eval_count.unwrap_or(0),
+            output_tokens: self.eval_count.unwrap_or(0),
+        })
+    }
+}
+
+/// A local model served by Ollama
+pub struct Ollama {
+    settings: ProviderSettings,
+}
+
+impl Ollama {
+    pub fn new(settings: ProviderSettings) -> Self {
+        Ollama { settings }
+    }
+
+    fn request(&self, system_prompt: &str, user_prompt: &str, stream: bool) -> Result<(RequestBuilder, serde_json::Value)> {
+        let mut options = json!({ "num_predict": self.settings.max_tokens });
+        if let Some(temperature) = self.settings.temperature {
+            options["temperature"] = json!(temperature);
+        }
+
+        let body = json!({
+            "model": self.settings.model,
+            "messages": [
+                {"role": "system", "content": system_prompt},
+                {"role": "user", "content": user_prompt}
+            ],
+            "format": "json",
+            "stream": stream,
+            "options": options,
+        });
+        let request = self.settings.client()?.post(self.settings.url("api/chat"));
+        Ok((request, body))
+    }
+}
+
+impl LlmProvider for Ollama {
+    fn settings(&self) -> &ProviderSettings {
+        &self.settings
+    }
+
+    fn complete(&self, system_prompt: &str, user_prompt: &str) -> Result<Completion> {
+        let (request, body) = self.request(system_prompt, user_prompt, false)?;
+        let response: OllamaResponse = send_json(&self.settings, request, &body)?;
+
+        Ok(Completion { usage: response.counts.usage(), text: response.message.content })
+    }
+
+    fn stream(&self, system_prompt: &str, user_prompt: &str, on_text: &mut dyn FnMut(&str)) -> Result<Completion> {
+        // Newline-delimited JSON objects, each with the next piece of the message
+        let (request, body) = self.request(system_prompt, user_prompt, true)?;
+        let mut reply = Completion::default();
+        let mut done = false;
+        send_lines(&self.settings, request, &body, |line| {
+            if line.trim().is_empty() {
+                return Ok(());
+            }
+            let chunk: OllamaChunk = serde_json::from_str(line)
+                .with_context(|| format!("Unexpected response from {}", self.settings.name))?;
+            if let Some(error) = chunk.error {
+                return Err(anyhow::anyhow!("{} failed: {}", self.settings.name, error));
+            }
+            if let Some(message) = chunk.message {
+                on_text(&message.content);
+                reply.text.push_str(&message.content);
+            }
+            if let Some(usage) = chunk.counts.usage() {
+                reply.usage = Some(usage);
+            }
+            done |= chunk.done;
+            Ok(())
+        })?;
+
+        if !done {
+            return Err(ended_early(&self.settings));
+        }
+        Ok(reply)
+    }
+}
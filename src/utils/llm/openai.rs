@@ -0,0 +1,137 @@
+use super::stream::{ended_early, send_lines, sse_data};
+use super::usage::Usage;
+use super::{send_json, Completion, LlmProvider, ProviderSettings};
+use anyhow::{Context, Result};
+use reqwest::blocking::RequestBuilder;
+use serde_json::json;
+
+#[derive(serde::Deserialize)]
+struct OpenAIChoice {
+    message: OpenAIMessage,
+}
+
+#[derive(serde::Deserialize)]
+struct OpenAIMessage {
+    content: String,
+}
+
+#[derive(serde::Deserialize)]
+struct OpenAIResponse {
+    choices: Vec<OpenAIChoice>,
+    usage: Option<OpenAIUsage>,
+}
+
+#[derive(serde::Deserialize, Clone, Copy)]
+struct OpenAIUsage {
+    prompt_tokens: u64,
+    completion_tokens: u64,
+}
+
+impl From<OpenAIUsage> for Usage {
+    fn from(usage: OpenAIUsage) -> Self {
+        Usage { input_tokens: usage.prompt_tokens, output_tokens: usage.completion_tokens }
+    }
+}
+
+/// One server-sent event of a streamed response
+#[derive(serde::Deserialize)]
+struct OpenAIChunk {
+    #[serde(default)]
+    choices: Vec<OpenAIChunkChoice>,
+    /// Only on the last event, when asked for with `stream_options`
+    usage: Option<OpenAIUsage>,
+}
+
+#[derive(serde::Deserialize)]
+struct OpenAIChunkChoice {
+    delta: OpenAIDelta,
+}
+
+#[derive(serde::Deserialize)]
+struct OpenAIDelta {
+    content: Option<String>,
+}
+
+/// The OpenAI chat completions API, or any server that implements it
+pub struct OpenAi {
+    settings: ProviderSettings,
+}
+
+impl OpenAi {
+    pub fn new(settings: ProviderSettings) -> Self {
+        OpenAi { settings }
+    }
+
+    fn request(&self, system_prompt: &str, user_prompt: &str) -> Result<(RequestBuilder, serde_json::Value)> {
+        let mut body = json!({
+            "model": self.settings.model,
+            "messages": [
+                {"role": "system", "content": system_prompt},
+                {"role": "user", "content": user_prompt}
+            ],
+            "response_format": { "type": "json_object" },
+            "max_tokens": self.settings.max_tokens,
+        });
+        if let Some(temperature) = self.settings.temperature {
+            body["temperature"] = json!(temperature);
+        }
+
+        let mut request = self.settings.client()?.post(self.settings.url("chat/completions"));
+        if let Some(api_key) = &self.settings.api_key {
+            request = request.bearer_auth(api_key);
+        }
+        Ok((request, body))
+    }
+}
+
+impl LlmProvider for OpenAi {
+    fn settings(&self) -> &ProviderSettings {
+        &self.settings
+    }
+
+    fn complete(&self, system_prompt: &str, user_prompt: &str) -> Result<Completion> {
+        let (request, body) = self.request(system_prompt, user_prompt)?;
+        let response: OpenAIResponse = send_json(&self.settings, request, &body)?;
+
+        match response.choices.first() {
+            Some(choice) => Ok(Completion {
+                text: choice.message.content.clone(),
+                usage: response.usage.map(Usage::from),
+            }),
+            None => Err(anyhow::anyhow!("No response from {}", self.settings.name)),
+        }
+    }
+
+    fn stream(&self, system_prompt: &str, user_prompt: &str, on_text: &mut dyn FnMut(&str)) -> Result<Completion> {
+        let (request, mut body) = self.request(system_prompt, user_prompt)?;
+        body["stream"] = json!(true);
+        body["stream_options"] = json!({ "include_usage": true });
+
+        let mut reply = Completion::default();
+        let mut done = false;
+        send_lines(&self.settings, request, &body, |line| {
+            let Some(data) = sse_data(line) else {
+                return Ok(());
+            };
+            if data == "[DONE]" {
+                done = true;
+                return Ok(());
+            }
+            let chunk: OpenAIChunk = serde_json::from_str(data)
+                .with_context(|| format!("Unexpected response from {}", self.settings.name))?;
+            if let Some(text) = chunk.choices.first().and_then(|choice| choice.delta.content.as_deref()) {
+                on_text(text);
+                reply.text.push_str(text);
+            }
+            if let Some(usage) = chunk.usage {
+                reply.usage = Some(usage.into());
+            }
+            Ok(())
+        })?;
+
+        if !done {
+            return Err(ended_early(&self.settings));
+        }
+        Ok(reply)
+    }
+}
@@ -0,0 +1,173 @@
+use super::ProviderSettings;
+use crate::utils::redact::redact_secrets;
+use anyhow::{Context, Result};
+use regex::Regex;
+use reqwest::blocking::RequestBuilder;
+use std::io::{self, BufRead, BufReader, Write};
+
+/// Send a streaming request and pass each line of the response body to `on_line`
+/// as it arrives. Failed requests are retried like `send_json`, but a stream
+/// that breaks off is not. A connection dropped mid-body is an error here; a
+/// body that ends cleanly before the provider's final event is caught by the
+/// provider with `ended_early`.
+pub fn send_lines(
+    settings: &ProviderSettings,
+    request: RequestBuilder,
+    body: &serde_json::Value,
+    mut on_line: impl FnMut(&str) -> Result<()>,
+) -> Result<()> {
+    let response = super::send(settings, request, body)?;
+    for line in BufReader::new(response).lines() {
+        let line = line.with_context(|| format!("Failed to read the response from {}", settings.name))?;
+        log::trace!("{} stream: {}", settings.name, redact_secrets(&line));
+        on_line(&line)?;
+    }
+    Ok(())
+}
+
+/// The error for a stream that ended before the provider said it was done
+pub fn ended_early(settings: &ProviderSettings) -> anyhow::Error {
+    anyhow::anyhow!("The response from {} ended early", settings.name)
+}
+
+/// The payload of a server-sent event `data:` line
+pub fn sse_data(line: &str) -> Option<&str> {
+    line.strip_prefix("data:").map(str::trim_start)
+}
+
+/// Prints one string field of a JSON reply to stdout while the reply streams
+/// in, so the user sees generated text rather than JSON
+pub struct FieldPrinter {
+    /// Matches the field's key up to the value's opening quote
+    value_start: Regex,
+    buffer: String,
+    /// Byte offset in `buffer` of the first undecoded character of the value
+    cursor: Option<usize>,
+    closed: bool,
+    /// Text printed for the current reply
+    printed: String,
+}
+
+impl FieldPrinter {
+    pub fn new(field: &str) -> Self {
+        FieldPrinter {
+            value_start: Regex::new(&format!(r#""{}"\s*:\s*""#, regex::escape(field))).unwrap(),
+            buffer: String::new(),
+            cursor: None,
+            closed: false,
+            printed: String::new(),
+        }
+    }
+
+    /// Start on a new reply (a repair or another provider), on a fresh line if
+    /// the previous one printed anything
+    pub fn restart(&mut self) {
+        if !self.printed.is_empty() {
+            println!();
+        }
+        self.buffer.clear();
+        self.cursor = None;
+        self.closed = false;
+        self.printed.clear();
+    }
+
+    /// Add the next piece of the reply and print whatever of the field it completes
+    pub fn push(&mut self, text: &str) {
+        self.buffer.push_str(text);
+        if self.closed {
+            return;
+        }
+        if self.cursor.is_none() {
+            self.cursor = self.value_start.find(&self.buffer).map(|m| m.end());
+        }
+        let Some(cursor) = self.cursor else {
+            return;
+        };
+
+        let (decoded, consumed, closed) = decode_partial(&self.buffer[cursor..]);
+        self.cursor = Some(cursor + consumed);
+        self.closed = closed;
+        if !decoded.is_empty() {
+            print!("{}", decoded);
+            let _ = io::stdout().flush();
+            self.printed.push_str(&decoded);
+        }
+    }
+
+    /// Make sure the field's final value is on screen, e.g. when the provider
+    /// could not stream or the printed text was not the value that was used
+    pub fn finish(&self, value: &str) -> Result<()> {
+        let mut stdout = io::stdout();
+        match value.strip_prefix(self.printed.as_str()) {
+            Some(rest) => write!(stdout, "{}", rest)?,
+            None => write!(stdout, "\n{}", value)?,
+        }
+        if !value.ends_with('\n') {
+            writeln!(stdout)?;
+        }
+        stdout.flush()?;
+        Ok(())
+    }
+}
+
+/// Decode the start of a JSON string's contents (after its opening quote).
+/// Returns the decoded text, the bytes consumed and whether the closing quote
+/// was reached. An escape cut off by the end of the input is left for later.
+fn decode_partial(text: &str) -> (String, usize, bool) {
+    let mut decoded = String::new();
+    let mut consumed = 0;
+    loop {
+        let rest = &text[consumed..];
+        let Some(c) = rest.chars().next() else {
+            return (decoded, consumed, false);
+        };
+        match c {
+            '"' => return (decoded, consumed + 1, true),
+            '\\' => match unescape(rest) {
+                Some((c, len)) => {
+                    decoded.push(c);
+                    consumed += len;
+                }
+                None => return (decoded, consumed, false),
+            },
+            c => {
+                decoded.push(c);
+                consumed += c.len_utf8();
+            }
+        }
+    }
+}
+
+/// The character for the escape sequence `text` starts with, and its length
+fn unescape(text: &str) -> Option<(char, usize)> {
+    let c = match text[1..].chars().next()? {
+        'n' => '\n',
+        't' => '\t',
+        'r' => '\r',
+        'b' => '\u{8}',
+        'f' => '\u{c}',
+        'u' => return unescape_unicode(text),
+        other => other,
+    };
+    Some((c, 2))
+}
+
+/// `\uXXXX`, combining a UTF-16 surrogate pair into one character
+fn unescape_unicode(text: &str) -> Option<(char, usize)> {
+    let hex = |at: usize| text.get(at..at + 4).and_then(|digits| u32::from_str_radix(digits, 16).ok());
+    let high = hex(2)?;
+    if !(0xD800..0xDC00).contains(&high) {
+        return Some((char::from_u32(high).unwrap_or(char::REPLACEMENT_CHARACTER), 6));
+    }
+
+    if text.len() < 12 {
+        return None;
+    }
+    match (text.get(6..8), hex(8)) {
+        (Some("\\u"), Some(low)) if (0xDC00..0xE000).contains(&low) => {
+            let code = 0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00);
+            Some((char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER), 12))
+        }
+        _ => Some((char::REPLACEMENT_CHARACTER, 6)),
+    }
+}
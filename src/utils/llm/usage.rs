@@ -0,0 +1,145 @@
+use super::ProviderSettings;
+use crate::config::model::{BudgetAction, BudgetConfig};
+use crate::state::store::{LlmUsage, StateStore};
+use anyhow::Result;
+use chrono::{Datelike, Local, SecondsFormat, TimeZone, Utc};
+use std::fmt;
+
+/// Tokens used by one call, as reported by the provider
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Usage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+}
+
+/// US dollars per million input and output tokens for the built-in providers'
+/// common models, by model name prefix (more specific names first)
+const PRICES: &[(&str, f64, f64)] = &[
+    ("gpt-4o-mini", 0.15, 0.60),
+    ("gpt-4o", 2.50, 10.00),
+    ("gpt-4.1-mini", 0.40, 1.60),
+    ("gpt-4.1", 2.00, 8.00),
+    ("claude-3-5-haiku", 0.80, 4.00),
+    ("claude-3-5-sonnet", 3.00, 15.00),
+    ("claude-3-7-sonnet", 3.00, 15.00),
+    ("gemini-2.0-flash", 0.10, 0.40),
+    ("gemini-1.5-pro", 1.25, 5.00),
+];
+
+/// Input and output prices for a built-in provider's model, if known
+pub fn built_in_prices(model: &str) -> Option<(f64, f64)> {
+    PRICES
+        .iter()
+        .find(|(prefix, _, _)| model.starts_with(prefix))
+        .map(|&(_, input, output)| (input, output))
+}
+
+/// Cost of a call in US dollars, when the model's prices are known
+pub fn cost(settings: &ProviderSettings, usage: Usage) -> Option<f64> {
+    let input = settings.input_price?;
+    let output = settings.output_price?;
+    Some((usage.input_tokens as f64 * input + usage.output_tokens as f64 * output) / 1_000_000.0)
+}
+
+/// A time in the format usage is stored in
+pub fn timestamp<Tz: TimeZone>(time: chrono::DateTime<Tz>) -> String {
+    time.with_timezone(&Utc).to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Record a call's tokens and cost; cache hits cost nothing
+pub fn record(state: &mut dyn StateStore, command: &str, settings: &ProviderSettings, usage: Usage, cached: bool) -> Result<()> {
+    state.save_llm_usage(LlmUsage {
+        command: command.to_string(),
+        provider: settings.name.clone(),
+        model: settings.model.clone(),
+        input_tokens: usage.input_tokens,
+        output_tokens: usage.output_tokens,
+        cost: if cached { Some(0.0) } else { cost(settings, usage) },
+        cached,
+        created_at: timestamp(Utc::now()),
+    })
+}
+
+/// Total known cost of the calls made since `since`
+pub fn spent_since(state: &dyn StateStore, since: &str) -> Result<f64> {
+    Ok(total_cost(&state.list_llm_usage(since)?))
+}
+
+/// Sum of the known costs (an empty `f64` sum would be -0.0)
+pub fn total_cost<'a>(calls: impl IntoIterator<Item = &'a LlmUsage>) -> f64 {
+    calls.into_iter().filter_map(|usage| usage.cost).fold(0.0, |total, cost| total + cost)
+}
+
+/// Start of today and of this month, in local time
+pub fn budget_periods() -> (String, String) {
+    let now = Local::now();
+    let today = now.date_naive().and_hms_opt(0, 0, 0).unwrap();
+    let month = today.with_day(1).unwrap();
+    let start = |naive: chrono::NaiveDateTime| {
+        Local
+            .from_local_datetime(&naive)
+            .earliest()
+            .map(timestamp)
+            .unwrap_or_else(|| timestamp(Utc.from_utc_datetime(&naive)))
+    };
+    (start(today), start(month))
+}
+
+/// Budgets that have been reached, e.g. "daily budget ($1.20 of $1.00)"
+pub fn exceeded(state: &dyn StateStore, budget: &BudgetConfig) -> Result<Vec<String>> {
+    if budget.daily_usd.is_none() && budget.monthly_usd.is_none() {
+        return Ok(Vec::new());
+    }
+
+    let (today, month) = budget_periods();
+    let mut exceeded = Vec::new();
+    if let Some(limit) = budget.daily_usd {
+        let spent = spent_since(state, &today)?;
+        if spent >= limit {
+            exceeded.push(format!("daily budget (${:.2} of ${:.2})", spent, limit));
+        }
+    }
+    if let Some(limit) = budget.monthly_usd {
+        let spent = spent_since(state, &month)?;
+        if spent >= limit {
+            exceeded.push(format!("monthly budget (${:.2} of ${:.2})", spent, limit));
+        }
+    }
+    Ok(exceeded)
+}
+
+/// Error for a call refused because a budget is used up; other providers are not tried
+#[derive(Debug)]
+pub struct BudgetExceeded(String);
+
+impl fmt::Display for BudgetExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "LLM {} reached. Raise [llm.budget] in ~/.dev/config.toml or set on_exceed = \"warn\"",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for BudgetExceeded {}
+
+/// Warn about or refuse a call once a budget is used up. Returns whether a
+/// warning was printed, so it is only shown once per command.
+pub fn check_budget(state: &dyn StateStore, budget: &BudgetConfig, warned: bool) -> Result<bool> {
+    let exceeded = exceeded(state, budget)?;
+    if exceeded.is_empty() {
+        return Ok(false);
+    }
+
+    let reached = exceeded.join(" and ");
+    match budget.on_exceed {
+        BudgetAction::Warn => {
+            if !warned {
+                eprintln!("⚠️  LLM {} reached", reached);
+            }
+            Ok(true)
+        }
+        BudgetAction::Refuse => Err(BudgetExceeded(reached).into()),
+    }
+}
@@ -1 +1,3 @@
 pub mod llm;
+pub mod redact;
+pub mod trace;
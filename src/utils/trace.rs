@@ -0,0 +1,104 @@
+use crate::utils::redact::redact_secrets;
+use anyhow::{Context, Result};
+use serde_json::json;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{Child, Command, ExitStatus, Output};
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+/// File given with `--trace-file`; one JSON object per line
+static TRACE_FILE: OnceLock<Mutex<File>> = OnceLock::new();
+
+/// Append JSON traces of LLM calls and subprocesses to `path`
+pub fn init(path: &Path) -> Result<()> {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open trace file {:?}", path))?;
+    let _ = TRACE_FILE.set(Mutex::new(file));
+    Ok(())
+}
+
+/// Write one trace event. `fields` should already be redacted.
+pub fn record(kind: &str, fields: serde_json::Value) {
+    let Some(file) = TRACE_FILE.get() else {
+        return;
+    };
+
+    let mut event = json!({
+        "time": chrono::Local::now().to_rfc3339(),
+        "kind": kind,
+    });
+    if let (Some(event), serde_json::Value::Object(fields)) = (event.as_object_mut(), fields) {
+        event.extend(fields);
+    }
+
+    let mut file = file.lock().unwrap();
+    if let Err(e) = writeln!(file, "{}", event) {
+        log::warn!("Failed to write trace event: {}", e);
+    }
+}
+
+/// `std::process::Command` runners that log the command and record it in the trace file
+pub trait TracedCommand {
+    fn traced_status(&mut self) -> io::Result<ExitStatus>;
+    fn traced_output(&mut self) -> io::Result<Output>;
+    /// The trace records the spawn only; the child's exit is not tracked
+    fn traced_spawn(&mut self) -> io::Result<Child>;
+}
+
+impl TracedCommand for Command {
+    fn traced_status(&mut self) -> io::Result<ExitStatus> {
+        let started = Instant::now();
+        log::debug!("Running {}", command_line(self));
+        let result = self.status();
+        record_process(self, "status", started, result.as_ref().map(|status| status.code()).map_err(|e| e.to_string()));
+        result
+    }
+
+    fn traced_output(&mut self) -> io::Result<Output> {
+        let started = Instant::now();
+        log::debug!("Running {}", command_line(self));
+        let result = self.output();
+        record_process(self, "output", started, result.as_ref().map(|output| output.status.code()).map_err(|e| e.to_string()));
+        result
+    }
+
+    fn traced_spawn(&mut self) -> io::Result<Child> {
+        let started = Instant::now();
+        log::debug!("Spawning {}", command_line(self));
+        let result = self.spawn();
+        record_process(self, "spawn", started, result.as_ref().map(|_| None).map_err(|e| e.to_string()));
+        result
+    }
+}
+
+fn command_line(command: &Command) -> String {
+    let mut parts = vec![command.get_program().to_string_lossy().to_string()];
+    parts.extend(command.get_args().map(|arg| arg.to_string_lossy().to_string()));
+    redact_secrets(&parts.join(" "))
+}
+
+fn record_process(command: &Command, mode: &str, started: Instant, result: Result<Option<i32>, String>) {
+    let duration_ms = started.elapsed().as_millis() as u64;
+    match &result {
+        Ok(code) => log::debug!("{} finished with {:?} in {} ms", command.get_program().to_string_lossy(), code, duration_ms),
+        Err(e) => log::debug!("{} failed to start: {}", command.get_program().to_string_lossy(), e),
+    }
+
+    let cwd = command.get_current_dir().map(|dir| dir.display().to_string());
+    let mut fields = json!({
+        "command": command_line(command),
+        "cwd": cwd,
+        "mode": mode,
+        "duration_ms": duration_ms,
+    });
+    match result {
+        Ok(code) => fields["exit_code"] = json!(code),
+        Err(e) => fields["error"] = json!(e),
+    }
+    record("process", fields);
+}